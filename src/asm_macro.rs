@@ -1,8 +1,239 @@
-use crate::datum::{Datum};
+use crate::diagnostic::Diagnostic;
+use crate::expression::evaluate;
+use crate::source::{SourceLine, SourceLocation};
+use crate::symbol::SymbolTable;
+use crate::utils::split_arguments;
 
-pub fn disassemble_macro(code: &str, _data: &[Datum]) -> Option<Vec<String>> {
+pub struct MacroParameter {
+    name: String,
+    default: Option<String>,
+    required: bool,
+    vararg: bool,
+}
+
+pub struct MacroDefinition {
+    pub name: String,
+    parameters: Vec<MacroParameter>,
+    body: Vec<SourceLine>,
+}
+
+impl MacroDefinition {
+    pub fn parse(header: &str, body: &[SourceLine], location: &SourceLocation) -> Result<Self, Diagnostic> {
+        let header = header.trim();
+        let name_end = header.find(|c: char| c.is_whitespace() || c == ',').unwrap_or(header.len());
+        let name = &header[..name_end];
+        if name.is_empty() {
+            return Err(Diagnostic::error("`.macro` requires a name", Some(location)));
+        }
+
+        let rest = header[name_end..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        let parameter_texts = if rest.contains(',') {
+            split_arguments(rest)
+        } else {
+            rest.split_whitespace().map(|text| text.to_string()).collect()
+        };
+
+        let mut parameters: Vec<MacroParameter> = vec![];
+        for text in parameter_texts.iter() {
+            if parameters.last().is_some_and(|parameter| parameter.vararg) {
+                let message = format!("`:vararg` parameter must be the last parameter of macro `{}`", name);
+                return Err(Diagnostic::error(&message, Some(location)));
+            }
+            parameters.push(parse_parameter(text, location)?);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            parameters,
+            body: body.to_vec(),
+        })
+    }
+
+    pub fn expand(&self, arguments: &str, counter: usize, location: &SourceLocation) -> Result<Vec<SourceLine>, Diagnostic> {
+        let values = self.bind_arguments(arguments, location)?;
+        Ok(self
+            .body
+            .iter()
+            .map(|line| SourceLine::new(&substitute_parameters(&line.text, &values, Some(counter)), line.location.clone()))
+            .collect())
+    }
+
+    fn bind_arguments(&self, arguments: &str, location: &SourceLocation) -> Result<Vec<(String, String)>, Diagnostic> {
+        let mut values: Vec<Option<String>> = vec![None; self.parameters.len()];
+        let mut position = 0;
+
+        let argument_texts = split_arguments(arguments);
+        for (index, argument) in argument_texts.iter().enumerate() {
+            if let Some(keyword) = self.find_keyword_argument(argument) {
+                let (_, value) = argument.split_at(argument.find('=').unwrap() + 1);
+                values[keyword] = Some(value.trim().to_string());
+                continue;
+            }
+            match self.parameters.get(position) {
+                Some(parameter) if parameter.vararg => {
+                    values[position] = Some(argument_texts[index..].join(", "));
+                    break;
+                }
+                Some(_) => {
+                    if !argument.is_empty() {
+                        values[position] = Some(argument.clone());
+                    }
+                    position += 1;
+                }
+                None => {
+                    let message = format!("too many arguments for macro `{}`", self.name);
+                    return Err(Diagnostic::error(&message, Some(location)));
+                }
+            }
+        }
+
+        self.parameters
+            .iter()
+            .zip(values)
+            .map(|(parameter, value)| match value.or_else(|| parameter.default.clone()) {
+                Some(value) => Ok((parameter.name.clone(), value)),
+                None if parameter.required => {
+                    let message = format!("missing value for required parameter `{}` of macro `{}`", parameter.name, self.name);
+                    Err(Diagnostic::error(&message, Some(location)))
+                }
+                None => Ok((parameter.name.clone(), String::new())),
+            })
+            .collect()
+    }
+
+    fn find_keyword_argument(&self, argument: &str) -> Option<usize> {
+        let (name, _) = argument.split_at(argument.find('=')?);
+        let name = name.trim();
+        self.parameters.iter().position(|parameter| parameter.name == name)
+    }
+}
+
+fn parse_parameter(text: &str, location: &SourceLocation) -> Result<MacroParameter, Diagnostic> {
+    let (declaration, default) = match text.find('=') {
+        Some(index) => (text[..index].trim(), Some(text[index + 1..].trim().to_string())),
+        None => (text.trim(), None),
+    };
+    let (name, qualifier) = match declaration.find(':') {
+        Some(index) => (&declaration[..index], Some(&declaration[index + 1..])),
+        None => (declaration, None),
+    };
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        let message = format!("invalid macro parameter `{}`", text);
+        return Err(Diagnostic::error(&message, Some(location)));
+    }
+
+    let (required, vararg) = match qualifier {
+        None => (false, false),
+        Some("req") => (true, false),
+        Some("vararg") => (false, true),
+        Some(qualifier) => {
+            let message = format!("unknown qualifier `:{}` for macro parameter `{}`", qualifier, name);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+    };
+
+    Ok(MacroParameter {
+        name: name.to_string(),
+        default,
+        required,
+        vararg,
+    })
+}
+
+fn substitute_parameters(text: &str, values: &[(String, String)], counter: Option<usize>) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(index) = rest.find('\\') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
+
+        if let (Some(stripped), Some(counter)) = (rest.strip_prefix('@'), counter) {
+            result.push_str(&counter.to_string());
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix("()") {
+            rest = stripped;
+        } else {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            match values.iter().find(|(name, _)| name == &rest[..length]) {
+                Some((_, value)) => result.push_str(value),
+                None => {
+                    result.push('\\');
+                    result.push_str(&rest[..length]);
+                }
+            }
+            rest = &rest[length..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+pub enum Repetition {
+    Count(i32),
+    Values(String, Vec<String>),
+}
+
+impl Repetition {
+    pub fn parse(directive: &str, arguments: &str, symbols: &SymbolTable, location: &SourceLocation) -> Result<Self, Diagnostic> {
+        match directive {
+            ".rept" => match evaluate(arguments, &|name| symbols.get(name)) {
+                Ok(count) if count < 0 => {
+                    let message = format!("negative repeat count {}", count);
+                    Err(Diagnostic::error(&message, Some(location)))
+                }
+                Ok(count) => Ok(Repetition::Count(count)),
+                Err(message) => {
+                    let message = format!("invalid repeat count: {}", message);
+                    Err(Diagnostic::error(&message, Some(location)))
+                }
+            },
+            ".irp" | ".irpc" => {
+                let (name, values) = match arguments.find(|c: char| c == ',' || c.is_whitespace()) {
+                    Some(index) => (&arguments[..index], arguments[index..].trim_start_matches(|c: char| c == ',' || c.is_whitespace())),
+                    None => (arguments, ""),
+                };
+                if name.is_empty() {
+                    let message = format!("`{}` requires a parameter name", directive);
+                    return Err(Diagnostic::error(&message, Some(location)));
+                }
+                let values = if directive == ".irp" {
+                    split_arguments(values)
+                } else {
+                    values.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_string()).collect()
+                };
+                Ok(Repetition::Values(name.to_string(), values))
+            }
+            _ => panic!("Unknown repetition directive {}.", directive),
+        }
+    }
+
+    pub fn expand(&self, body: &[SourceLine]) -> Vec<SourceLine> {
+        match self {
+            Repetition::Count(count) => (0..*count).flat_map(|_| body.iter().cloned()).collect(),
+            Repetition::Values(name, values) => {
+                // an empty list still expands the body once, with an empty value
+                let values = if values.is_empty() { vec![String::new()] } else { values.clone() };
+                values
+                    .iter()
+                    .flat_map(|value| {
+                        let binding = [(name.clone(), value.clone())];
+                        body.iter()
+                            .map(|line| SourceLine::new(&substitute_parameters(&line.text, &binding, None), line.location.clone()))
+                            .collect::<Vec<SourceLine>>()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+pub fn disassemble_macro(code: &str) -> Option<Vec<String>> {
     let contents = code.trim_start().split('\t').collect::<Vec<&str>>();
-    if contents.len() > 0 {
+    if !contents.is_empty() {
         let name = contents[0];
         match name {
             "nop" => Some(nop()),
@@ -14,7 +245,5 @@ pub fn disassemble_macro(code: &str, _data: &[Datum]) -> Option<Vec<String>> {
 }
 
 fn nop() -> Vec<String> {
-    let mut result: Vec<String> = vec![];
-    result.push(format!("add\tr0,r0,r0"));
-    result
+    vec!["add\tr0,r0,r0".to_string()]
 }
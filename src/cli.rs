@@ -0,0 +1,565 @@
+use std::path::{Path, PathBuf};
+
+use crate::constants::{DATA_SECTION_MIN_ADDRESS, INSTRUCTION_TABLE, TEXT_SECTION_MIN_ADDRESS};
+use crate::emit::EMIT_KINDS;
+use crate::export::ExportFormat;
+use crate::expression::evaluate;
+use crate::map::MAP_FORMATS;
+use crate::image::AddressUnit;
+use crate::output::{OutputOptions, DEFAULT_LINK_FORMAT, DEFAULT_OUTPUT_FORMAT, OUTPUT_FORMATS};
+use crate::simulator::DEFAULT_MAX_STEPS;
+
+// Exit status of a run stopped by errors in the sources, objects or images.
+pub const EXIT_FAILURE: i32 = 1;
+// Exit status of a run stopped by invalid arguments.
+pub const EXIT_USAGE: i32 = 2;
+
+// File name standing for the standard input or output.
+pub const STANDARD_STREAM: &str = "-";
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    QUIET,
+    NORMAL,
+    VERBOSE,
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum Command {
+    Help,
+    Version,
+    Assemble(Options),
+    Disassemble(DisassembleOptions),
+    Link(LinkOptions),
+    Explain(ExplainOptions),
+    Simulate(SimulateOptions),
+}
+
+pub struct Options {
+    pub input_filepaths: Vec<String>,
+    pub output_filepath: String,
+    pub text_start_address: i32,
+    pub data_start_address: i32,
+    pub definitions: Vec<(String, i32)>,
+    pub include_paths: Vec<PathBuf>,
+    pub preprocess: bool,
+    pub format: String,
+    pub output_options: OutputOptions,
+    pub listing_filepath: Option<String>,
+    pub map_options: MapOptions,
+    // one of `EMIT_KINDS`, what the output file holds
+    pub emit: String,
+}
+
+// `disasm` command: a flat binary image whose first word is at the text base. `symbolic` writes
+// a labelled source instead of a listing, with the names of the `symbols_filepath` file.
+pub struct DisassembleOptions {
+    pub input_filepath: String,
+    pub output_filepath: String,
+    pub text_start_address: i32,
+    pub symbolic: bool,
+    pub symbols_filepath: Option<String>,
+}
+
+// `explain` command: an assembly line or a hexadecimal word, at `address` for branch offsets.
+pub struct ExplainOptions {
+    pub input: String,
+    pub address: i32,
+}
+
+// `sim` command: a memory image read like `disasm` reads it, run from `entry`, else from the reset
+// address when one is given, else from the entry point of an ELF executable or the first word of
+// the image. `irqs` are the IRQ lines raised with the step they are raised at.
+pub struct SimulateOptions {
+    pub input_filepath: String,
+    pub text_start_address: i32,
+    pub entry: Option<i32>,
+    pub max_steps: u64,
+    pub trace: bool,
+    pub reset_address: Option<i32>,
+    pub exception_address: Option<i32>,
+    pub irqs: Vec<(u32, u64)>,
+    pub unimplemented: Vec<&'static str>,
+}
+
+// `link` command: relocatable objects written by the `elf-obj` format, laid out from the two bases.
+pub struct LinkOptions {
+    pub input_filepaths: Vec<String>,
+    pub output_filepath: String,
+    pub text_start_address: i32,
+    pub data_start_address: i32,
+    pub format: String,
+    pub output_options: OutputOptions,
+    pub map_options: MapOptions,
+}
+
+// Where the sections and symbols end up, written to `filepath` in one of `MAP_FORMATS`, and the
+// files the exported symbols are written to, in the format their extension tells.
+pub struct MapOptions {
+    pub filepath: Option<String>,
+    pub format: String,
+    pub export_filepaths: Vec<String>,
+}
+
+impl Default for MapOptions {
+    fn default() -> Self {
+        Self {
+            filepath: None,
+            format: MAP_FORMATS[0].to_string(),
+            export_filepaths: vec![],
+        }
+    }
+}
+
+pub fn usage(program: &str) -> String {
+    let formats = OUTPUT_FORMATS
+        .iter()
+        .map(|format| format!("  {:<28} {}", format.name(), format.description()))
+        .collect::<Vec<String>>();
+    format!(
+        "Usage:\n\
+         \x20 {0} [asm] [options] -o output_file input_file...\n\
+         \x20 {0} [asm] [options] input_file output_file (text_min_address) (data_min_address)\n\
+         \x20 {0} disasm [--text-base ADDRESS] [--symbolic] [--symbols FILE] [-o output_file] input_file\n\
+         \x20 {0} link [options] -o output_file object_file...\n\
+         \x20 {0} explain [--address ADDRESS] instruction_or_word\n\
+         \x20 {0} sim [--text-base ADDRESS] [--entry ADDRESS] [--max-steps N] [--trace] [vectors] image_file\n\
+         \n\
+         `-` stands for the standard input or output.\n\n{1}\n\nOutput formats:\n{2}",
+        program,
+        USAGE_OPTIONS,
+        formats.join("\n")
+    )
+}
+
+const USAGE_OPTIONS: &str = "Options:
+  -h, --help                   print this help
+      --version                print the version
+  -q, --quiet                  do not print warnings
+  -v, --verbose                print the progress of each step
+  -o, --output FILE            write the output to FILE, every other argument is an input file
+  -a, --listing FILE           write a listing with addresses, encodings and symbols to FILE
+      --map FILE               write the sections and symbols with their addresses to FILE
+      --map-format FORMAT      write the map as `text`, `json` or `nm` output (default: text)
+      --export FILE            write the global and `.export` symbols as constants to a C header
+                               (`.h`), SystemVerilog package (`.sv`) or VHDL package (`.vhd`) FILE
+      --text-base ADDRESS      start address of the `.text` section (default: 0x0)
+      --data-base ADDRESS      start address of the `.data` section (default: 0x100)
+      --symbolic               disassemble to a source with labels that assembles back to the image
+      --symbols FILE           name the addresses listed in the `nm`-style FILE (implies --symbolic)
+      --address ADDRESS        address of the instruction given to `explain` (default: 0x0)
+  -O, --format FORMAT          write the output in FORMAT (default: logisim-raw, `elf` for `link`)
+      --emit json              write the statements, data, sections, symbols and diagnostics as
+                               a JSON document instead of the memory image (default: image)
+      --record-length BYTES    data bytes per record for record-based formats (default: 16)
+      --word-width BITS        memory word width for word-addressed formats (default: 32)
+      --depth WORDS            memory depth for the `mif` and `vhdl` formats (default: inferred)
+      --comments               annotate instructions with their disassembly in the `mif` format
+      --entry SYMBOL           entry point for the `srec` and `elf` formats (default: `_start`),
+                               or the ADDRESS `sim` starts at
+      --max-steps N            instructions `sim` runs before giving up (default: 1000000)
+      --trace                  print each instruction `sim` runs
+      --reset-address ADDRESS  reset vector of `sim`, where it starts without --entry
+                               (default: the `.reset` section, else 0x0)
+      --exception-address ADDRESS
+                               exception vector of `sim` (default: the `.exceptions` section,
+                               else 0x8)
+      --irq LINE@STEP          raise IRQ LINE after STEP instructions, until the handler's `eret`
+      --unimplemented LIST     comma-separated instructions raising an unimplemented
+                               instruction exception in `sim`, like `mul,div,divu`
+  -g, --debug-line             add `.debug_line` source line information to the `elf` format
+      --fill BYTE              byte used to pad the gaps of the `bin` format (default: 0)
+  -D, --defsym NAME[=VALUE]    define the symbol NAME (VALUE defaults to 1)
+  -I, --include-dir DIR        search DIR for `.include`, `.incbin` and `#include` files
+      --cpp                    run the C preprocessor first (default for `.S` files)
+
+Exit status: 0 on success, 1 when the input has errors, 2 when the arguments are invalid.";
+
+// Picks the command from the first argument, `asm` when it names none. `--help`, `--version`,
+// `--quiet` and `--verbose` are accepted anywhere.
+pub fn parse_command(args: &[String]) -> Result<(Command, Verbosity), String> {
+    let mut verbosity = Verbosity::NORMAL;
+    let mut arguments = vec![];
+    for argument in args.iter().skip(1) {
+        match argument.as_str() {
+            "-h" | "--help" => return Ok((Command::Help, verbosity)),
+            "--version" => return Ok((Command::Version, verbosity)),
+            "-q" | "--quiet" => verbosity = Verbosity::QUIET,
+            "-v" | "--verbose" => verbosity = Verbosity::VERBOSE,
+            _ => arguments.push(argument.clone()),
+        }
+    }
+
+    let command = match arguments.first().map(|argument| argument.as_str()) {
+        None => return Err("no input files".to_string()),
+        Some("asm") => Command::Assemble(parse_arguments(&arguments[1..])?),
+        Some("disasm") => Command::Disassemble(parse_disassemble_arguments(&arguments[1..])?),
+        Some("link") => Command::Link(parse_link_arguments(&arguments[1..])?),
+        Some("explain") => Command::Explain(parse_explain_arguments(&arguments[1..])?),
+        Some("sim") => Command::Simulate(parse_simulate_arguments(&arguments[1..])?),
+        Some(_) => Command::Assemble(parse_arguments(&arguments)?),
+    };
+    Ok((command, verbosity))
+}
+
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut positionals: Vec<&str> = vec![];
+    let mut output_filepath = None;
+    let mut text_start_address = None;
+    let mut data_start_address = None;
+    let mut definitions = vec![];
+    let mut include_paths = vec![];
+    let mut preprocess = false;
+    let mut format = DEFAULT_OUTPUT_FORMAT.to_string();
+    let mut output_options = OutputOptions::default();
+    let mut listing_filepath = None;
+    let mut map_options = MapOptions::default();
+    let mut emit = EMIT_KINDS[0].to_string();
+    let mut arguments = args.iter();
+
+    while let Some(argument) = arguments.next() {
+        if let Some(value) = option_value(argument, &["-o", "--output"], &mut arguments)? {
+            output_filepath = Some(value);
+        } else if let Some(value) = option_value(argument, &["-a", "--listing"], &mut arguments)? {
+            listing_filepath = Some(value);
+        } else if let Some(value) = option_value(argument, &["--emit"], &mut arguments)? {
+            if !EMIT_KINDS.contains(&value.as_str()) {
+                return Err(format!("cannot emit `{}`, expected `image` or `json`", value));
+            }
+            emit = value;
+        } else if let Some(value) = option_value(argument, &["--text-base"], &mut arguments)? {
+            text_start_address = Some(parse_address(&value)?);
+        } else if let Some(value) = option_value(argument, &["--data-base"], &mut arguments)? {
+            data_start_address = Some(parse_address(&value)?);
+        } else if let Some(value) = option_value(argument, &["-D", "--defsym"], &mut arguments)? {
+            definitions.push(parse_definition(&value)?);
+        } else if let Some(value) = option_value(argument, &["-I", "--include-dir"], &mut arguments)? {
+            include_paths.push(PathBuf::from(value));
+        } else if parse_output_option(argument, &mut arguments, &mut format, &mut output_options)?
+            || parse_map_option(argument, &mut arguments, &mut map_options)?
+        {
+            continue;
+        } else if argument == "--cpp" {
+            preprocess = true;
+        } else if is_option(argument) && !is_number(argument) {
+            return Err(format!("unknown option `{}`", argument));
+        } else {
+            positionals.push(argument);
+        }
+    }
+
+    // without `-o`, the historical form: one input, the output and optionally both start addresses
+    let (input_filepaths, output_filepath, (text, data)) = match output_filepath {
+        Some(output) => (positionals, output, (None, None)),
+        None => match positionals[..] {
+            [input, output] => (vec![input], output.to_string(), (None, None)),
+            [input, output, text, data] => {
+                (vec![input], output.to_string(), (Some(parse_address(text)?), Some(parse_address(data)?)))
+            }
+            _ => return Err("expected input files and an output file given with `-o`".to_string()),
+        },
+    };
+    if input_filepaths.is_empty() {
+        return Err("no input files".to_string());
+    }
+
+    Ok(Options {
+        input_filepaths: input_filepaths.iter().map(|filepath| filepath.to_string()).collect(),
+        output_filepath,
+        text_start_address: text_start_address.or(text).unwrap_or(TEXT_SECTION_MIN_ADDRESS),
+        data_start_address: data_start_address.or(data).unwrap_or(DATA_SECTION_MIN_ADDRESS),
+        definitions,
+        include_paths,
+        preprocess,
+        format,
+        output_options,
+        listing_filepath,
+        map_options,
+        emit,
+    })
+}
+
+fn parse_link_arguments(args: &[String]) -> Result<LinkOptions, String> {
+    let mut input_filepaths = vec![];
+    let mut output_filepath = None;
+    let mut text_start_address = TEXT_SECTION_MIN_ADDRESS;
+    let mut data_start_address = DATA_SECTION_MIN_ADDRESS;
+    let mut format = DEFAULT_LINK_FORMAT.to_string();
+    let mut output_options = OutputOptions::default();
+    let mut map_options = MapOptions::default();
+    let mut arguments = args.iter();
+
+    while let Some(argument) = arguments.next() {
+        if let Some(value) = option_value(argument, &["-o", "--output"], &mut arguments)? {
+            output_filepath = Some(value);
+        } else if let Some(value) = option_value(argument, &["--text-base"], &mut arguments)? {
+            text_start_address = parse_address(&value)?;
+        } else if let Some(value) = option_value(argument, &["--data-base"], &mut arguments)? {
+            data_start_address = parse_address(&value)?;
+        } else if parse_output_option(argument, &mut arguments, &mut format, &mut output_options)?
+            || parse_map_option(argument, &mut arguments, &mut map_options)?
+        {
+            continue;
+        } else if is_option(argument) {
+            return Err(format!("unknown option `{}`", argument));
+        } else {
+            input_filepaths.push(argument.clone());
+        }
+    }
+
+    let output_filepath = output_filepath.ok_or_else(|| "`link` requires an output file given with `-o`".to_string())?;
+    if input_filepaths.is_empty() {
+        return Err("`link` expects at least one object file".to_string());
+    }
+    // the relocations are applied in bytes, formats counting words would need every branch re-encoded
+    let linkable = OUTPUT_FORMATS
+        .iter()
+        .filter(|output_format| !output_format.relocatable() && output_format.address_unit() == AddressUnit::BYTE)
+        .map(|output_format| output_format.name())
+        .collect::<Vec<&str>>();
+    if !linkable.contains(&format.as_str()) {
+        return Err(format!("the `{}` format cannot hold a linked image, use {}", format, linkable.join(", ")));
+    }
+
+    Ok(LinkOptions {
+        input_filepaths,
+        output_filepath,
+        text_start_address,
+        data_start_address,
+        format,
+        output_options,
+        map_options,
+    })
+}
+
+fn parse_disassemble_arguments(args: &[String]) -> Result<DisassembleOptions, String> {
+    let mut input_filepaths = vec![];
+    let mut output_filepath = STANDARD_STREAM.to_string();
+    let mut text_start_address = TEXT_SECTION_MIN_ADDRESS;
+    let mut symbolic = false;
+    let mut symbols_filepath = None;
+    let mut arguments = args.iter();
+
+    while let Some(argument) = arguments.next() {
+        if let Some(value) = option_value(argument, &["-o", "--output"], &mut arguments)? {
+            output_filepath = value;
+        } else if let Some(value) = option_value(argument, &["--text-base"], &mut arguments)? {
+            text_start_address = parse_address(&value)?;
+        } else if argument == "--symbolic" {
+            symbolic = true;
+        } else if let Some(value) = option_value(argument, &["--symbols"], &mut arguments)? {
+            symbolic = true;
+            symbols_filepath = Some(value);
+        } else if is_option(argument) {
+            return Err(format!("unknown option `{}`", argument));
+        } else {
+            input_filepaths.push(argument.clone());
+        }
+    }
+
+    match input_filepaths.len() {
+        1 => Ok(DisassembleOptions {
+            input_filepath: input_filepaths.remove(0),
+            output_filepath,
+            text_start_address,
+            symbolic,
+            symbols_filepath,
+        }),
+        _ => Err("`disasm` expects one input file".to_string()),
+    }
+}
+
+fn parse_simulate_arguments(args: &[String]) -> Result<SimulateOptions, String> {
+    let mut input_filepaths = vec![];
+    let mut text_start_address = TEXT_SECTION_MIN_ADDRESS;
+    let mut entry = None;
+    let mut max_steps = DEFAULT_MAX_STEPS;
+    let mut trace = false;
+    let mut reset_address = None;
+    let mut exception_address = None;
+    let mut irqs = vec![];
+    let mut unimplemented = vec![];
+    let mut arguments = args.iter();
+
+    while let Some(argument) = arguments.next() {
+        if let Some(value) = option_value(argument, &["--text-base"], &mut arguments)? {
+            text_start_address = parse_address(&value)?;
+        } else if let Some(value) = option_value(argument, &["--entry"], &mut arguments)? {
+            entry = Some(parse_address(&value)?);
+        } else if let Some(value) = option_value(argument, &["--max-steps"], &mut arguments)? {
+            max_steps = parse_size(&value, 1, i32::MAX as usize)? as u64;
+        } else if argument == "--trace" {
+            trace = true;
+        } else if let Some(value) = option_value(argument, &["--reset-address"], &mut arguments)? {
+            reset_address = Some(parse_address(&value)?);
+        } else if let Some(value) = option_value(argument, &["--exception-address"], &mut arguments)? {
+            exception_address = Some(parse_address(&value)?);
+        } else if let Some(value) = option_value(argument, &["--irq"], &mut arguments)? {
+            let (line, step) = value.split_once('@').ok_or_else(|| format!("invalid IRQ `{}`, expected LINE@STEP", value))?;
+            irqs.push((parse_size(line, 0, 31)? as u32, parse_size(step, 0, i32::MAX as usize)? as u64));
+        } else if let Some(value) = option_value(argument, &["--unimplemented"], &mut arguments)? {
+            for name in value.split(',').map(str::trim) {
+                let instruction = INSTRUCTION_TABLE.get(name).ok_or_else(|| format!("unknown instruction `{}`", name))?;
+                unimplemented.push(instruction.name);
+            }
+        } else if is_option(argument) {
+            return Err(format!("unknown option `{}`", argument));
+        } else {
+            input_filepaths.push(argument.clone());
+        }
+    }
+
+    match input_filepaths.len() {
+        1 => Ok(SimulateOptions {
+            input_filepath: input_filepaths.remove(0),
+            text_start_address,
+            entry,
+            max_steps,
+            trace,
+            reset_address,
+            exception_address,
+            irqs,
+            unimplemented,
+        }),
+        _ => Err("`sim` expects one image file".to_string()),
+    }
+}
+
+// The words of the instruction may be separate arguments, and may look like options, as `-5` does.
+fn parse_explain_arguments(args: &[String]) -> Result<ExplainOptions, String> {
+    let mut words = vec![];
+    let mut address = 0;
+    let mut arguments = args.iter();
+
+    while let Some(argument) = arguments.next() {
+        if let Some(value) = option_value(argument, &["--address"], &mut arguments)? {
+            address = parse_address(&value)?;
+        } else {
+            words.push(argument.as_str());
+        }
+    }
+
+    if words.is_empty() {
+        return Err("`explain` expects an instruction or a hexadecimal word".to_string());
+    }
+    Ok(ExplainOptions {
+        input: words.join(" "),
+        address,
+    })
+}
+
+// Options selecting and configuring the output writer, shared by `asm` and `link`. Returns whether
+// `argument` was one of them.
+fn parse_output_option<'a>(
+    argument: &str,
+    arguments: &mut impl Iterator<Item = &'a String>,
+    format: &mut String,
+    output_options: &mut OutputOptions,
+) -> Result<bool, String> {
+    if let Some(value) = option_value(argument, &["-O", "--format"], arguments)? {
+        if !OUTPUT_FORMATS.iter().any(|output_format| output_format.name() == value) {
+            return Err(format!("unknown output format `{}`", value));
+        }
+        *format = value;
+    } else if let Some(value) = option_value(argument, &["--record-length"], arguments)? {
+        output_options.record_length = parse_size(&value, 1, 255)?;
+    } else if let Some(value) = option_value(argument, &["--word-width"], arguments)? {
+        output_options.word_width = parse_size(&value, 8, 32)?;
+    } else if let Some(value) = option_value(argument, &["--depth"], arguments)? {
+        output_options.depth = Some(parse_size(&value, 1, i32::MAX as usize)?);
+    } else if let Some(value) = option_value(argument, &["--entry"], arguments)? {
+        output_options.entry = Some(value);
+    } else if let Some(value) = option_value(argument, &["--fill"], arguments)? {
+        output_options.fill = parse_size(&value, 0, 255)? as u8;
+    } else if argument == "-g" || argument == "--debug-line" {
+        output_options.debug_line = true;
+    } else if argument == "--comments" {
+        output_options.comments = true;
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// `--map`, `--map-format` and `--export`, shared by `asm` and `link`. Returns whether `argument` was
+// one of them.
+fn parse_map_option<'a>(
+    argument: &str,
+    arguments: &mut impl Iterator<Item = &'a String>,
+    map_options: &mut MapOptions,
+) -> Result<bool, String> {
+    if let Some(value) = option_value(argument, &["--map-format"], arguments)? {
+        if !MAP_FORMATS.contains(&value.as_str()) {
+            return Err(format!("unknown map format `{}`", value));
+        }
+        map_options.format = value;
+    } else if let Some(value) = option_value(argument, &["--map"], arguments)? {
+        map_options.filepath = Some(value);
+    } else if let Some(value) = option_value(argument, &["--export"], arguments)? {
+        if ExportFormat::from_path(Path::new(&value)).is_none() {
+            return Err(format!("cannot tell the export format of `{}`, expected a `.h`, `.sv` or `.vhd` file", value));
+        }
+        map_options.export_filepaths.push(value);
+    } else {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+// Accepts `-D VALUE`, `-DVALUE`, `--defsym VALUE` and `--defsym=VALUE` forms.
+fn option_value<'a>(
+    argument: &str,
+    names: &[&str],
+    arguments: &mut impl Iterator<Item = &'a String>,
+) -> Result<Option<String>, String> {
+    for name in names {
+        if argument == *name {
+            return match arguments.next() {
+                Some(value) => Ok(Some(value.clone())),
+                None => Err(format!("option `{}` requires a value", name)),
+            };
+        }
+        if let Some(value) = argument.strip_prefix(name) {
+            if name.starts_with("--") {
+                if let Some(value) = value.strip_prefix('=') {
+                    return Ok(Some(value.to_string()));
+                }
+            } else if !value.is_empty() {
+                return Ok(Some(value.to_string()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn parse_definition(text: &str) -> Result<(String, i32), String> {
+    let (name, value) = match text.find('=') {
+        Some(index) => (&text[..index], parse_address(&text[index + 1..])?),
+        None => (text, 1),
+    };
+    if name.is_empty() {
+        return Err(format!("invalid symbol definition `{}`", text));
+    }
+    Ok((name.to_string(), value))
+}
+
+fn parse_address(text: &str) -> Result<i32, String> {
+    evaluate(text, &|_| None).map_err(|message| format!("invalid value `{}`: {}", text, message))
+}
+
+fn parse_size(text: &str, minimum: usize, maximum: usize) -> Result<usize, String> {
+    match parse_address(text)? {
+        value if (minimum as i32..=maximum as i32).contains(&value) => Ok(value as usize),
+        value => Err(format!("value {} is outside of {}..={}", value, minimum, maximum)),
+    }
+}
+
+// `-` alone is a file name.
+fn is_option(text: &str) -> bool {
+    text.starts_with('-') && text != STANDARD_STREAM
+}
+
+fn is_number(text: &str) -> bool {
+    text[1..].starts_with(|c: char| c.is_ascii_digit())
+}
@@ -3,14 +3,44 @@ use phf::{phf_map, Map};
 use crate::instruction::Instruction;
 
 pub const WORD_SIZE: i32 = 1;
+pub const WORD_BYTES: i32 = 4;
 pub const ZERO_REGISTER: i32 = 0;
 pub const AT_REGISTER: i32 = 1;
+pub const ET_REGISTER: i32 = 24;
+pub const BT_REGISTER: i32 = 25;
+pub const GP_REGISTER: i32 = 26;
 pub const SP_REGISTER: i32 = 28;
+pub const EA_REGISTER: i32 = 29;
+pub const BA_REGISTER: i32 = 30;
 pub const RA_REGISTER: i32 = 31;
 pub const R_INSTRUCTION_OPCODE: i32 = 0x3A;
 pub const DATA_SECTION_MIN_ADDRESS: i32 = 0x100;
 pub const TEXT_SECTION_MIN_ADDRESS: i32 = 0x0;
 
+// Register names accepted besides `rN`, and used by the disassembler.
+pub const REGISTER_NAMES: [(&str, i32); 9] = [
+    ("zero", ZERO_REGISTER),
+    ("at", AT_REGISTER),
+    ("et", ET_REGISTER),
+    ("bt", BT_REGISTER),
+    ("gp", GP_REGISTER),
+    ("sp", SP_REGISTER),
+    ("ea", EA_REGISTER),
+    ("ba", BA_REGISTER),
+    ("ra", RA_REGISTER),
+];
+
+// Control registers of `rdctl` and `wrctl`, also accepted as `ctlN`.
+pub const CONTROL_REGISTER_NAMES: [(&str, i32); 7] = [
+    ("status", 0),
+    ("estatus", 1),
+    ("bstatus", 2),
+    ("ienable", 3),
+    ("ipending", 4),
+    ("cpuid", 5),
+    ("exception", 7),
+];
+
 // https://www.intel.com/content/dam/www/programmable/us/en/pdfs/literature/hb/nios2/n2cpu_nii51017.pdf
 pub const INSTRUCTION_TABLE: Map<&'static str, &'static Instruction> = phf_map! {
  // J-Type
@@ -29,13 +59,19 @@ pub const INSTRUCTION_TABLE: Map<&'static str, &'static Instruction> = phf_map!
  "sth" => &Instruction::new("sth", 0x0D, 0x0),
  "bge" => &Instruction::new("bge", 0x0E, 0x0),
  "ldh" => &Instruction::new("ldh", 0x0F, 0x0),
+ "cmplti" => &Instruction::new("cmplti", 0x10, 0x0),
+ "initda" => &Instruction::new("initda", 0x13, 0x0),
+ "ori" => &Instruction::new("ori", 0x14, 0x0),
  "stw" => &Instruction::new("stw", 0x15, 0x0),
  "blt" => &Instruction::new("blt", 0x16, 0x0),
  "ldw" => &Instruction::new("ldw", 0x17, 0x0),
- "cmpnei" => &Instruction::new("cmpnei", 0x17, 0x0),
+ "cmpnei" => &Instruction::new("cmpnei", 0x18, 0x0),
  "flushda" => &Instruction::new("flushda", 0x1B, 0x0),
  "xori" => &Instruction::new("xori", 0x1C, 0x0),
  "bne" => &Instruction::new("bne", 0x1E, 0x0),
+ "cmpeqi" => &Instruction::new("cmpeqi", 0x20, 0x0),
+ "ldbuio" => &Instruction::new("ldbuio", 0x23, 0x0),
+ "muli" => &Instruction::new("muli", 0x24, 0x0),
  "stbio" => &Instruction::new("stbio", 0x25, 0x0),
  "beq" => &Instruction::new("beq", 0x26, 0x0),
  "ldbio" => &Instruction::new("ldbio", 0x27, 0x0),
@@ -44,7 +80,10 @@ pub const INSTRUCTION_TABLE: Map<&'static str, &'static Instruction> = phf_map!
  "andhi" => &Instruction::new("andhi", 0x2C, 0x0),
  "sthio" => &Instruction::new("sthio", 0x2D, 0x0),
  "bgeu" => &Instruction::new("bgeu", 0x2E, 0x0),
- "ldhio" => &Instruction::new("bldhio", 0x2F, 0x0),
+ "ldhio" => &Instruction::new("ldhio", 0x2F, 0x0),
+ "cmpltui" => &Instruction::new("cmpltui", 0x30, 0x0),
+ "initd" => &Instruction::new("initd", 0x33, 0x0),
+ "orhi" => &Instruction::new("orhi", 0x34, 0x0),
  "stwio" => &Instruction::new("stwio", 0x35, 0x0),
  "bltu" => &Instruction::new("bltu", 0x36, 0x0),
  "ldwio" => &Instruction::new("ldwio", 0x37, 0x0),
@@ -87,6 +126,7 @@ pub const INSTRUCTION_TABLE: Map<&'static str, &'static Instruction> = phf_map!
  "cmpgeu" => &Instruction::new("cmpgeu", R_INSTRUCTION_OPCODE, 0x28),
  "initi" => &Instruction::new("initi", R_INSTRUCTION_OPCODE, 0x29),
  "trap" => &Instruction::new("trap", R_INSTRUCTION_OPCODE, 0x2D),
+ "wrctl" => &Instruction::new("wrctl", R_INSTRUCTION_OPCODE, 0x2E),
  "cmpltu" => &Instruction::new("cmpltu", R_INSTRUCTION_OPCODE, 0x30),
  "add" => &Instruction::new("add", R_INSTRUCTION_OPCODE, 0x31),
  "break" => &Instruction::new("break", R_INSTRUCTION_OPCODE, 0x34),
@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::diagnostic::Diagnostic;
+use crate::expression::evaluate;
+use crate::source::{find_source_file, read_source_file, SourceLine, SourceLocation};
+
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+struct Define {
+    parameters: Option<Vec<String>>,
+    body: String,
+}
+
+struct Conditional {
+    parent_active: bool,
+    active: bool,
+    taken: bool,
+    location: SourceLocation,
+}
+
+// Remapping installed by `#line` and `# N "file"` markers.
+struct LineMarker {
+    file: Rc<PathBuf>,
+    delta: i64,
+}
+
+pub fn is_preprocessed_source(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "S")
+}
+
+#[derive(Default)]
+pub struct CPreprocessor {
+    defines: HashMap<String, Define>,
+    include_paths: Vec<PathBuf>,
+}
+
+impl CPreprocessor {
+    pub fn new(include_paths: &[PathBuf]) -> Self {
+        Self {
+            include_paths: include_paths.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    pub fn define(&mut self, name: &str, body: &str) {
+        let define = Define {
+            parameters: None,
+            body: body.to_string(),
+        };
+        self.defines.insert(name.to_string(), define);
+    }
+
+    pub fn run(&mut self, lines: &[SourceLine]) -> Result<Vec<SourceLine>, Diagnostic> {
+        let mut output = vec![];
+        self.process(lines, &mut output, 0)?;
+        Ok(output)
+    }
+
+    fn process(&mut self, lines: &[SourceLine], output: &mut Vec<SourceLine>, depth: usize) -> Result<(), Diagnostic> {
+        let mut conditionals: Vec<Conditional> = vec![];
+        let mut marker: Option<LineMarker> = None;
+
+        for line in join_lines(lines) {
+            let location = match &marker {
+                Some(marker) => SourceLocation::new(
+                    marker.file.clone(),
+                    (line.location.line as i64 + marker.delta) as usize,
+                    line.location.included_from.clone(),
+                ),
+                None => line.location.clone(),
+            };
+            let active = conditionals.last().map_or(true, |conditional| conditional.active);
+
+            let directive = match line.text.trim_start().strip_prefix('#') {
+                Some(directive) => directive.trim(),
+                None => {
+                    if active {
+                        output.push(SourceLine::new(&self.expand(&line.text, &mut vec![]), location));
+                    }
+                    continue;
+                }
+            };
+            let name_end = directive.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(directive.len());
+            let (name, arguments) = (&directive[..name_end], directive[name_end..].trim());
+
+            match name {
+                "if" | "ifdef" | "ifndef" => {
+                    let condition = active
+                        && match name {
+                            "ifdef" => self.defines.contains_key(identifier(arguments)),
+                            "ifndef" => !self.defines.contains_key(identifier(arguments)),
+                            _ => self.evaluate_condition(arguments, &location)?,
+                        };
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        active: condition,
+                        taken: condition,
+                        location: location.clone(),
+                    });
+                }
+                "elif" | "else" => {
+                    let conditional = match conditionals.last_mut() {
+                        Some(conditional) => conditional,
+                        None => {
+                            let message = format!("#{} without #if", name);
+                            return Err(Diagnostic::error(&message, Some(&location)));
+                        }
+                    };
+                    let condition = conditional.parent_active
+                        && !conditional.taken
+                        && (name == "else" || self.evaluate_condition(arguments, &location)?);
+                    conditional.active = condition;
+                    conditional.taken |= condition;
+                }
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| Diagnostic::error("#endif without #if", Some(&location)))?;
+                }
+                _ if !active => {}
+                "define" => self.parse_define(arguments, &location)?,
+                "undef" => {
+                    self.defines.remove(identifier(arguments));
+                }
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(Diagnostic::error("#include nested too deeply", Some(&location)));
+                    }
+                    let included = self.read_included_file(arguments, &location)?;
+                    self.process(&included, output, depth + 1)?;
+                }
+                "line" => marker = Some(parse_line_marker(arguments, &line.location, &location)?),
+                _ if name.starts_with(|c: char| c.is_ascii_digit()) => {
+                    marker = Some(parse_line_marker(directive, &line.location, &location)?);
+                }
+                "error" => return Err(Diagnostic::error(&format!("#error {}", arguments), Some(&location))),
+                // anything else is an assembler comment
+                _ => {}
+            }
+        }
+
+        match conditionals.first() {
+            Some(conditional) => Err(Diagnostic::error("unterminated #if", Some(&conditional.location))),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_define(&mut self, arguments: &str, location: &SourceLocation) -> Result<(), Diagnostic> {
+        let name = identifier(arguments);
+        if name.is_empty() {
+            return Err(Diagnostic::error("macro names must be identifiers", Some(location)));
+        }
+
+        let rest = &arguments[name.len()..];
+        let define = match rest.strip_prefix('(') {
+            Some(rest) => {
+                let end = match rest.find(')') {
+                    Some(end) => end,
+                    None => return Err(Diagnostic::error("missing `)` in macro parameter list", Some(location))),
+                };
+                let parameters = rest[..end]
+                    .split(',')
+                    .map(|parameter| parameter.trim().to_string())
+                    .filter(|parameter| !parameter.is_empty())
+                    .collect();
+                Define {
+                    parameters: Some(parameters),
+                    body: rest[end + 1..].trim().to_string(),
+                }
+            }
+            None => Define {
+                parameters: None,
+                body: rest.trim().to_string(),
+            },
+        };
+        self.defines.insert(name.to_string(), define);
+        Ok(())
+    }
+
+    fn read_included_file(&self, arguments: &str, location: &SourceLocation) -> Result<Vec<SourceLine>, Diagnostic> {
+        let arguments = self.expand(arguments, &mut vec![]);
+        let arguments = arguments.trim();
+        let (name, search_local) = if arguments.len() >= 2 && arguments.starts_with('"') && arguments.ends_with('"') {
+            (&arguments[1..arguments.len() - 1], true)
+        } else if arguments.len() >= 2 && arguments.starts_with('<') && arguments.ends_with('>') {
+            (&arguments[1..arguments.len() - 1], false)
+        } else {
+            return Err(Diagnostic::error("#include expects \"FILENAME\" or <FILENAME>", Some(location)));
+        };
+
+        let including_file = if search_local { location.file.as_path() } else { Path::new("") };
+        let path = find_source_file(name, including_file, &self.include_paths)
+            .map_err(|error| Diagnostic::error(&error.to_string(), Some(location)))?;
+        let mut file = File::open(&path).map_err(|error| {
+            let message = format!("cannot read `{}`: {}", path.display(), error);
+            Diagnostic::error(&message, Some(location))
+        })?;
+        Ok(read_source_file(&path, &mut file, Some(Rc::new(location.clone()))))
+    }
+
+    fn evaluate_condition(&self, text: &str, location: &SourceLocation) -> Result<bool, Diagnostic> {
+        let text = self.expand(&self.replace_defined(text), &mut vec![]);
+        // identifiers left after expansion evaluate to zero, as in C
+        evaluate(&text, &|_| Some(0))
+            .map(|value| value != 0)
+            .map_err(|message| Diagnostic::error(&message, Some(location)))
+    }
+
+    fn replace_defined(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(index) = find_identifier(rest, "defined") {
+            result.push_str(&rest[..index]);
+            let after = rest[index + "defined".len()..].trim_start();
+            let (name, after) = match after.strip_prefix('(') {
+                Some(inner) => {
+                    let end = inner.find(')').unwrap_or(inner.len());
+                    (inner[..end].trim(), inner.get(end + 1..).unwrap_or(""))
+                }
+                None => {
+                    let name = identifier(after);
+                    (name, &after[name.len()..])
+                }
+            };
+            result.push_str(if self.defines.contains_key(name) { "1" } else { "0" });
+            rest = after;
+        }
+        result.push_str(rest);
+        result
+    }
+
+    fn expand(&self, text: &str, disabled: &mut Vec<String>) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(first) = rest.chars().next() {
+            if first == '"' || first == '\'' {
+                let end = rest[1..].find(first).map_or(rest.len(), |end| end + 2);
+                result.push_str(&rest[..end]);
+                rest = &rest[end..];
+            } else if first.is_ascii_digit() || first == '\\' || first == '.' {
+                // numbers, assembler macro parameters and local labels are never expanded
+                let end = rest[1..]
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .map_or(rest.len(), |end| end + 1);
+                result.push_str(&rest[..end]);
+                rest = &rest[end..];
+            } else if first.is_ascii_alphabetic() || first == '_' {
+                let name = identifier(rest);
+                rest = &rest[name.len()..];
+                match self.defines.get(name) {
+                    Some(define) if !disabled.iter().any(|disabled| disabled == name) => {
+                        let body = match &define.parameters {
+                            None => define.body.clone(),
+                            Some(parameters) => match parse_invocation(rest) {
+                                Some((arguments, after)) => {
+                                    rest = after;
+                                    let expanded = arguments.iter().map(|argument| self.expand(argument, disabled)).collect::<Vec<String>>();
+                                    substitute(&define.body, parameters, &arguments, &expanded)
+                                }
+                                None => {
+                                    result.push_str(name);
+                                    continue;
+                                }
+                            },
+                        };
+                        disabled.push(name.to_string());
+                        result.push_str(&self.expand(&body, disabled));
+                        disabled.pop();
+                    }
+                    _ => result.push_str(name),
+                }
+            } else {
+                result.push(first);
+                rest = &rest[first.len_utf8()..];
+            }
+        }
+        result
+    }
+}
+
+fn identifier(text: &str) -> &str {
+    let text = text.trim_start();
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return "";
+    }
+    let end = text.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(text.len());
+    &text[..end]
+}
+
+fn find_identifier(text: &str, name: &str) -> Option<usize> {
+    text.match_indices(name).map(|(index, _)| index).find(|index| {
+        let before = text[..*index].chars().last();
+        let after = text[index + name.len()..].chars().next();
+        !before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !after.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn parse_invocation(text: &str) -> Option<(Vec<String>, &str)> {
+    let text = text.trim_start().strip_prefix('(')?;
+    let mut arguments = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+
+    for (index, char) in text.char_indices() {
+        match char {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                arguments.push(current.trim().to_string());
+                if arguments.len() == 1 && arguments[0].is_empty() {
+                    arguments.clear();
+                }
+                return Some((arguments, &text[index + 1..]));
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(char);
+    }
+    None
+}
+
+// Replaces the parameters of a function-like macro body. Arguments next to `##` or `#` are used
+// as written, the others are already macro-expanded.
+fn substitute(body: &str, parameters: &[String], arguments: &[String], expanded: &[String]) -> String {
+    let mut result = String::new();
+    let mut rest = body;
+
+    while let Some(first) = rest.chars().next() {
+        if first.is_ascii_alphabetic() || first == '_' {
+            let name = identifier(rest);
+            let after = &rest[name.len()..];
+            match parameters.iter().position(|parameter| parameter == name) {
+                Some(index) => {
+                    let raw = arguments.get(index).map_or("", |argument| argument.as_str());
+                    let trimmed = result.trim_end();
+                    if trimmed.ends_with('#') && !trimmed.ends_with("##") {
+                        result.truncate(trimmed.len() - 1);
+                        result.push_str(&format!("\"{}\"", raw));
+                    } else if trimmed.ends_with("##") || after.trim_start().starts_with("##") {
+                        result.push_str(raw);
+                    } else {
+                        result.push_str(expanded.get(index).map_or("", |argument| argument.as_str()));
+                    }
+                }
+                None => result.push_str(name),
+            }
+            rest = after;
+        } else {
+            result.push(first);
+            rest = &rest[first.len_utf8()..];
+        }
+    }
+
+    // token pasting
+    let mut pasted = String::new();
+    for (index, part) in result.split("##").enumerate() {
+        if index == 0 {
+            pasted.push_str(part.trim_end());
+        } else {
+            pasted.push_str(part.trim());
+        }
+    }
+    pasted
+}
+
+fn parse_line_marker(arguments: &str, physical: &SourceLocation, location: &SourceLocation) -> Result<LineMarker, Diagnostic> {
+    let mut parts = arguments.splitn(2, char::is_whitespace);
+    let line = match parts.next().and_then(|line| line.parse::<i64>().ok()) {
+        Some(line) => line,
+        None => return Err(Diagnostic::error("#line expects a line number", Some(location))),
+    };
+    let file = match parts.next().map(|file| file.trim()) {
+        Some(file) if file.starts_with('"') => {
+            let end = file[1..].find('"').map_or(file.len(), |end| end + 1);
+            Rc::new(PathBuf::from(&file[1..end]))
+        }
+        _ => location.file.clone(),
+    };
+    Ok(LineMarker {
+        file,
+        delta: line - (physical.line as i64 + 1),
+    })
+}
+
+// Joins backslash-continued lines and strips C comments.
+fn join_lines(lines: &[SourceLine]) -> Vec<SourceLine> {
+    let mut result: Vec<SourceLine> = vec![];
+    let mut pending: Option<SourceLine> = None;
+    let mut in_comment = false;
+
+    for line in lines {
+        let text = strip_comments(&line.text, &mut in_comment);
+        let (text, continued) = match text.strip_suffix('\\') {
+            Some(text) => (text.to_string(), true),
+            None => (text, false),
+        };
+        let current = match pending.take() {
+            Some(mut previous) => {
+                previous.text.push_str(&text);
+                previous
+            }
+            None => SourceLine::new(&text, line.location.clone()),
+        };
+        if continued || in_comment {
+            pending = Some(current);
+        } else {
+            result.push(current);
+        }
+    }
+    result.extend(pending);
+    result
+}
+
+fn strip_comments(text: &str, in_comment: &mut bool) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    let mut quote: Option<char> = None;
+
+    while let Some(first) = rest.chars().next() {
+        if *in_comment {
+            match rest.find("*/") {
+                Some(end) => {
+                    *in_comment = false;
+                    result.push(' ');
+                    rest = &rest[end + 2..];
+                }
+                None => return result,
+            }
+        } else if let Some(open) = quote {
+            if first == open {
+                quote = None;
+            }
+            result.push(first);
+            rest = &rest[first.len_utf8()..];
+        } else if rest.starts_with("/*") {
+            *in_comment = true;
+            rest = &rest[2..];
+        } else if rest.starts_with("//") {
+            return result;
+        } else {
+            if first == '"' {
+                quote = Some(first);
+            }
+            result.push(first);
+            rest = &rest[first.len_utf8()..];
+        }
+    }
+    result
+}
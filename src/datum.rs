@@ -1,19 +1,32 @@
+use crate::diagnostic::Diagnostic;
+use crate::expression::evaluate;
+use crate::image::AddressUnit;
+use crate::label::{find_label, Label};
 use crate::line::Line;
 use crate::section::Section;
-use crate::utils::{convert_int_to_binary, convert_string_to_hex, convert_string_to_int};
+use crate::symbol::SymbolTable;
+use crate::utils::{convert_int_to_binary, convert_string_to_hex};
 
 pub struct Datum {
     pub name: String,
     value: i32,
     pub address: i32,
+    pub section: Section,
+    // the label or datum whose address is stored, which needs a relocation
+    pub symbol: Option<String>,
+    // unlabelled words get a name derived from the previous datum, but are not symbols
+    pub labelled: bool,
 }
 
 impl Datum {
-    pub fn new(name: &str, value: i32, address: i32) -> Self {
+    pub fn new(name: &str, value: i32, address: i32, section: Section) -> Self {
         Self {
             name: name.to_string(),
             value,
             address,
+            section,
+            symbol: None,
+            labelled: true,
         }
     }
 
@@ -24,47 +37,99 @@ impl Datum {
     pub fn to_hex(&self) -> String {
         convert_string_to_hex(&self.to_binary(), 4)
     }
+
+    pub fn to_word(&self) -> u32 {
+        self.value as u32
+    }
+}
+
+pub fn is_data_definition(code: &str) -> bool {
+    matches!(code.split('\t').collect::<Vec<&str>>()[..], [_, ".word", _])
 }
 
-pub fn extract_data_from_lines(lines: &[Line]) -> Vec<Datum> {
+// Values may use constants, labels and other data, whose addresses count `unit`. A value made of
+// a single undefined symbol is stored as zero and left for the linker.
+pub fn extract_data_from_lines(
+    lines: &[Line],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+) -> Result<Vec<Datum>, Diagnostic> {
     let mut prev_datum_name: Option<String> = None;
 
-    lines
+    let mut data = vec![];
+    for line in lines.iter().filter(|line| line.section.is_data()) {
+        let datum = resolve_data(line.text.as_ref().unwrap(), &prev_datum_name, line.address, line.section)
+            .map_err(|message| Diagnostic::error(&message, Some(&line.location)))?;
+        if let Some(datum) = datum {
+            prev_datum_name = Some(datum.name.clone());
+            data.push(datum);
+        }
+    }
+
+    let values = lines
         .iter()
-        .filter(|line| line.section == Section::DATA)
-        .map(|line| {
-            if let Some(datum) =
-                resolve_data(&line.text.as_ref().unwrap(), &prev_datum_name, line.address)
-            {
-                prev_datum_name = Some(datum.name.clone());
-                Some(datum)
-            } else {
-                None
-            }
+        .filter(|line| line.section.is_data())
+        .filter_map(|line| line.text.as_ref().unwrap().split('\t').nth(2).map(|value| (value, line)))
+        .map(|(value, line)| {
+            resolve_value(value.trim(), &data, labels, symbols, unit)
+                .map_err(|message| Diagnostic::error(&message, Some(&line.location)))
         })
-        .filter_map(|line| line)
-        .collect()
+        .collect::<Result<Vec<(i32, Option<String>)>, Diagnostic>>()?;
+    for (datum, (value, symbol)) in data.iter_mut().zip(values) {
+        datum.value = value;
+        datum.symbol = symbol;
+    }
+    Ok(data)
 }
 
 pub fn find_datum<'a>(name: &'a str, data: &'a [Datum]) -> Option<&'a Datum> {
     data.iter().find(|datum| datum.name == name)
 }
 
-fn resolve_data(code: &str, prev_datum_name: &Option<String>, address: i32) -> Option<Datum> {
-    if let [name, _, value] = code.split('\t').collect::<Vec<&str>>()[..] {
-        let value = convert_string_to_int(value);
+fn resolve_data(
+    code: &str,
+    prev_datum_name: &Option<String>,
+    address: i32,
+    section: Section,
+) -> Result<Option<Datum>, String> {
+    if let [name, _, _] = code.split('\t').collect::<Vec<&str>>()[..] {
         let name = name.trim_end_matches(':');
         if name.is_empty() {
-            if let Some(prev_datum_name) = prev_datum_name {
-                let name = format!("{}_{}", prev_datum_name, address);
-                Some(Datum::new(&name, value, address))
-            } else {
-                panic!("Data name not found.")
-            }
+            // unlabelled data is named after the datum before it
+            let prev_datum_name = prev_datum_name.as_ref().ok_or("the first datum needs a label on its line")?;
+            let name = format!("{}_{}", prev_datum_name, address);
+            let mut datum = Datum::new(&name, 0, address, section);
+            datum.labelled = false;
+            Ok(Some(datum))
         } else {
-            Some(Datum::new(name, value, address))
+            Ok(Some(Datum::new(name, 0, address, section)))
         }
     } else {
-        None
+        Ok(None)
+    }
+}
+
+fn resolve_value(
+    value: &str,
+    data: &[Datum],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+) -> Result<(i32, Option<String>), String> {
+    let address_of = |name: &str| {
+        find_datum(name, data)
+            .map(|datum| datum.address)
+            .or_else(|| find_label(name, labels).map(|label| label.address))
+            .map(|address| unit.from_words(address))
+    };
+
+    let is_symbol = value.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$');
+    if is_symbol && !symbols.contains(value) {
+        return Ok((address_of(value).unwrap_or(0), Some(value.to_string())));
     }
+
+    let value = evaluate(value, &|name| symbols.get(name).or_else(|| address_of(name)))?;
+    Ok((value, None))
 }
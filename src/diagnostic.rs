@@ -0,0 +1,54 @@
+use std::fmt;
+
+use crate::source::SourceLocation;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Severity {
+    ERROR,
+    WARNING,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+}
+
+impl Diagnostic {
+    pub fn error(message: &str, location: Option<&SourceLocation>) -> Self {
+        Self {
+            severity: Severity::ERROR,
+            message: message.to_string(),
+            location: location.cloned(),
+        }
+    }
+
+    pub fn warning(message: &str, location: Option<&SourceLocation>) -> Self {
+        Self {
+            severity: Severity::WARNING,
+            message: message.to_string(),
+            location: location.cloned(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::ERROR => "error",
+            Severity::WARNING => "warning",
+        };
+        match &self.location {
+            Some(location) => {
+                write!(f, "{}: {}: {}", location, severity, self.message)?;
+                for parent in location.include_chain() {
+                    write!(f, "\n  included from {}", parent)?;
+                }
+                Ok(())
+            }
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::constants::{CONTROL_REGISTER_NAMES, INSTRUCTION_TABLE, REGISTER_NAMES, R_INSTRUCTION_OPCODE, WORD_SIZE};
+use crate::image::AddressUnit;
+use crate::instruction::{convert_opcode_to_format, Instruction, InstructionFormat};
+
+// Fields of an instruction word, named after the Nios II A, B and C register fields.
+pub struct DecodedWord {
+    pub format: InstructionFormat,
+    pub opcode: i32,
+    pub opx: i32,
+    pub a: i32,
+    pub b: i32,
+    pub c: i32,
+    pub imm5: i32,
+    pub imm16: i32,
+    pub imm26: i32,
+}
+
+pub fn decode_word(word: u32) -> DecodedWord {
+    let opcode = (word & 0x3F) as i32;
+    DecodedWord {
+        format: convert_opcode_to_format(opcode),
+        opcode,
+        opx: ((word >> 11) & 0x3F) as i32,
+        a: ((word >> 27) & 0x1F) as i32,
+        b: ((word >> 22) & 0x1F) as i32,
+        c: ((word >> 17) & 0x1F) as i32,
+        imm5: ((word >> 6) & 0x1F) as i32,
+        imm16: ((word >> 6) & 0xFFFF) as i32,
+        imm26: (word >> 6) as i32,
+    }
+}
+
+// `INSTRUCTION_TABLE` keyed by opcode, and by OPX for R-type instructions.
+fn instructions_by_encoding() -> &'static HashMap<(i32, i32), &'static Instruction> {
+    static INSTRUCTIONS: OnceLock<HashMap<(i32, i32), &'static Instruction>> = OnceLock::new();
+    INSTRUCTIONS.get_or_init(|| {
+        INSTRUCTION_TABLE
+            .values()
+            .map(|instruction| ((instruction.opcode, instruction.opx), *instruction))
+            .collect()
+    })
+}
+
+pub fn find_instruction(opcode: i32, opx: i32) -> Option<&'static Instruction> {
+    let opx = if opcode == R_INSTRUCTION_OPCODE { opx } else { 0 };
+    instructions_by_encoding().get(&(opcode, opx)).copied()
+}
+
+pub fn register_name(number: i32) -> String {
+    match REGISTER_NAMES.iter().find(|(_, register)| *register == number) {
+        Some((name, _)) => name.to_string(),
+        None => format!("r{}", number),
+    }
+}
+
+pub fn control_register_name(number: i32) -> String {
+    match CONTROL_REGISTER_NAMES.iter().find(|(_, register)| *register == number) {
+        Some((name, _)) => name.to_string(),
+        None => format!("ctl{}", number),
+    }
+}
+
+// Returns the instruction as the assembler accepts it, with a tab after the mnemonic. `address`
+// and the branch and call targets are in `unit`.
+pub fn disassemble_word(word: u32, address: i32, unit: AddressUnit) -> String {
+    format_instruction(word, address, unit, &|_| None, None)
+}
+
+// Same as `disassemble_word`, with branch and call targets named by `target_name` when it knows
+// them, and the 16-bit immediate written as `immediate` when one is given.
+pub(crate) fn format_instruction(
+    word: u32,
+    address: i32,
+    unit: AddressUnit,
+    target_name: &dyn Fn(i32) -> Option<String>,
+    immediate: Option<&str>,
+) -> String {
+    let decoded = decode_word(word);
+    let instruction = match find_instruction(decoded.opcode, decoded.opx) {
+        Some(instruction) => instruction,
+        None => return format!(".word\t0x{:08x}", word),
+    };
+    let signed = decoded.imm16 as i16 as i32;
+    let target = |address: i32| target_name(address).unwrap_or_else(|| format!("0x{:x}", address));
+    let signed_immediate = immediate.map_or_else(|| signed.to_string(), str::to_string);
+    let unsigned_immediate = immediate.map_or_else(|| format!("0x{:x}", decoded.imm16), str::to_string);
+    let (a, b, c) = (register_name(decoded.a), register_name(decoded.b), register_name(decoded.c));
+
+    let operands = match (decoded.format, instruction.name) {
+        (InstructionFormat::JUMP, _) => target(decoded.imm26.wrapping_mul(unit.per_word())),
+        (InstructionFormat::REGISTER, "ret") | (InstructionFormat::REGISTER, "eret") | (InstructionFormat::REGISTER, "bret")
+        | (InstructionFormat::REGISTER, "flushp") | (InstructionFormat::REGISTER, "sync") => String::new(),
+        (InstructionFormat::REGISTER, "break") | (InstructionFormat::REGISTER, "trap") => {
+            if decoded.imm5 == 0 { String::new() } else { decoded.imm5.to_string() }
+        }
+        (InstructionFormat::REGISTER, "jmp") | (InstructionFormat::REGISTER, "callr") | (InstructionFormat::REGISTER, "flushi")
+        | (InstructionFormat::REGISTER, "initi") => a,
+        (InstructionFormat::REGISTER, "nextpc") => c,
+        (InstructionFormat::REGISTER, "rdctl") => format!("{}, {}", c, control_register_name(decoded.imm5)),
+        (InstructionFormat::REGISTER, "wrctl") => format!("{}, {}", control_register_name(decoded.imm5), a),
+        (InstructionFormat::REGISTER, "rdprs") | (InstructionFormat::REGISTER, "wrprs") => format!("{}, {}", c, a),
+        (InstructionFormat::REGISTER, "slli") | (InstructionFormat::REGISTER, "srli") | (InstructionFormat::REGISTER, "srai")
+        | (InstructionFormat::REGISTER, "roli") => format!("{}, {}, {}", c, a, decoded.imm5),
+        (InstructionFormat::REGISTER, _) => format!("{}, {}, {}", c, a, b),
+        (_, "br") => target(branch_target(address, signed, unit)),
+        (_, _) if instruction.is_conditional_branch() => {
+            format!("{}, {}, {}", a, b, target(branch_target(address, signed, unit)))
+        }
+        (_, "flushd") | (_, "flushda") | (_, "initd") | (_, "initda") => format!("{}({})", signed_immediate, a),
+        (_, name) if name.starts_with("ld") || name.starts_with("st") => format!("{}, {}({})", b, signed_immediate, a),
+        (_, "andi") | (_, "ori") | (_, "xori") | (_, "andhi") | (_, "orhi") | (_, "xorhi") | (_, "cmpgeui") | (_, "cmpltui") => {
+            format!("{}, {}, {}", b, a, unsigned_immediate)
+        }
+        (_, _) => format!("{}, {}, {}", b, a, signed_immediate),
+    };
+
+    if operands.is_empty() {
+        instruction.name.to_string()
+    } else {
+        format!("{}\t{}", instruction.name, operands)
+    }
+}
+
+// Branch offsets count `unit` from the instruction following the branch at `address`.
+pub fn branch_target(address: i32, offset: i32, unit: AddressUnit) -> i32 {
+    address.wrapping_add(unit.per_word()).wrapping_add(offset)
+}
+
+// One line per word, `address: word instruction`, the first word being at `start_address` in
+// assembler address units. The listed addresses are in `unit`.
+pub fn disassemble(words: &[u32], start_address: i32, unit: AddressUnit) -> String {
+    words
+        .iter()
+        .enumerate()
+        .map(|(index, word)| {
+            let address = unit.from_words(start_address + index as i32 * WORD_SIZE);
+            format!("{:08x}:\t{:08x}\t{}\n", address, word, disassemble_word(*word, address, unit))
+        })
+        .collect()
+}
@@ -0,0 +1,110 @@
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+pub struct LineRow {
+    pub address: u32,
+    pub file: String,
+    pub line: usize,
+}
+
+// DWARF 2 `.debug_line` contents for one sequence of rows sorted by address, ending at `end_address`.
+// Only standard opcodes are used, one row per change of source line.
+pub fn encode_line_program(rows: &[LineRow], end_address: u32, instruction_size: u8) -> Vec<u8> {
+    let mut files: Vec<&str> = vec![];
+    for row in rows.iter() {
+        if !files.contains(&row.file.as_str()) {
+            files.push(&row.file);
+        }
+    }
+
+    let mut header = vec![instruction_size, 1, LINE_BASE as u8, LINE_RANGE, OPCODE_BASE];
+    header.extend_from_slice(&STANDARD_OPCODE_LENGTHS);
+    // no include directories, then the file names with directory, time and size left unknown
+    header.push(0);
+    for file in files.iter() {
+        header.extend_from_slice(file.as_bytes());
+        header.extend_from_slice(&[0, 0, 0, 0]);
+    }
+    header.push(0);
+
+    let mut program = vec![];
+    if let Some(first) = rows.first() {
+        program.extend_from_slice(&[0, 5, DW_LNE_SET_ADDRESS]);
+        program.extend_from_slice(&first.address.to_le_bytes());
+
+        let (mut address, mut file, mut line) = (first.address, 1, 1);
+        let mut previous: Option<(&str, usize)> = None;
+        for row in rows.iter() {
+            if previous == Some((row.file.as_str(), row.line)) {
+                continue;
+            }
+            previous = Some((row.file.as_str(), row.line));
+
+            let row_file = files.iter().position(|name| *name == row.file).unwrap() + 1;
+            if row_file != file {
+                program.push(DW_LNS_SET_FILE);
+                write_unsigned(&mut program, row_file as u64);
+                file = row_file;
+            }
+            if row.line != line {
+                program.push(DW_LNS_ADVANCE_LINE);
+                write_signed(&mut program, row.line as i64 - line as i64);
+                line = row.line;
+            }
+            if row.address != address {
+                program.push(DW_LNS_ADVANCE_PC);
+                write_unsigned(&mut program, ((row.address - address) / instruction_size as u32) as u64);
+                address = row.address;
+            }
+            program.push(DW_LNS_COPY);
+        }
+
+        if end_address > address {
+            program.push(DW_LNS_ADVANCE_PC);
+            write_unsigned(&mut program, ((end_address - address) / instruction_size as u32) as u64);
+        }
+        program.extend_from_slice(&[0, 1, DW_LNE_END_SEQUENCE]);
+    }
+
+    let mut bytes = vec![];
+    let unit_length = 2 + 4 + header.len() + program.len();
+    bytes.extend_from_slice(&(unit_length as u32).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&program);
+    bytes
+}
+
+fn write_unsigned(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_signed(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
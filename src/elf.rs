@@ -0,0 +1,367 @@
+use std::convert::TryInto;
+use std::io::{self, Write};
+
+use crate::relocation::RelocationKind;
+use crate::symbol::Binding;
+
+pub const EM_ALTERA_NIOS2: u16 = 113;
+
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+
+pub const PT_LOAD: u32 = 1;
+
+pub const PF_X: u32 = 0x1;
+pub const PF_W: u32 = 0x2;
+pub const PF_R: u32 = 0x4;
+
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_RELA: u32 = 4;
+pub const SHT_NOBITS: u32 = 8;
+
+pub const SHF_WRITE: u32 = 0x1;
+pub const SHF_ALLOC: u32 = 0x2;
+pub const SHF_EXECINSTR: u32 = 0x4;
+pub const SHF_INFO_LINK: u32 = 0x40;
+
+pub const SHN_UNDEF: u16 = 0;
+pub const SHN_ABS: u16 = 0xFFF1;
+
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_SECTION: u8 = 3;
+
+const ELF_HEADER_SIZE: u32 = 52;
+const PROGRAM_HEADER_SIZE: u32 = 32;
+const SECTION_HEADER_SIZE: u32 = 40;
+pub const SYMBOL_SIZE: u32 = 16;
+pub const RELA_SIZE: u32 = 12;
+
+pub fn relocation_type(kind: RelocationKind) -> u8 {
+    match kind {
+        RelocationKind::S16 => 1,
+        RelocationKind::PCREL16 => 3,
+        RelocationKind::CALL26 => 4,
+        RelocationKind::HI16 => 9,
+        RelocationKind::LO16 => 10,
+        RelocationKind::HIADJ16 => 11,
+        RelocationKind::BFD_RELOC_32 => 12,
+    }
+}
+
+pub fn relocation_kind(kind: u8) -> Option<RelocationKind> {
+    match kind {
+        1 => Some(RelocationKind::S16),
+        3 => Some(RelocationKind::PCREL16),
+        4 => Some(RelocationKind::CALL26),
+        9 => Some(RelocationKind::HI16),
+        10 => Some(RelocationKind::LO16),
+        11 => Some(RelocationKind::HIADJ16),
+        12 => Some(RelocationKind::BFD_RELOC_32),
+        _ => None,
+    }
+}
+
+pub fn symbol_binding(binding: Binding) -> u8 {
+    match binding {
+        Binding::LOCAL => 0,
+        Binding::GLOBAL => 1,
+        Binding::WEAK => 2,
+    }
+}
+
+pub fn binding_from_elf(binding: u8) -> Option<Binding> {
+    match binding {
+        0 => Some(Binding::LOCAL),
+        1 => Some(Binding::GLOBAL),
+        2 => Some(Binding::WEAK),
+        _ => None,
+    }
+}
+
+pub(crate) struct SectionHeader {
+    pub name: String,
+    pub kind: u32,
+    pub flags: u32,
+    pub address: u32,
+    pub offset: usize,
+    pub size: usize,
+    pub link: usize,
+    pub info: usize,
+}
+
+// Reads the little-endian fields of an ELF32 file, errors naming the file.
+pub(crate) struct ElfReader<'a> {
+    pub name: &'a str,
+    pub bytes: &'a [u8],
+}
+
+impl<'a> ElfReader<'a> {
+    pub fn slice(&self, offset: usize, size: usize) -> Result<&'a [u8], String> {
+        self.bytes
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| format!("`{}` is truncated", self.name))
+    }
+
+    pub fn u16(&self, offset: usize) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.slice(offset, 2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&self, offset: usize) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.slice(offset, 4)?.try_into().unwrap()))
+    }
+
+    pub fn string(&self, table: &SectionHeader, index: usize) -> Result<String, String> {
+        let bytes = self.slice(table.offset, table.size)?;
+        let bytes = bytes.get(index..).ok_or_else(|| format!("`{}` has an invalid string index", self.name))?;
+        let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    // The section headers with their names.
+    pub fn section_headers(&self) -> Result<Vec<SectionHeader>, String> {
+        let section_headers_offset = self.u32(32)? as usize;
+        let section_count = self.u16(48)? as usize;
+        let names_index = self.u16(50)? as usize;
+        let mut headers = vec![];
+        for index in 0..section_count {
+            let offset = section_headers_offset + index * SECTION_HEADER_SIZE as usize;
+            headers.push(SectionHeader {
+                name: String::new(),
+                kind: self.u32(offset + 4)?,
+                flags: self.u32(offset + 8)?,
+                address: self.u32(offset + 12)?,
+                offset: self.u32(offset + 16)? as usize,
+                size: self.u32(offset + 20)? as usize,
+                link: self.u32(offset + 24)? as usize,
+                info: self.u32(offset + 28)? as usize,
+            });
+        }
+        let names = headers.get(names_index).ok_or_else(|| format!("`{}` has no section names", self.name))?;
+        let section_names = (0..section_count)
+            .map(|index| self.string(names, self.u32(section_headers_offset + index * SECTION_HEADER_SIZE as usize)? as usize))
+            .collect::<Result<Vec<String>, String>>()?;
+        for (header, section_name) in headers.iter_mut().zip(section_names) {
+            header.name = section_name;
+        }
+        Ok(headers)
+    }
+}
+
+// Null-terminated names, the first byte is the empty name.
+pub struct StringTable {
+    bytes: Vec<u8>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self { bytes: vec![0] }
+    }
+
+    pub fn add(&mut self, name: &str) -> u32 {
+        if name.is_empty() {
+            return 0;
+        }
+        let index = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.bytes.push(0);
+        index
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ElfSymbol {
+    pub name: u32,
+    pub value: u32,
+    pub size: u32,
+    pub binding: u8,
+    pub kind: u8,
+    pub section: u16,
+}
+
+impl ElfSymbol {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.name.to_le_bytes());
+        bytes.extend_from_slice(&self.value.to_le_bytes());
+        bytes.extend_from_slice(&self.size.to_le_bytes());
+        bytes.push((self.binding << 4) | self.kind);
+        bytes.push(0);
+        bytes.extend_from_slice(&self.section.to_le_bytes());
+        bytes
+    }
+}
+
+pub struct ElfRelocation {
+    pub offset: u32,
+    pub symbol: u32,
+    pub kind: u8,
+    pub addend: i32,
+}
+
+impl ElfRelocation {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&self.offset.to_le_bytes());
+        bytes.extend_from_slice(&((self.symbol << 8) | self.kind as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.addend.to_le_bytes());
+        bytes
+    }
+}
+
+pub struct ElfSection {
+    pub name: String,
+    pub kind: u32,
+    pub flags: u32,
+    pub address: u32,
+    pub data: Vec<u8>,
+    // differs from the data length for `SHT_NOBITS` sections
+    pub size: u32,
+    pub link: u32,
+    pub info: u32,
+    pub align: u32,
+    pub entry_size: u32,
+}
+
+impl ElfSection {
+    pub fn new(name: &str, kind: u32, flags: u32, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            flags,
+            address: 0,
+            size: data.len() as u32,
+            data,
+            link: 0,
+            info: 0,
+            align: 4,
+            entry_size: 0,
+        }
+    }
+}
+
+// `PT_LOAD` segment loading the section at index `section` of `ElfFile::sections`.
+pub struct ProgramHeader {
+    pub section: usize,
+    pub flags: u32,
+}
+
+// Little-endian ELF32 file. The null section and `.shstrtab` are added when writing, so the
+// section at index `i` of `sections` gets the section header index `i + 1`.
+pub struct ElfFile {
+    pub kind: u16,
+    pub entry: u32,
+    pub sections: Vec<ElfSection>,
+    pub program_headers: Vec<ProgramHeader>,
+}
+
+impl ElfFile {
+    pub fn new(kind: u16) -> Self {
+        Self {
+            kind,
+            entry: 0,
+            sections: vec![],
+            program_headers: vec![],
+        }
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> io::Result<()> {
+        let mut names = StringTable::new();
+        let name_indexes = self.sections.iter().map(|section| names.add(&section.name)).collect::<Vec<u32>>();
+        let shstrtab_name = names.add(".shstrtab");
+        let shstrtab = names.into_bytes();
+
+        // section contents follow the ELF and program headers, the section headers come last
+        let program_headers_size = self.program_headers.len() as u32 * PROGRAM_HEADER_SIZE;
+        let program_headers_offset = if self.program_headers.is_empty() { 0 } else { ELF_HEADER_SIZE };
+        let mut offsets = vec![];
+        let mut position = ELF_HEADER_SIZE + program_headers_size;
+        for section in self.sections.iter() {
+            position = align(position, section.align);
+            offsets.push(position);
+            if section.kind != SHT_NOBITS {
+                position += section.data.len() as u32;
+            }
+        }
+        let shstrtab_offset = position;
+        let section_headers_offset = align(shstrtab_offset + shstrtab.len() as u32, 4);
+        let section_count = self.sections.len() as u16 + 2;
+
+        let mut bytes = vec![0x7F, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend_from_slice(&self.kind.to_le_bytes());
+        bytes.extend_from_slice(&EM_ALTERA_NIOS2.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&self.entry.to_le_bytes());
+        bytes.extend_from_slice(&program_headers_offset.to_le_bytes());
+        bytes.extend_from_slice(&section_headers_offset.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+        bytes.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.program_headers.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+        bytes.extend_from_slice(&section_count.to_le_bytes());
+        bytes.extend_from_slice(&(section_count - 1).to_le_bytes());
+
+        for header in self.program_headers.iter() {
+            let section = &self.sections[header.section];
+            let file_size = if section.kind == SHT_NOBITS { 0 } else { section.size };
+            let fields = [
+                PT_LOAD,
+                offsets[header.section],
+                section.address,
+                section.address,
+                file_size,
+                section.size,
+                header.flags,
+                section.align,
+            ];
+            fields.iter().for_each(|field| bytes.extend_from_slice(&field.to_le_bytes()));
+        }
+
+        for (section, offset) in self.sections.iter().zip(offsets.iter()) {
+            bytes.resize(*offset as usize, 0);
+            if section.kind != SHT_NOBITS {
+                bytes.extend_from_slice(&section.data);
+            }
+        }
+        bytes.resize(shstrtab_offset as usize, 0);
+        bytes.extend_from_slice(&shstrtab);
+        bytes.resize(section_headers_offset as usize, 0);
+
+        bytes.extend_from_slice(&[0; SECTION_HEADER_SIZE as usize]);
+        for ((section, offset), name) in self.sections.iter().zip(offsets.iter()).zip(name_indexes) {
+            let fields = [
+                name,
+                section.kind,
+                section.flags,
+                section.address,
+                *offset,
+                section.size,
+                section.link,
+                section.info,
+                section.align,
+                section.entry_size,
+            ];
+            fields.iter().for_each(|field| bytes.extend_from_slice(&field.to_le_bytes()));
+        }
+        let fields = [shstrtab_name, SHT_STRTAB, 0, 0, shstrtab_offset, shstrtab.len() as u32, 0, 0, 1, 0];
+        fields.iter().for_each(|field| bytes.extend_from_slice(&field.to_le_bytes()));
+
+        output.write_all(&bytes)
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    let alignment = alignment.max(1);
+    (value + alignment - 1) / alignment * alignment
+}
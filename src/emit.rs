@@ -0,0 +1,121 @@
+use crate::asm_macro::disassemble_macro;
+use crate::constants::WORD_SIZE;
+use crate::datum::Datum;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::instruction::convert_opcode_to_format;
+use crate::json::{json_array, json_location, json_object, json_string};
+use crate::label::is_label;
+use crate::line::Line;
+use crate::map::{section_json, symbol_json, SymbolMap};
+use crate::section::{resolve_section, Section};
+use crate::text::Text;
+use crate::utils::{split_arguments, split_statement};
+
+// What `--emit` can write instead of the memory image.
+pub const EMIT_JSON: &str = "json";
+pub const EMIT_KINDS: [&str; 2] = ["image", EMIT_JSON];
+
+// The assembly result for `--emit json`, as `ListingInput` holds it for the listing. `texts` are
+// the instructions of `lines` from `text_start_address`, and `diagnostics` the warnings and errors.
+pub struct EmitInput<'a> {
+    pub lines: &'a [Line],
+    pub texts: &'a [Text],
+    pub data: &'a [Datum],
+    pub map: &'a SymbolMap,
+    pub diagnostics: &'a [Diagnostic],
+    pub text_start_address: i32,
+}
+
+// One JSON document with every encoded instruction, every datum, the sections, the symbols and
+// the diagnostics. A pseudo-instruction gives one statement per instruction it expands to, each
+// with the source span it came from. An instruction that did not encode has an error at its line.
+pub fn emit_json(input: &EmitInput) -> String {
+    let statements = statements(input);
+    let data = input
+        .data
+        .iter()
+        .map(|datum| {
+            let line = input.lines.iter().find(|line| line.section.is_data() && line.address == datum.address && line.text.is_some());
+            let (file, line) = json_location(line.map(|line| &line.location));
+            json_object(&[
+                ("name", json_string(&datum.name)),
+                ("labelled", datum.labelled.to_string()),
+                ("section", json_string(datum.section.name())),
+                ("address", datum.address.to_string()),
+                ("word", datum.to_word().to_string()),
+                ("symbol", datum.symbol.as_deref().map_or("null".to_string(), json_string)),
+                ("file", file),
+                ("line", line),
+            ])
+        })
+        .collect::<Vec<String>>();
+    let sections = input.map.sections.iter().map(section_json).collect::<Vec<String>>();
+    let symbols = input.map.by_address().iter().map(|symbol| symbol_json(symbol)).collect::<Vec<String>>();
+    let diagnostics = input
+        .diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let severity = match diagnostic.severity {
+                Severity::ERROR => "error",
+                Severity::WARNING => "warning",
+            };
+            let (file, line) = json_location(diagnostic.location.as_ref());
+            json_object(&[
+                ("severity", json_string(severity)),
+                ("message", json_string(&diagnostic.message)),
+                ("file", file),
+                ("line", line),
+            ])
+        })
+        .collect::<Vec<String>>();
+
+    let members = [
+        ("statements", statements),
+        ("data", data),
+        ("sections", sections),
+        ("symbols", symbols),
+        ("diagnostics", diagnostics),
+    ];
+    let members = members
+        .iter()
+        .map(|(name, values)| format!("  {}: {}", json_string(name), json_array(values, 4)))
+        .collect::<Vec<String>>();
+    format!("{{\n{}\n}}\n", members.join(",\n"))
+}
+
+// The instructions as `extract_codes` finds them in the lines.
+fn statements(input: &EmitInput) -> Vec<String> {
+    let mut texts = input.texts.iter();
+    let mut address = input.text_start_address;
+    let mut statements = vec![];
+    for line in input.lines.iter().filter(|line| line.section == Section::TEXT) {
+        let source = match &line.text {
+            Some(text) if resolve_section(text).is_none() && !is_label(text) => text,
+            _ => continue,
+        };
+        let codes = disassemble_macro(source).unwrap_or_else(|| vec![source.trim_start().to_string()]);
+        for (code, text) in codes.iter().zip(texts.by_ref()) {
+            let (_, mnemonic, operands) = split_statement(code);
+            let operands = split_arguments(operands).iter().map(|operand| json_string(operand)).collect::<Vec<String>>();
+            let word = text.to_word();
+            let (file, line_number) = json_location(Some(&line.location));
+            // columns count characters from one, the end being past the statement
+            let column = source.chars().take_while(|character| character.is_whitespace()).count() + 1;
+            let end_column = source.chars().count() + 1;
+            statements.push(json_object(&[
+                ("address", address.to_string()),
+                ("source", json_string(source.trim())),
+                ("mnemonic", json_string(mnemonic)),
+                ("operands", format!("[{}]", operands.join(", "))),
+                ("format", json_string(convert_opcode_to_format((word & 0x3F) as i32).letter())),
+                ("word", word.to_string()),
+                ("file", file),
+                ("line", line_number),
+                ("column", column.to_string()),
+                ("end_column", end_column.to_string()),
+            ]));
+            address += WORD_SIZE;
+        }
+    }
+    statements
+}
@@ -0,0 +1,107 @@
+use crate::asm_macro::disassemble_macro;
+use crate::constants::{INSTRUCTION_TABLE, R_INSTRUCTION_OPCODE, WORD_SIZE};
+use crate::image::AddressUnit;
+use crate::disassembler::{branch_target, control_register_name, disassemble_word, find_instruction, register_name};
+use crate::instruction::{convert_opcode_to_format, Instruction};
+use crate::symbol::SymbolTable;
+use crate::text::{get_text_from_code, Text};
+
+// Explains a hexadecimal word, or the words an assembly line encodes to, the first word being at
+// `address`: its format, and the bit positions and values of every field.
+pub fn explain(input: &str, address: i32) -> Result<String, String> {
+    let input = input.trim();
+    if let Some(word) = parse_word(input) {
+        return Ok(explain_text(&Text::from_word(word, address), address));
+    }
+
+    // the encoder expects a tab between the mnemonic and the operands
+    let code = match input.split_once(char::is_whitespace) {
+        Some((name, operands)) => format!("{}\t{}", name, operands.trim()),
+        None => input.to_string(),
+    };
+    let codes = match disassemble_macro(&code) {
+        Some(codes) => codes,
+        None => {
+            let name = code.split('\t').next().unwrap_or("");
+            if !INSTRUCTION_TABLE.contains_key(name) {
+                return Err(format!("unknown instruction `{}`", name));
+            }
+            vec![code]
+        }
+    };
+
+    let mut explanations = vec![];
+    for (index, code) in codes.iter().enumerate() {
+        let address = address + index as i32 * WORD_SIZE;
+        let text = get_text_from_code(code, address, &[], &[], &SymbolTable::new(), AddressUnit::WORD, None)
+            .map_err(|diagnostic| diagnostic.message)?;
+        if let Some(reference) = text.reference {
+            return Err(format!("undefined symbol `{}`", reference.symbol));
+        }
+        explanations.push(explain_text(&text, address));
+    }
+    Ok(explanations.join("\n"))
+}
+
+// Eight hexadecimal digits, with or without `0x`.
+fn parse_word(text: &str) -> Option<u32> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    if digits.len() == 8 && digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+        u32::from_str_radix(digits, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn explain_text(text: &Text, address: i32) -> String {
+    let binary = text.to_binary();
+    let word = text.to_word();
+    let opcode = (word & 0x3F) as i32;
+    let format = convert_opcode_to_format(opcode);
+    let instruction = find_instruction(opcode, ((word >> 11) & 0x3F) as i32);
+
+    let fields = format
+        .fields()
+        .iter()
+        .map(|(name, high, low)| (*name, *high, *low, &binary[(31 - high) as usize..=(31 - low) as usize]))
+        .collect::<Vec<_>>();
+    let mut lines = vec![
+        disassemble_word(word, address, AddressUnit::WORD),
+        format!("format: {}", format.letter()),
+        format!("binary: {}", fields.iter().map(|field| field.3).collect::<Vec<&str>>().join(" ")),
+        format!("{:<6} {:<6} {:<26} {:>10}  {:<10} {}", "field", "bits", "binary", "decimal", "hex", "meaning"),
+    ];
+    for (name, high, low, bits) in fields.iter() {
+        let value = u32::from_str_radix(bits, 2).unwrap();
+        let line = format!(
+            "{:<6} {:<6} {:<26} {:>10}  {:<10} {}",
+            name,
+            format!("{}..{}", high, low),
+            bits,
+            value,
+            format!("0x{:x}", value),
+            field_meaning(name, value, instruction, address)
+        );
+        lines.push(line.trim_end().to_string());
+    }
+    lines.push(format!("hex: {:08x}", word));
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+fn field_meaning(field: &str, value: u32, instruction: Option<&Instruction>, address: i32) -> String {
+    let name = instruction.map_or("", |instruction| instruction.name);
+    match field {
+        "A" | "B" | "C" => register_name(value as i32),
+        "OP" if instruction.is_none() => "unknown".to_string(),
+        "OP" if value as i32 == R_INSTRUCTION_OPCODE => "R-type, see OPX".to_string(),
+        "OP" | "OPX" => name.to_string(),
+        "IMM5" if name == "rdctl" || name == "wrctl" => control_register_name(value as i32),
+        "IMM5" if instruction.is_some_and(|instruction| instruction.is_immediate_shift()) => "shift amount".to_string(),
+        "IMM16" if instruction.is_some_and(|instruction| instruction.is_conditional_branch() || instruction.is_relative_branch()) => {
+            format!("target 0x{:x}", branch_target(address, value as u16 as i16 as i32, AddressUnit::WORD))
+        }
+        "IMM16" if value & 0x8000 != 0 => format!("signed {}", value as u16 as i16),
+        "IMM26" => format!("target 0x{:x}", value),
+        _ => String::new(),
+    }
+}
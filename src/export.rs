@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::constants::WORD_BYTES;
+use crate::map::{MapSymbol, SymbolKind, SymbolMap};
+use crate::symbol::Binding;
+
+// Prefix of the exported names, which keeps them apart from the names of the including code.
+pub const EXPORT_PREFIX: &str = "SYM_";
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    C,
+    SYSTEMVERILOG,
+    VHDL,
+}
+
+impl ExportFormat {
+    // The format is told by the extension of the file written.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "h" | "hh" | "hpp" => Some(ExportFormat::C),
+            "sv" | "svh" => Some(ExportFormat::SYSTEMVERILOG),
+            "vhd" | "vhdl" => Some(ExportFormat::VHDL),
+            _ => None,
+        }
+    }
+}
+
+// Writes the global symbols and those in `marked` as constants of a C header, a SystemVerilog
+// package or a VHDL package, named after `path`. The C header gives the byte addresses the
+// processor uses, the packages the word addresses of the memories.
+pub fn export_symbols(map: &SymbolMap, marked: &HashSet<String>, path: &Path) -> Result<String, String> {
+    let format = ExportFormat::from_path(path).ok_or_else(|| format!("unknown export format of `{}`", path.display()))?;
+    if let Some(name) = marked.iter().find(|name| map.find(name).map_or(true, |symbol| symbol.kind == SymbolKind::UNDEFINED)) {
+        return Err(format!("exported symbol `{}` is not defined", name));
+    }
+    let symbols = map
+        .symbols
+        .iter()
+        .filter(|symbol| symbol.kind != SymbolKind::UNDEFINED)
+        .filter(|symbol| symbol.binding != Binding::LOCAL || marked.contains(&symbol.name))
+        .collect::<Vec<&MapSymbol>>();
+    // VHDL identifiers ignore case
+    let mut exported: HashMap<String, &str> = HashMap::new();
+    for symbol in symbols.iter() {
+        let constant = constant_name(symbol, format);
+        let key = if format == ExportFormat::VHDL { constant.to_ascii_lowercase() } else { constant.clone() };
+        if let Some(other) = exported.insert(key, &symbol.name) {
+            return Err(format!("symbols `{}` and `{}` are both exported as `{}`", other, symbol.name, constant));
+        }
+    }
+    let stem = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let name = identifier(&stem, format);
+
+    let mut output = String::new();
+    match format {
+        ExportFormat::C => {
+            let guard = format!("{}_H", name.to_ascii_uppercase());
+            output += &format!("/* Generated by nios2asm */\n#ifndef {0}\n#define {0}\n\n", guard);
+            for symbol in symbols.iter() {
+                let value = match symbol.kind {
+                    SymbolKind::CONSTANT => symbol.value,
+                    _ => symbol.value.wrapping_mul(WORD_BYTES),
+                };
+                output += &format!("#define {} 0x{:08x}\n", constant_name(symbol, format), value);
+            }
+            output += &format!("\n#endif /* {} */\n", guard);
+        }
+        ExportFormat::SYSTEMVERILOG => {
+            output += &format!("// Generated by nios2asm\npackage {};\n", name);
+            for symbol in symbols.iter() {
+                output += &format!("    localparam logic [31:0] {} = 32'h{:08x};\n", constant_name(symbol, format), symbol.value);
+            }
+            output += &format!("endpackage : {}\n", name);
+        }
+        ExportFormat::VHDL => {
+            output += "-- Generated by nios2asm\nlibrary ieee;\nuse ieee.std_logic_1164.all;\n\n";
+            output += &format!("package {} is\n", name);
+            for symbol in symbols.iter() {
+                let constant = constant_name(symbol, format);
+                output += &format!("    constant {} : std_logic_vector(31 downto 0) := x\"{:08X}\";\n", constant, symbol.value);
+            }
+            output += &format!("end package {};\n", name);
+        }
+    }
+    Ok(output)
+}
+
+fn constant_name(symbol: &MapSymbol, format: ExportFormat) -> String {
+    identifier(&format!("{}{}", EXPORT_PREFIX, symbol.name), format)
+}
+
+// `name` with the characters no identifier may hold replaced by underscores. VHDL identifiers
+// must also start with a letter and cannot hold two underscores in a row or end with one.
+fn identifier(name: &str, format: ExportFormat) -> String {
+    let mut identifier = name.chars().map(|character| if character.is_ascii_alphanumeric() { character } else { '_' }).collect::<String>();
+    if format == ExportFormat::VHDL {
+        while identifier.contains("__") {
+            identifier = identifier.replace("__", "_");
+        }
+        identifier = identifier.trim_matches('_').to_string();
+    }
+    if !identifier.starts_with(|first: char| first.is_ascii_alphabetic() || (first == '_' && format != ExportFormat::VHDL)) {
+        identifier.insert_str(0, "s_");
+    }
+    identifier
+}
@@ -0,0 +1,203 @@
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 22] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "<>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">", "=",
+];
+
+// Binary operators from the lowest to the highest precedence.
+const PRECEDENCE: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!=", "<>", "="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+pub fn evaluate(text: &str, lookup: &dyn Fn(&str) -> Option<i32>) -> Result<i32, String> {
+    let tokens = tokenize(text)?;
+    if tokens.is_empty() {
+        return Err("expected an expression".to_string());
+    }
+
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        lookup,
+    };
+    let value = parser.parse_binary(0)?;
+    match parser.tokens.get(parser.position) {
+        Some(token) => Err(format!("unexpected `{}` in expression `{}`", describe(token), text.trim())),
+        // a value is 32 bits wide, read as signed or as unsigned
+        None if value < i32::MIN as i64 || value > u32::MAX as i64 => {
+            Err(format!("value {} of `{}` does not fit in 32 bits", value, text.trim()))
+        }
+        None => Ok(value as i32),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap();
+        if first.is_ascii_digit() {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..length])?));
+            rest = &rest[length..];
+        } else if first.is_ascii_alphabetic() || first == '_' || first == '.' || first == '$' {
+            let length = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Symbol(rest[..length].to_string()));
+            rest = &rest[length..];
+        } else if first == '\'' {
+            let mut chars = rest[1..].chars();
+            match (chars.next(), chars.next()) {
+                (Some(value), Some('\'')) => {
+                    tokens.push(Token::Number(value as i64));
+                    rest = &rest[value.len_utf8() + 2..];
+                }
+                _ => return Err(format!("invalid character constant in `{}`", text.trim())),
+            }
+        } else if first == '(' {
+            tokens.push(Token::Open);
+            rest = &rest[1..];
+        } else if first == ')' {
+            tokens.push(Token::Close);
+            rest = &rest[1..];
+        } else if let Some(operator) = OPERATORS.iter().find(|operator| rest.starts_with(*operator)) {
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        } else {
+            return Err(format!("unexpected character `{}` in expression `{}`", first, text.trim()));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let lowercase = text.to_ascii_lowercase();
+    let result = if let Some(digits) = lowercase.strip_prefix("0x") {
+        i64::from_str_radix(digits, 16)
+    } else if let Some(digits) = lowercase.strip_prefix("0b") {
+        i64::from_str_radix(digits, 2)
+    } else {
+        lowercase.parse::<i64>()
+    };
+    result.map_err(|_| format!("invalid number `{}`", text))
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => value.to_string(),
+        Token::Symbol(name) => name.clone(),
+        Token::Operator(operator) => operator.to_string(),
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    lookup: &'a dyn Fn(&str) -> Option<i32>,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_binary(&mut self, level: usize) -> Result<i64, String> {
+        if level == PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+
+        let mut left = self.parse_binary(level + 1)?;
+        while let Some(Token::Operator(operator)) = self.tokens.get(self.position) {
+            if !PRECEDENCE[level].contains(operator) {
+                break;
+            }
+            self.position += 1;
+            let right = self.parse_binary(level + 1)?;
+            left = apply_binary(operator, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<i64, String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) if ["-", "+", "~", "!"].contains(operator) => {
+                self.position += 1;
+                let value = self.parse_unary()?;
+                Ok(match *operator {
+                    "-" => value.wrapping_neg(),
+                    "~" => !value,
+                    "!" => (value == 0) as i64,
+                    _ => value,
+                })
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<i64, String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+
+        match token {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Symbol(name)) => match (self.lookup)(&name) {
+                Some(value) => Ok(value as i64),
+                None => Err(format!("symbol `{}` is not defined", name)),
+            },
+            Some(Token::Open) => {
+                let value = self.parse_binary(0)?;
+                match self.tokens.get(self.position) {
+                    Some(Token::Close) => {
+                        self.position += 1;
+                        Ok(value)
+                    }
+                    _ => Err("missing `)` in expression".to_string()),
+                }
+            }
+            Some(token) => Err(format!("unexpected `{}` in expression", describe(&token))),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn apply_binary(operator: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match operator {
+        "||" => (left != 0 || right != 0) as i64,
+        "&&" => (left != 0 && right != 0) as i64,
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" | "=" => (left == right) as i64,
+        "!=" | "<>" => (left != right) as i64,
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("division by zero in expression".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        _ => panic!("Unknown operator {}.", operator),
+    })
+}
@@ -0,0 +1,206 @@
+use crate::constants::{WORD_BYTES, WORD_SIZE};
+use crate::datum::Datum;
+use crate::label::Label;
+use crate::relocation::{Relocation, RelocationKind};
+use crate::section::Section;
+use crate::source::SourceLocation;
+use crate::symbol::{Binding, SymbolTable};
+use crate::text::Text;
+
+// What the addresses held by instructions and data count: words, as the Logisim CPU addresses
+// its memory, or bytes, as a Nios II does and as ELF files expect. Memory is laid out in
+// assembler address units either way.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressUnit {
+    WORD,
+    BYTE,
+}
+
+impl AddressUnit {
+    // Addresses per 32-bit word.
+    pub fn per_word(self) -> i32 {
+        match self {
+            AddressUnit::WORD => 1,
+            AddressUnit::BYTE => WORD_BYTES,
+        }
+    }
+
+    // Bytes of memory per address.
+    pub fn bytes(self) -> i32 {
+        WORD_BYTES / self.per_word()
+    }
+
+    // Converts an address in assembler address units into this unit.
+    pub fn from_words(self, address: i32) -> i32 {
+        (address / WORD_SIZE).wrapping_mul(self.per_word())
+    }
+}
+
+// Addresses are expressed in assembler address units, i.e. one unit per 32-bit word.
+pub struct Segment {
+    pub name: String,
+    pub base: i32,
+    pub words: Vec<u32>,
+}
+
+impl Segment {
+    pub fn new(name: &str, base: i32, words: Vec<u32>) -> Self {
+        Self {
+            name: name.to_string(),
+            base,
+            words,
+        }
+    }
+
+    pub fn end(&self) -> i32 {
+        self.base + self.words.len() as i32
+    }
+
+    pub fn contains(&self, address: i32) -> bool {
+        address >= self.base && address < self.end()
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.words.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect()
+    }
+}
+
+pub struct ImageSymbol {
+    pub name: String,
+    pub address: i32,
+    pub section: String,
+    pub binding: Binding,
+}
+
+pub struct MemoryImage {
+    pub segments: Vec<Segment>,
+    pub symbols: Vec<ImageSymbol>,
+    pub relocations: Vec<Relocation>,
+    // referenced symbols defined in another unit, with their binding
+    pub externals: Vec<(String, Binding)>,
+    // source location of each instruction address
+    pub line_table: Vec<(i32, SourceLocation)>,
+    // what the instructions and data of the segments count addresses in
+    pub unit: AddressUnit,
+}
+
+impl MemoryImage {
+    pub fn new(
+        texts: &[Text],
+        data: &[Datum],
+        labels: &[Label],
+        symbol_table: &SymbolTable,
+        text_start_address: i32,
+        data_start_address: i32,
+        unit: AddressUnit,
+    ) -> Self {
+        let section_words = |section: Section| {
+            data.iter().filter(|datum| datum.section == section).map(|datum| datum.to_word()).collect::<Vec<u32>>()
+        };
+        let mut segments = vec![
+            Segment::new(".text", text_start_address, texts.iter().map(|text| text.to_word()).collect()),
+            Segment::new(".data", data_start_address, section_words(Section::DATA)),
+        ];
+        // `.rodata` and `.bss` only exist when they hold data
+        for section in [Section::RODATA, Section::BSS] {
+            if let Some(first) = data.iter().find(|datum| datum.section == section) {
+                segments.push(Segment::new(section.name(), first.address, section_words(section)));
+            }
+        }
+
+        let mut symbols = labels
+            .iter()
+            .map(|label| ImageSymbol {
+                name: label.name.clone(),
+                address: label.address,
+                section: ".text".to_string(),
+                binding: symbol_table.binding(&label.name),
+            })
+            .collect::<Vec<ImageSymbol>>();
+        symbols.extend(data.iter().filter(|datum| datum.labelled).map(|datum| ImageSymbol {
+            name: datum.name.clone(),
+            address: datum.address,
+            section: datum.section.name().to_string(),
+            binding: symbol_table.binding(&datum.name),
+        }));
+
+        let mut relocations = texts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, text)| {
+                text.reference.as_ref().map(|reference| Relocation {
+                    kind: reference.kind,
+                    section: ".text".to_string(),
+                    address: text_start_address + index as i32 * WORD_SIZE,
+                    symbol: reference.symbol.clone(),
+                })
+            })
+            .collect::<Vec<Relocation>>();
+        relocations.extend(data.iter().filter_map(|datum| {
+            datum.symbol.as_ref().map(|symbol| Relocation {
+                kind: RelocationKind::BFD_RELOC_32,
+                section: datum.section.name().to_string(),
+                address: datum.address,
+                symbol: symbol.clone(),
+            })
+        }));
+
+        let mut externals: Vec<(String, Binding)> = vec![];
+        for relocation in relocations.iter() {
+            let defined = symbols.iter().any(|symbol| symbol.name == relocation.symbol);
+            if !defined && !externals.iter().any(|(name, _)| *name == relocation.symbol) {
+                let binding = match symbol_table.binding(&relocation.symbol) {
+                    Binding::WEAK => Binding::WEAK,
+                    _ => Binding::GLOBAL,
+                };
+                externals.push((relocation.symbol.clone(), binding));
+            }
+        }
+
+        let line_table = texts
+            .iter()
+            .enumerate()
+            .filter_map(|(index, text)| {
+                let address = text_start_address + index as i32 * WORD_SIZE;
+                text.location.clone().map(|location| (address, location))
+            })
+            .collect();
+
+        Self {
+            segments,
+            symbols,
+            relocations,
+            externals,
+            line_table,
+            unit,
+        }
+    }
+
+    // Image read back from a file that only holds memory contents.
+    pub fn from_segments(segments: Vec<Segment>) -> Self {
+        Self {
+            segments,
+            symbols: vec![],
+            relocations: vec![],
+            externals: vec![],
+            line_table: vec![],
+            unit: AddressUnit::WORD,
+        }
+    }
+
+    pub fn find_symbol(&self, name: &str) -> Option<&ImageSymbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    pub fn check_overlaps(&self) -> Result<(), String> {
+        let mut segments = self.segments.iter().filter(|segment| !segment.words.is_empty()).collect::<Vec<&Segment>>();
+        segments.sort_by_key(|segment| segment.base);
+        for pair in segments.windows(2) {
+            if pair[0].end() > pair[1].base {
+                return Err(format!("The {} section overlap the {} section!", pair[0].name, pair[1].name));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::constants::WORD_BYTES;
+use crate::elf::{ElfReader, EM_ALTERA_NIOS2, ET_EXEC, SHF_ALLOC, SHT_NOBITS};
+use crate::image::Segment;
+
+pub const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+
+// Reads the allocated sections of an ELF32 executable, as the `elf` format and the linker write
+// it, one segment per section. Byte addresses are converted back to assembler address units.
+pub fn read_elf_executable(bytes: &[u8]) -> Result<Vec<Segment>, String> {
+    let reader = check_executable(bytes)?;
+    let mut segments = vec![];
+    for header in reader.section_headers()?.iter().filter(|header| header.flags & SHF_ALLOC != 0) {
+        let words = if header.kind == SHT_NOBITS {
+            vec![0; header.size / WORD_BYTES as usize]
+        } else {
+            reader
+                .slice(header.offset, header.size)?
+                .chunks(WORD_BYTES as usize)
+                .map(|chunk| chunk.iter().rev().fold(0, |word, byte| (word << 8) | *byte as u32))
+                .collect()
+        };
+        if header.address % WORD_BYTES as u32 != 0 {
+            return Err(format!("section `{}` address 0x{:x} is not word-aligned", header.name, header.address));
+        }
+        segments.push(Segment::new(&header.name, header.address as i32 / WORD_BYTES, words));
+    }
+    Ok(segments)
+}
+
+// The byte address an ELF32 executable starts at, `None` for any other image.
+pub fn elf_entry_point(bytes: &[u8]) -> Option<i32> {
+    let reader = check_executable(bytes).ok()?;
+    reader.u32(24).ok().map(|entry| entry as i32)
+}
+
+fn check_executable(bytes: &[u8]) -> Result<ElfReader<'_>, String> {
+    let reader = ElfReader { name: "image", bytes };
+    if !bytes.starts_with(&[0x7F, b'E', b'L', b'F', 1, 1]) || reader.u16(18)? != EM_ALTERA_NIOS2 {
+        return Err("not a Nios II ELF32 file".to_string());
+    }
+    if reader.u16(16)? != ET_EXEC {
+        return Err("not an ELF executable, relocatable objects must be linked first".to_string());
+    }
+    Ok(reader)
+}
@@ -0,0 +1,58 @@
+use crate::constants::WORD_BYTES;
+use crate::image::Segment;
+use crate::input::segment_name;
+
+pub const LOGISIM_RAW_HEADER: &str = "v2.0 raw";
+
+// Logisim memory image: hex words separated by blanks or line breaks, `N*V` for N copies of V and
+// `#` comments. Runs of zeros written as `N*0` are gaps between segments, like the assembler
+// writes them between sections, while explicit zero words belong to the segment.
+pub fn read_logisim_raw(text: &str) -> Result<Vec<Segment>, String> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.trim() == LOGISIM_RAW_HEADER => {}
+        _ => return Err(format!("missing `{}` header", LOGISIM_RAW_HEADER)),
+    }
+
+    let mut segments: Vec<Segment> = vec![];
+    let mut address: i32 = 0;
+    let mut in_segment = false;
+    for (index, line) in lines {
+        let line = line.split('#').next().unwrap_or("");
+        for token in line.split_whitespace() {
+            let invalid = || format!("line {}: invalid entry `{}`", index + 1, token);
+            let (count, value, run) = match token.split_once('*') {
+                Some((count, value)) => {
+                    let count = count.parse::<i32>().map_err(|_| invalid())?;
+                    (count, parse_word(value).ok_or_else(invalid)?, true)
+                }
+                None => (1, parse_word(token).ok_or_else(invalid)?, false),
+            };
+            if count < 0 {
+                return Err(format!("line {}: negative count in `{}`", index + 1, token));
+            }
+            // every word must keep a byte address
+            let end = address
+                .checked_add(count)
+                .filter(|end| end.checked_mul(WORD_BYTES).is_some())
+                .ok_or_else(|| format!("line {}: `{}` runs past the end of the address space", index + 1, token))?;
+
+            if run && value == 0 {
+                in_segment = false;
+            } else {
+                if !in_segment {
+                    segments.push(Segment::new(segment_name(segments.len()), address, vec![]));
+                    in_segment = true;
+                }
+                let segment = segments.last_mut().unwrap();
+                segment.words.extend(std::iter::repeat(value).take(count as usize));
+            }
+            address = end;
+        }
+    }
+    Ok(segments)
+}
+
+fn parse_word(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
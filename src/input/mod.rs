@@ -0,0 +1,60 @@
+use crate::constants::WORD_BYTES;
+use crate::image::{AddressUnit, MemoryImage, Segment};
+
+mod elf;
+mod logisim;
+
+pub use self::elf::{elf_entry_point, read_elf_executable, ELF_MAGIC};
+pub use self::logisim::{read_logisim_raw, LOGISIM_RAW_HEADER};
+
+// Images without section names get these in address order, as the assembler lays them out.
+const SEGMENT_NAMES: [&str; 4] = [".text", ".data", ".rodata", ".bss"];
+
+pub(crate) fn segment_name(index: usize) -> &'static str {
+    SEGMENT_NAMES.get(index).copied().unwrap_or(".data")
+}
+
+// Loads a memory image for the disassembler and the simulator: an ELF executable and a Logisim
+// `v2.0 raw` image are recognised by their header, a text made of hexadecimal words and any other
+// content, read as a flat little-endian binary, are placed at `base`. ELF executables and flat
+// binaries count bytes, like the `elf` and `bin` outputs.
+pub fn read_image(bytes: &[u8], base: i32) -> Result<MemoryImage, String> {
+    if bytes.starts_with(&ELF_MAGIC) {
+        let mut image = MemoryImage::from_segments(read_elf_executable(bytes)?);
+        image.unit = AddressUnit::BYTE;
+        return Ok(image);
+    }
+    let text = std::str::from_utf8(bytes).ok();
+    let mut unit = AddressUnit::WORD;
+    let segments = match text {
+        Some(text) if text.trim_start().starts_with(LOGISIM_RAW_HEADER) => read_logisim_raw(text.trim_start())?,
+        _ => {
+            let words = match text.and_then(parse_hex_words) {
+                Some(words) => words,
+                None => {
+                    unit = AddressUnit::BYTE;
+                    bytes
+                        .chunks(WORD_BYTES as usize)
+                        .map(|chunk| chunk.iter().rev().fold(0, |word, byte| (word << 8) | *byte as u32))
+                        .collect()
+                }
+            };
+            vec![Segment::new(segment_name(0), base, words)]
+        }
+    };
+    let mut image = MemoryImage::from_segments(segments);
+    image.unit = unit;
+    Ok(image)
+}
+
+fn parse_hex_words(text: &str) -> Option<Vec<u32>> {
+    let words = text
+        .split_whitespace()
+        .map(|word| u32::from_str_radix(word.trim_start_matches("0x"), 16).ok())
+        .collect::<Option<Vec<u32>>>()?;
+    if words.is_empty() {
+        None
+    } else {
+        Some(words)
+    }
+}
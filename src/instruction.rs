@@ -1,6 +1,7 @@
 use crate::text::Text;
 use crate::constants::{R_INSTRUCTION_OPCODE};
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum InstructionFormat {
     REGISTER,
     IMMEDIATE,
@@ -8,6 +9,30 @@ pub enum InstructionFormat {
     PSEUDO,
 }
 
+impl InstructionFormat {
+    // R, I or J, as the Nios II documentation names the formats.
+    pub fn letter(&self) -> &'static str {
+        match self {
+            InstructionFormat::REGISTER => "R",
+            InstructionFormat::IMMEDIATE => "I",
+            InstructionFormat::JUMP => "J",
+            InstructionFormat::PSEUDO => "pseudo",
+        }
+    }
+
+    // Bit fields of the format from the most significant one, as (name, highest bit, lowest bit).
+    pub fn fields(&self) -> &'static [(&'static str, u32, u32)] {
+        match self {
+            InstructionFormat::REGISTER => {
+                &[("A", 31, 27), ("B", 26, 22), ("C", 21, 17), ("OPX", 16, 11), ("IMM5", 10, 6), ("OP", 5, 0)]
+            }
+            InstructionFormat::IMMEDIATE => &[("A", 31, 27), ("B", 26, 22), ("IMM16", 21, 6), ("OP", 5, 0)],
+            InstructionFormat::JUMP => &[("IMM26", 31, 6), ("OP", 5, 0)],
+            InstructionFormat::PSEUDO => &[],
+        }
+    }
+}
+
 pub struct Instruction {
     pub name: &'static str,
     pub opcode: i32,
@@ -24,8 +49,8 @@ impl Instruction {
     }
 
     pub fn is_conditional_branch(&self) -> bool {
-        // bne, blt, beq, bge
-        self.opcode == 0x26 || self.opcode == 0x1E || self.opcode == 0x0E || self.opcode == 0x16
+        // bne, blt, beq, bge, bgeu, bltu
+        matches!(self.opcode, 0x26 | 0x1E | 0x0E | 0x16 | 0x2E | 0x36)
     }
 
     pub fn is_relative_branch(&self) -> bool {
@@ -38,6 +63,16 @@ impl Instruction {
         self.opx == 0x5
     }
 
+    pub fn is_register_operand_only(&self) -> bool {
+        // jmp, callr, flushi, initi, whose only register is in the A field
+        self.opcode == R_INSTRUCTION_OPCODE && matches!(self.opx, 0x0D | 0x1D | 0x0C | 0x29)
+    }
+
+    pub fn is_immediate_shift(&self) -> bool {
+        // roli, slli, srli, srai
+        self.opcode == R_INSTRUCTION_OPCODE && matches!(self.opx, 0x02 | 0x12 | 0x1A | 0x3A)
+    }
+
     pub fn to_register_format_text(&self, ra: i32, rb: i32, rc: i32, shamt: i32) -> Text {
         Text::new(ra, rb, rc, shamt, self.opx, R_INSTRUCTION_OPCODE, 0, 0)
     }
@@ -0,0 +1,46 @@
+// Minimal JSON writing helpers, the documents are assembled by hand.
+
+use crate::source::SourceLocation;
+
+// `text` as a quoted JSON string.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            character if (character as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", character as u32)),
+            character => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// An object from its already encoded members, on one line.
+pub fn json_object(members: &[(&str, String)]) -> String {
+    let members = members.iter().map(|(name, value)| format!("{}: {}", json_string(name), value)).collect::<Vec<String>>();
+    format!("{{{}}}", members.join(", "))
+}
+
+// An array of already encoded values, one per line with `indent` spaces before each.
+pub fn json_array(values: &[String], indent: usize) -> String {
+    if values.is_empty() {
+        return "[]".to_string();
+    }
+    let padding = " ".repeat(indent);
+    let values = values.iter().map(|value| format!("{}{}", padding, value)).collect::<Vec<String>>();
+    format!("[\n{}\n{}]", values.join(",\n"), &padding[..indent.saturating_sub(2)])
+}
+
+// The `file` and `line` members of a source location, both null when it is unknown.
+pub fn json_location(location: Option<&SourceLocation>) -> (String, String) {
+    match location {
+        Some(location) => (json_string(&location.file.display().to_string()), location.line.to_string()),
+        None => ("null".to_string(), "null".to_string()),
+    }
+}
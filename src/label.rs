@@ -3,9 +3,10 @@ use regex::Regex;
 use crate::constants::{WORD_SIZE};
 use crate::line::Line;
 use crate::section::Section;
+use crate::source::SourceLine;
 
 pub struct Label {
-    name: String,
+    pub name: String,
     pub address: i32,
 }
 
@@ -29,7 +30,7 @@ pub fn is_label(code: &str) -> bool {
 
 pub fn resolve_labels(code: &str) -> Option<Label> {
     let label_regex = Regex::new(r"^.*:").unwrap();
-    let label = if let Some(cap) = label_regex.captures_iter(&code).next() {
+    let label = if let Some(cap) = label_regex.captures_iter(code).next() {
         let name = cap[0].trim_end_matches(':');
         Some(Label::new(name, 0))
     } else {
@@ -37,14 +38,25 @@ pub fn resolve_labels(code: &str) -> Option<Label> {
     }; label
 }
 
-pub fn get_addressed_labels(lines: &[Line], codes: &[String], text_min_address: i32) -> Vec<Label> {
+// Makes the `.L` labels of one input file distinct from those of the other files by appending
+// `$index` to every definition and use.
+pub fn localize_labels(lines: &mut [SourceLine], index: usize) {
+    let local_regex = Regex::new(r"(^|[^\w.$])(\.L\w*)").unwrap();
+    for line in lines.iter_mut() {
+        line.text = local_regex
+            .replace_all(&line.text, |captures: &regex::Captures| format!("{}{}${}", &captures[1], &captures[2], index))
+            .to_string();
+    }
+}
+
+pub fn get_addressed_labels(lines: &[Line], codes: &[SourceLine], text_min_address: i32) -> Vec<Label> {
     let mut current_address = text_min_address;
     let labels = extract_labels_from_lines(lines);
 
     codes
         .iter()
         .filter_map(|code| {
-            if let Some(label) = resolve_labels(&code) {
+            if let Some(label) = resolve_labels(&code.text) {
                 if let Some(label) = find_label(&label.name, &labels) {
                     Some(Label::new(&label.name, current_address))
                 } else {
@@ -62,13 +74,6 @@ fn extract_labels_from_lines(lines: &[Line]) -> Vec<Label> {
     lines
         .iter()
         .filter(|line| line.section == Section::TEXT)
-        .map(|line| {
-            if let Some(label) = resolve_labels(&line.text.as_ref().unwrap()) {
-                Some(label)
-            } else {
-                None
-            }
-        })
-        .filter_map(|label| label)
+        .filter_map(|line| resolve_labels(line.text.as_ref().unwrap()))
         .collect()
 }
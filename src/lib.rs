@@ -0,0 +1,32 @@
+pub mod asm_macro;
+pub mod cli;
+pub mod constants;
+pub mod cpp;
+pub mod datum;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod dwarf;
+pub mod elf;
+pub mod emit;
+pub mod explain;
+pub mod export;
+pub mod expression;
+pub mod image;
+pub mod input;
+pub mod instruction;
+pub mod json;
+pub mod label;
+pub mod line;
+pub mod linker;
+pub mod listing;
+pub mod map;
+pub mod output;
+pub mod preprocessor;
+pub mod relocation;
+pub mod section;
+pub mod simulator;
+pub mod source;
+pub mod symbol;
+pub mod symbolic;
+pub mod text;
+pub mod utils;
@@ -1,58 +1,88 @@
-use crate::constants::{DATA_SECTION_MIN_ADDRESS, WORD_SIZE};
+use std::collections::HashMap;
+
+use crate::constants::WORD_SIZE;
+use crate::datum::is_data_definition;
 use crate::section::{resolve_section, Section};
-use crate::utils::read_lines;
-use std::fs::File;
+use crate::source::{SourceLine, SourceLocation};
 
 pub struct Line {
     pub section: Section,
     pub address: i32,
     pub text: Option<String>,
+    pub location: SourceLocation,
 }
 
 impl Line {
-    fn new(section: Section, address: i32, text: Option<String>) -> Self {
+    fn new(section: Section, address: i32, text: Option<String>, location: &SourceLocation) -> Self {
         Self {
             section,
             address,
             text,
+            location: location.clone(),
         }
     }
 }
 
-pub fn compose_lines(mut input_file: &mut File) -> Vec<Line> {
-    let lines = read_lines(&mut input_file);
+// `.rodata` is laid out right after `.data`, and `.bss` right after `.rodata`.
+pub fn compose_lines(lines: &[SourceLine], data_start_address: i32) -> Vec<Line> {
+    let sizes = count_data_definitions(lines);
+    let rodata_start_address = data_start_address + sizes.get(&Section::DATA).unwrap_or(&0) * WORD_SIZE;
+    let mut addresses = HashMap::new();
+    addresses.insert(Section::DATA, data_start_address);
+    addresses.insert(Section::RODATA, rodata_start_address);
+    addresses.insert(Section::BSS, rodata_start_address + sizes.get(&Section::RODATA).unwrap_or(&0) * WORD_SIZE);
 
-    let mut current_address = DATA_SECTION_MIN_ADDRESS - WORD_SIZE;
     let mut current_section = Section::NONE;
 
     lines
-        .map(|line| {
-            if !line.trim_start().is_empty() {
-                current_section = resolve_section(&line).unwrap_or_else(|| current_section.clone());
-            } else {
+        .iter()
+        .map(|source| {
+            let (line, location) = (&source.text, &source.location);
+            // a blank line ends the current section
+            if line.trim_start().is_empty() {
                 current_section = Section::NONE;
+                return Line::new(Section::NONE, 0, None, location);
+            }
+            if let Some(section) = resolve_section(line) {
+                current_section = section;
+                return Line::new(Section::NONE, 0, None, location);
             }
             match current_section {
-                Section::DATA => {
-                    let result = if resolve_section(&line).is_none() {
-                        Line::new(Section::DATA, current_address, Some(line))
+                section if section.is_data() => {
+                    let address = addresses.get_mut(&section).unwrap();
+                    if is_data_definition(line) {
+                        *address += WORD_SIZE;
+                        Line::new(section, *address - WORD_SIZE, Some(line.clone()), location)
                     } else {
-                        Line::new(Section::NONE, current_address, None)
-                    };
-                    current_address += WORD_SIZE;
-                    result
+                        Line::new(Section::NONE, *address, None, location)
+                    }
                 }
                 Section::TEXT => {
                     // line is a comment
-                    if !line.starts_with(|char| char == ';' || char == '#') {
-                        Line::new(Section::TEXT, current_address, Some(line.trim_end().to_string()))
+                    if !line.starts_with(&[';', '#'][..]) {
+                        Line::new(Section::TEXT, 0, Some(line.trim_end().to_string()), location)
                     }
                     else {
-                        Line::new(Section::NONE, current_address, None)
+                        Line::new(Section::NONE, 0, None, location)
                     }
                 }
-                Section::NONE => Line::new(Section::NONE, current_address, None),
+                _ => Line::new(Section::NONE, 0, None, location),
             }
         })
         .collect::<Vec<Line>>()
 }
+
+fn count_data_definitions(lines: &[SourceLine]) -> HashMap<Section, i32> {
+    let mut sizes = HashMap::new();
+    let mut current_section = Section::NONE;
+    for line in lines.iter() {
+        if line.text.trim_start().is_empty() {
+            current_section = Section::NONE;
+        } else if let Some(section) = resolve_section(&line.text) {
+            current_section = section;
+        } else if current_section.is_data() && is_data_definition(&line.text) {
+            *sizes.entry(current_section).or_insert(0) += 1;
+        }
+    }
+    sizes
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use crate::constants::{WORD_BYTES, WORD_SIZE};
+use crate::elf::{
+    binding_from_elf, relocation_kind, ElfReader, EM_ALTERA_NIOS2, ET_REL, RELA_SIZE, SHN_UNDEF, SHT_NOBITS, SHT_RELA,
+    SHT_SYMTAB, SYMBOL_SIZE,
+};
+use crate::image::{AddressUnit, ImageSymbol, MemoryImage, Segment};
+use crate::relocation::RelocationKind;
+use crate::symbol::Binding;
+
+// Sections merged by the linker, in memory map order: `.text` starts at the text base, `.data` at
+// the data base, and `.rodata` and `.bss` follow `.data` like they do in a single unit.
+const SECTIONS: [&str; 4] = [".text", ".data", ".rodata", ".bss"];
+
+// Offsets are in assembler address units from the start of the section.
+pub struct ObjectSymbol {
+    pub name: String,
+    // `None` when the symbol is defined in another unit
+    pub section: Option<String>,
+    pub offset: i32,
+    pub binding: Binding,
+}
+
+pub struct ObjectRelocation {
+    pub kind: RelocationKind,
+    pub section: String,
+    pub offset: i32,
+    // index in `ObjectFile::symbols`
+    pub symbol: usize,
+    pub addend: i32,
+}
+
+// An assembled unit read back from an `elf-obj` file.
+pub struct ObjectFile {
+    pub name: String,
+    pub sections: Vec<(String, Vec<u32>)>,
+    pub symbols: Vec<ObjectSymbol>,
+    pub relocations: Vec<ObjectRelocation>,
+}
+
+impl ObjectFile {
+    fn section_words(&self, name: &str) -> &[u32] {
+        self.sections.iter().find(|(section, _)| section == name).map_or(&[], |(_, words)| words)
+    }
+}
+
+// Reads an ELF32 relocatable object written by the `elf-obj` format. Byte offsets are converted
+// back to assembler address units.
+pub fn read_object(name: &str, bytes: &[u8]) -> Result<ObjectFile, String> {
+    let reader = ElfReader { name, bytes };
+    if !bytes.starts_with(&[0x7F, b'E', b'L', b'F', 1, 1])
+        || reader.u16(16)? != ET_REL
+        || reader.u16(18)? != EM_ALTERA_NIOS2
+    {
+        return Err(format!("`{}` is not a Nios II ELF32 relocatable object", name));
+    }
+
+    let headers = reader.section_headers()?;
+    let header = |index: usize| headers.get(index).ok_or_else(|| format!("`{}` has an invalid section index", name));
+
+    let mut sections = vec![];
+    for header in headers.iter().filter(|header| SECTIONS.contains(&header.name.as_str())) {
+        let words = if header.kind == SHT_NOBITS {
+            vec![0; header.size / WORD_BYTES as usize]
+        } else {
+            reader
+                .slice(header.offset, header.size)?
+                .chunks(WORD_BYTES as usize)
+                .map(|chunk| chunk.iter().rev().fold(0, |word, byte| (word << 8) | *byte as u32))
+                .collect()
+        };
+        sections.push((header.name.clone(), words));
+    }
+
+    let mut symbols = vec![];
+    if let Some(symtab) = headers.iter().find(|header| header.kind == SHT_SYMTAB) {
+        let strtab = header(symtab.link)?;
+        for offset in (symtab.offset..symtab.offset + symtab.size).step_by(SYMBOL_SIZE as usize) {
+            let info = reader.slice(offset + 12, 1)?[0];
+            let section = match reader.u16(offset + 14)? {
+                SHN_UNDEF => None,
+                index => Some(header(index as usize)?.name.clone()),
+            };
+            if section.as_ref().is_some_and(|section| !SECTIONS.contains(&section.as_str())) {
+                return Err(format!("`{}` defines a symbol outside of the linked sections", name));
+            }
+            symbols.push(ObjectSymbol {
+                name: reader.string(strtab, reader.u32(offset)? as usize)?,
+                section,
+                offset: reader.u32(offset + 4)? as i32 / WORD_BYTES * WORD_SIZE,
+                binding: binding_from_elf(info >> 4)
+                    .ok_or_else(|| format!("`{}` has a symbol with an unknown binding", name))?,
+            });
+        }
+    }
+
+    let mut relocations = vec![];
+    for rela in headers.iter().filter(|header| header.kind == SHT_RELA) {
+        let section = header(rela.info)?.name.clone();
+        if !SECTIONS.contains(&section.as_str()) {
+            return Err(format!("`{}` has relocations outside of the linked sections", name));
+        }
+        for offset in (rela.offset..rela.offset + rela.size).step_by(RELA_SIZE as usize) {
+            let info = reader.u32(offset + 4)?;
+            let kind = relocation_kind(info as u8)
+                .ok_or_else(|| format!("`{}` has an unsupported relocation type {}", name, info & 0xFF))?;
+            let symbol = (info >> 8) as usize;
+            if symbol >= symbols.len() {
+                return Err(format!("`{}` has a relocation against an invalid symbol", name));
+            }
+            relocations.push(ObjectRelocation {
+                kind,
+                section: section.clone(),
+                offset: reader.u32(offset)? as i32 / WORD_BYTES * WORD_SIZE,
+                symbol,
+                addend: reader.u32(offset + 8)? as i32 / WORD_BYTES * WORD_SIZE,
+            });
+        }
+    }
+
+    Ok(ObjectFile {
+        name: name.to_string(),
+        sections,
+        symbols,
+        relocations,
+    })
+}
+
+// Merges the units into one image, placing the sections of each unit after those of the previous
+// units, and applies every relocation. Errors name the units involved.
+pub fn link(objects: &[ObjectFile], text_start_address: i32, data_start_address: i32) -> Result<MemoryImage, Vec<String>> {
+    let mut bases: HashMap<(usize, &str), i32> = HashMap::new();
+    let mut segments = vec![];
+    let mut address = text_start_address;
+    for section in SECTIONS.iter() {
+        if *section == ".data" {
+            address = data_start_address;
+        }
+        let mut words = vec![];
+        for (unit, object) in objects.iter().enumerate() {
+            bases.insert((unit, *section), address + words.len() as i32 * WORD_SIZE);
+            words.extend_from_slice(object.section_words(section));
+        }
+        let segment = Segment::new(section, address, words);
+        address = segment.end();
+        // `.rodata` and `.bss` only exist when they hold data
+        if segments.len() < 2 || !segment.words.is_empty() {
+            segments.push(segment);
+        }
+    }
+
+    // a global definition takes precedence over a weak one, two global definitions are an error
+    let mut errors = vec![];
+    let mut globals: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (unit, object) in objects.iter().enumerate() {
+        for (index, symbol) in object.symbols.iter().enumerate() {
+            if symbol.section.is_none() || symbol.binding == Binding::LOCAL || symbol.name.is_empty() {
+                continue;
+            }
+            match globals.get(symbol.name.as_str()) {
+                Some(&(other_unit, other_index)) => {
+                    let other = &objects[other_unit].symbols[other_index];
+                    if other.binding == Binding::GLOBAL && symbol.binding == Binding::GLOBAL {
+                        errors.push(format!(
+                            "duplicate symbol `{}` defined in {} and {}",
+                            symbol.name, objects[other_unit].name, object.name
+                        ));
+                    } else if other.binding == Binding::WEAK && symbol.binding == Binding::GLOBAL {
+                        globals.insert(&symbol.name, (unit, index));
+                    }
+                }
+                None => {
+                    globals.insert(&symbol.name, (unit, index));
+                }
+            }
+        }
+    }
+
+    let definition_address = |unit: usize, symbol: &ObjectSymbol| {
+        symbol.section.as_ref().map(|section| bases[&(unit, section.as_str())] + symbol.offset)
+    };
+    // weak references nobody defines resolve to zero
+    let resolve = |unit: usize, symbol: &ObjectSymbol| {
+        if symbol.binding == Binding::LOCAL || symbol.name.is_empty() {
+            definition_address(unit, symbol)
+        } else if let Some(&(other_unit, other_index)) = globals.get(symbol.name.as_str()) {
+            definition_address(other_unit, &objects[other_unit].symbols[other_index])
+        } else if symbol.binding == Binding::WEAK {
+            Some(0)
+        } else {
+            None
+        }
+    };
+
+    let mut undefined: Vec<(&str, Vec<&str>)> = vec![];
+    for (unit, object) in objects.iter().enumerate() {
+        for relocation in object.relocations.iter() {
+            let symbol = &object.symbols[relocation.symbol];
+            let value = match resolve(unit, symbol) {
+                Some(value) => value + relocation.addend,
+                None => {
+                    match undefined.iter_mut().find(|(name, _)| *name == symbol.name) {
+                        Some((_, units)) if !units.contains(&object.name.as_str()) => units.push(&object.name),
+                        Some(_) => {}
+                        None => undefined.push((&symbol.name, vec![&object.name])),
+                    }
+                    continue;
+                }
+            };
+            let address = bases[&(unit, relocation.section.as_str())] + relocation.offset;
+            match segments.iter_mut().find(|segment| segment.name == relocation.section) {
+                Some(segment) if (segment.base..segment.end()).contains(&address) => {
+                    let index = ((address - segment.base) / WORD_SIZE) as usize;
+                    segment.words[index] = relocation.kind.apply(segment.words[index], address, value, AddressUnit::BYTE);
+                }
+                _ => errors.push(format!(
+                    "relocation {} in {} is outside of its section",
+                    relocation.kind.name(),
+                    object.name
+                )),
+            }
+        }
+    }
+    for (name, units) in undefined.iter() {
+        errors.push(format!("undefined symbol `{}` referenced by {}", name, units.join(", ")));
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut symbols = vec![];
+    let mut externals: Vec<(String, Binding)> = vec![];
+    for (unit, object) in objects.iter().enumerate() {
+        for (index, symbol) in object.symbols.iter().enumerate() {
+            if symbol.name.is_empty() {
+                continue;
+            }
+            match &symbol.section {
+                // weak definitions overridden by a global one are dropped
+                Some(section) if symbol.binding == Binding::LOCAL || globals[symbol.name.as_str()] == (unit, index) => {
+                    symbols.push(ImageSymbol {
+                        name: symbol.name.clone(),
+                        address: bases[&(unit, section.as_str())] + symbol.offset,
+                        section: section.clone(),
+                        binding: symbol.binding,
+                    })
+                }
+                None if !globals.contains_key(symbol.name.as_str())
+                    && !externals.iter().any(|(name, _)| *name == symbol.name) =>
+                {
+                    externals.push((symbol.name.clone(), symbol.binding))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(MemoryImage {
+        segments,
+        symbols,
+        relocations: vec![],
+        externals,
+        line_table: vec![],
+        // objects are ELF files, which address bytes
+        unit: AddressUnit::BYTE,
+    })
+}
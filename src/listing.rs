@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use regex::Regex;
+
+use crate::asm_macro::disassemble_macro;
+use crate::constants::WORD_SIZE;
+use crate::datum::Datum;
+use crate::label::is_label;
+use crate::line::Line;
+use crate::map::{binding_name, SymbolKind, SymbolMap};
+use crate::section::{resolve_section, Section};
+use crate::source::{SourceLine, SourceLocation};
+use crate::symbol::Binding;
+use crate::text::Text;
+
+// A source line as the preprocessor met it, in a macro expansion `macro_depth` levels deep, and
+// the range of the lines it output.
+pub struct ListingEntry {
+    pub line: SourceLine,
+    pub macro_depth: usize,
+    pub output: Range<usize>,
+}
+
+// The assembled program for the listing. `lines` are the composed output lines of the entries,
+// `texts` the instructions from `text_start_address`.
+pub struct ListingInput<'a> {
+    pub entries: &'a [ListingEntry],
+    pub lines: &'a [Line],
+    pub texts: &'a [Text],
+    pub data: &'a [Datum],
+    pub map: &'a SymbolMap,
+    pub text_start_address: i32,
+}
+
+// Listing in the spirit of GNU `as -al`: each source line with its line number, its address and
+// the words it encodes to, data as little-endian bytes, then the symbols with their references.
+// Lines from an included file are marked with one `>` per include level, and lines from a macro
+// expansion with one `+` per nesting level.
+pub fn write_listing(input: &ListingInput) -> String {
+    let words = encoded_words(input);
+
+    // an output line belongs to the innermost entry, which comes last
+    let mut owners = vec![0; input.lines.len()];
+    for (index, entry) in input.entries.iter().enumerate() {
+        for owner in owners[entry.output.clone()].iter_mut() {
+            *owner = index;
+        }
+    }
+    let mut entry_words = vec![vec![]; input.entries.len()];
+    for (line, owner) in owners.iter().enumerate() {
+        entry_words[*owner].extend(words[line].iter().cloned());
+    }
+
+    let mut listing = String::new();
+    let mut file: Option<Rc<PathBuf>> = None;
+    for (entry, words) in input.entries.iter().zip(entry_words.iter()) {
+        let location = &entry.line.location;
+        if file.as_ref() != Some(&location.file) {
+            listing += &format!("==== {}", location.file.display());
+            if let Some(parent) = &location.included_from {
+                listing += &format!(" (included from {})", parent);
+            }
+            listing += "\n";
+            file = Some(location.file.clone());
+        }
+
+        let markers = format!("{}{}", ">".repeat(location.include_chain().len()), "+".repeat(entry.macro_depth));
+        let (address, hex) = words.first().map_or((String::new(), String::new()), |(address, hex)| {
+            (format!("{:08x}", address), hex.clone())
+        });
+        let line = format!("{:>5} {:<4}{:<8} {:<8}  {}", location.line, markers, address, hex, entry.line.text);
+        listing += line.trim_end();
+        listing += "\n";
+        for (address, hex) in words.iter().skip(1) {
+            listing += &format!("{:>5} {:<4}{:08x} {}\n", "", "", address, hex);
+        }
+    }
+
+    listing + &symbol_table(input)
+}
+
+// Address and hexadecimal encoding of the words of each output line.
+fn encoded_words(input: &ListingInput) -> Vec<Vec<(i32, String)>> {
+    let mut texts = input.texts.iter();
+    let mut address = input.text_start_address;
+    input
+        .lines
+        .iter()
+        .map(|line| match (&line.text, line.section) {
+            // the instructions as `extract_codes` finds them, pseudo-instructions expanding to several
+            (Some(text), Section::TEXT) if resolve_section(text).is_none() && !is_label(text) => {
+                let count = disassemble_macro(text).map_or(1, |codes| codes.len());
+                texts
+                    .by_ref()
+                    .take(count)
+                    .map(|text| {
+                        address += WORD_SIZE;
+                        (address - WORD_SIZE, text.to_hex())
+                    })
+                    .collect()
+            }
+            (Some(_), section) if section.is_data() => input
+                .data
+                .iter()
+                .filter(|datum| datum.address == line.address)
+                .map(|datum| {
+                    let bytes = datum.to_word().to_le_bytes();
+                    (datum.address, bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+                })
+                .collect(),
+            _ => vec![],
+        })
+        .collect()
+}
+
+struct ListedSymbol {
+    value: String,
+    section: String,
+    binding: Binding,
+    definition: String,
+    references: Vec<String>,
+}
+
+fn symbol_table(input: &ListingInput) -> String {
+    let mut symbols = BTreeMap::new();
+    for symbol in input.map.symbols.iter() {
+        let definition = match (&symbol.location, symbol.kind) {
+            (Some(location), _) => location_text(location),
+            (None, SymbolKind::CONSTANT) => "command line".to_string(),
+            (None, _) => String::new(),
+        };
+        symbols.insert(symbol.name.clone(), ListedSymbol {
+            value: match symbol.kind {
+                SymbolKind::UNDEFINED => "undefined".to_string(),
+                _ => format!("{:08x}", symbol.value),
+            },
+            section: symbol.section.clone(),
+            binding: symbol.binding,
+            definition,
+            references: vec![],
+        });
+    }
+
+    let identifier = Regex::new(r"(^|[^\w.$%])([A-Za-z_.$][\w.$]*)").unwrap();
+    for line in input.lines.iter() {
+        let text = match &line.text {
+            Some(text) => text,
+            None => continue,
+        };
+        let operands = match text.split_once(':') {
+            Some((_, rest)) if is_label(text) => rest,
+            _ => text.as_str(),
+        };
+        // the first word is the mnemonic or the directive
+        let operands = operands.trim_start().split_once(char::is_whitespace).map_or("", |(_, operands)| operands);
+        for captures in identifier.captures_iter(operands) {
+            if let Some(symbol) = symbols.get_mut(&captures[2]) {
+                let location = location_text(&line.location);
+                if !symbol.references.contains(&location) {
+                    symbol.references.push(location);
+                }
+            }
+        }
+    }
+
+    let width = symbols.keys().map(|name| name.len()).max().unwrap_or(0).max(6);
+    let mut table = format!(
+        "\nSymbols:\n{:<width$}  {:<9} {:<8} {:<7} {:<16} {}\n",
+        "name",
+        "value",
+        "section",
+        "binding",
+        "defined at",
+        "referenced at",
+        width = width
+    );
+    for (name, symbol) in symbols.iter() {
+        let line = format!(
+            "{:<width$}  {:<9} {:<8} {:<7} {:<16} {}",
+            name,
+            symbol.value,
+            symbol.section,
+            binding_name(symbol.binding),
+            symbol.definition,
+            symbol.references.join(", "),
+            width = width
+        );
+        table += line.trim_end();
+        table += "\n";
+    }
+    table
+}
+
+fn location_text(location: &SourceLocation) -> String {
+    let file = location.file.file_name().map_or_else(|| location.file.display().to_string(), |name| {
+        name.to_string_lossy().into_owned()
+    });
+    format!("{}:{}", file, location.line)
+}
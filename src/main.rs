@@ -1,70 +1,356 @@
+use std::collections::HashSet;
 use std::env;
-use std::fs::File;
-use std::io::Write;
-
-mod constants;
-mod datum;
-mod instruction;
-mod label;
-mod line;
-mod asm_macro;
-mod section;
-mod text;
-mod utils;
-
-use crate::constants::{TEXT_SECTION_MIN_ADDRESS, DATA_SECTION_MIN_ADDRESS, WORD_SIZE};
-use crate::datum::{extract_data_from_lines, Datum};
-use crate::label::{get_addressed_labels, is_label, resolve_labels, Label};
-use crate::line::{compose_lines, Line};
-use crate::asm_macro::disassemble_macro;
-use crate::section::{resolve_section, Section};
-use crate::text::{get_text_from_code, Text};
-use crate::utils::{convert_string_to_int};
-fn main() {
-    println!("nios2assembler-rs (version 1.0.0)");
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+use nios2asm::cli::{
+    parse_command, usage, Command, DisassembleOptions, ExplainOptions, LinkOptions, MapOptions, Options, SimulateOptions, Verbosity,
+    EXIT_FAILURE, EXIT_USAGE, STANDARD_STREAM,
+};
+use nios2asm::constants::WORD_SIZE;
+use nios2asm::cpp::{is_preprocessed_source, CPreprocessor};
+use nios2asm::datum::{extract_data_from_lines, Datum};
+use nios2asm::diagnostic::Diagnostic;
+use nios2asm::disassembler::{disassemble, disassemble_word};
+use nios2asm::emit::{emit_json, EmitInput, EMIT_JSON};
+use nios2asm::explain::explain;
+use nios2asm::export::export_symbols;
+use nios2asm::image::{AddressUnit, MemoryImage};
+use nios2asm::input::read_image;
+use nios2asm::label::{get_addressed_labels, is_label, localize_labels, resolve_labels, Label};
+use nios2asm::line::{compose_lines, Line};
+use nios2asm::linker::{link, read_object, ObjectFile};
+use nios2asm::listing::{write_listing, ListingInput};
+use nios2asm::map::SymbolMap;
+use nios2asm::output::{find_output_format, OutputFormat, OutputOptions};
+use nios2asm::asm_macro::disassemble_macro;
+use nios2asm::preprocessor::Preprocessor;
+use nios2asm::section::{resolve_section, Section};
+use nios2asm::simulator::{Simulator, Stop};
+use nios2asm::source::{split_source_text, SourceLine, SourceLocation};
+use nios2asm::symbol::{Binding, SymbolTable};
+use nios2asm::symbolic::{disassemble_symbolic, read_symbol_file};
+use nios2asm::text::{get_text_from_code, Text};
+use nios2asm::utils::exit_with_error;
 
+fn main() {
     let args: Vec<String> = env::args().collect();
+    let program = Path::new(&args[0]).file_name().map_or("nios2asm".into(), |name| name.to_string_lossy());
 
-    if args.len() < 3 {
-        println!("./{} [input_file] [output_file] (text_min_address) (data_min_address)", &args[0]);
-        return;
+    let (command, verbosity) = parse_command(&args).unwrap_or_else(|message| {
+        eprintln!("error: {}", message);
+        eprintln!("Try `{} --help` for more information.", program);
+        process::exit(EXIT_USAGE);
+    });
+    match command {
+        Command::Help => println!("{}", usage(&program)),
+        Command::Version => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        Command::Assemble(options) => assemble(&options, verbosity),
+        Command::Disassemble(options) => disassemble_image(&options),
+        Command::Link(options) => link_objects(&options, verbosity),
+        Command::Explain(options) => explain_instruction(&options),
+        Command::Simulate(options) => simulate(&options),
     }
+}
 
-    let mut text_start_address = TEXT_SECTION_MIN_ADDRESS;
-    let mut data_start_address = DATA_SECTION_MIN_ADDRESS;
+fn assemble(options: &Options, verbosity: Verbosity) {
+    let text_start_address = options.text_start_address;
+    let data_start_address = options.data_start_address;
 
-    if args.len() >= 5 {
-        text_start_address = convert_string_to_int(&args[3]);
-        data_start_address = convert_string_to_int(&args[4]);
+    let mut symbols = SymbolTable::new();
+    for (name, value) in options.definitions.iter() {
+        symbols.define(name, *value);
     }
 
-    let input_filepath = &args[1];
-    let output_filepath = &args[2];
-    let mut input_file = File::open(input_filepath).expect("Failed to read input file.");
-
-    let lines = compose_lines(&mut input_file);
+    // the files are concatenated, each of them starting in `.text` with its own `.L` labels
+    let mut preprocessor = Preprocessor::new(symbols, &options.include_paths);
+    if options.listing_filepath.is_some() {
+        preprocessor.record_listing();
+    }
+    let mut source = vec![];
+    let mut entries = vec![];
+    for (index, filepath) in options.input_filepaths.iter().enumerate() {
+        report(verbosity, &format!("reading {}", display_name(filepath)));
+        let mut lines = read_input(filepath, options);
+        if index > 0 {
+            let location = SourceLocation::new(Rc::new(PathBuf::from(filepath)), 1, None);
+            lines.insert(0, SourceLine::new("\t.text", location));
+        }
+        let result = preprocessor.run(&lines);
+        let mut lines = result.unwrap_or_else(|diagnostic| fail(options, &preprocessor, &diagnostic, verbosity));
+        if options.input_filepaths.len() > 1 {
+            localize_labels(&mut lines, index);
+        }
+        // the `.text` line added above is not part of the file
+        for mut entry in preprocessor.take_listing().into_iter().skip(if index > 0 { 1 } else { 0 }) {
+            entry.output = entry.output.start + source.len()..entry.output.end + source.len();
+            entries.push(entry);
+        }
+        source.extend(lines);
+    }
+    print_warnings(&preprocessor, verbosity);
+    let symbols = preprocessor.symbols();
+    let lines = compose_lines(&source, data_start_address);
 
-    let data = extract_data_from_lines(&lines);
-    let codes = extract_codes(&lines, &data);
+    let codes = extract_codes(&lines);
     let labels = get_addressed_labels(&lines, &codes, text_start_address);
-    println!("[+] Disassembling instructions ...");
-    let texts = disassemble_instructions(&data, &labels, &codes);
-    println!("[+] Writing output assembly file.");
-    write_output(output_filepath, &data, &texts, text_start_address, data_start_address);
+    let format = find_output_format(&options.format)
+        .unwrap_or_else(|| exit_with_error(&format!("unknown output format `{}`", options.format)));
+    let unit = format.address_unit();
+    let data = extract_data_from_lines(&lines, &labels, symbols, unit)
+        .unwrap_or_else(|diagnostic| fail(options, &preprocessor, &diagnostic, verbosity));
+    report(verbosity, "encoding instructions");
+    let (texts, mut errors) = disassemble_instructions(&data, &labels, symbols, &codes, text_start_address, unit);
+    let image = MemoryImage::new(&texts, &data, &labels, symbols, text_start_address, data_start_address, unit);
+    if let Err(message) = image.check_overlaps() {
+        errors.push(Diagnostic::error(&message, None));
+    }
+
+    // weak references resolve to zero, anything else must be defined unless a linker runs next
+    if !format.relocatable() {
+        for (name, _) in image.externals.iter().filter(|(_, binding)| *binding != Binding::WEAK) {
+            let location = first_reference(name, &texts, &data, &lines);
+            errors.push(Diagnostic::error(&format!("undefined symbol `{}`", name), location));
+        }
+    }
+
+    let mut map = SymbolMap::new(&image);
+    map.add_constants(symbols, preprocessor.definitions());
+    map.locate_labels(&lines);
+    if options.emit == EMIT_JSON {
+        let mut diagnostics = preprocessor.warnings().to_vec();
+        diagnostics.extend(errors.iter().cloned());
+        let input = EmitInput {
+            lines: &lines,
+            texts: &texts,
+            data: &data,
+            map: &map,
+            diagnostics: &diagnostics,
+            text_start_address,
+        };
+        write_document(options, &input, verbosity);
+    }
+    if !errors.is_empty() {
+        for error in errors.iter() {
+            eprintln!("{}", error);
+        }
+        process::exit(EXIT_FAILURE);
+    }
+    if options.emit != EMIT_JSON {
+        write_output(&options.output_filepath, format, &options.output_options, &image, verbosity);
+    }
+    write_map(&options.map_options, &map, symbols.exports(), verbosity);
+    if let Some(filepath) = &options.listing_filepath {
+        let listing = write_listing(&ListingInput {
+            entries: &entries,
+            lines: &lines,
+            texts: &texts,
+            data: &data,
+            map: &map,
+            text_start_address,
+        });
+        report(verbosity, &format!("writing listing to {}", filepath));
+        write_file(filepath, listing.as_bytes());
+    }
+}
+
+// Reports an error of the source after the warnings, in the JSON document as well when one is emitted.
+fn fail(options: &Options, preprocessor: &Preprocessor, diagnostic: &Diagnostic, verbosity: Verbosity) -> ! {
+    if options.emit == EMIT_JSON {
+        // the document holds the diagnostics only, there is nothing assembled
+        let mut diagnostics = preprocessor.warnings().to_vec();
+        diagnostics.push(diagnostic.clone());
+        let map = SymbolMap { sections: vec![], symbols: vec![] };
+        let input = EmitInput {
+            lines: &[],
+            texts: &[],
+            data: &[],
+            map: &map,
+            diagnostics: &diagnostics,
+            text_start_address: options.text_start_address,
+        };
+        write_document(options, &input, verbosity);
+    }
+    print_warnings(preprocessor, verbosity);
+    eprintln!("{}", diagnostic);
+    process::exit(EXIT_FAILURE);
+}
+
+fn write_document(options: &Options, input: &EmitInput, verbosity: Verbosity) {
+    report(verbosity, &format!("writing the JSON document to {}", display_name(&options.output_filepath)));
+    write_file(&options.output_filepath, emit_json(input).as_bytes());
+}
+
+fn print_warnings(preprocessor: &Preprocessor, verbosity: Verbosity) {
+    if verbosity > Verbosity::QUIET {
+        for warning in preprocessor.warnings() {
+            eprintln!("{}", warning);
+        }
+    }
+}
+
+fn report(verbosity: Verbosity, message: &str) {
+    if verbosity == Verbosity::VERBOSE {
+        eprintln!("{}", message);
+    }
+}
+
+fn display_name(filepath: &str) -> &str {
+    if filepath == STANDARD_STREAM {
+        "<stdin>"
+    } else {
+        filepath
+    }
+}
+
+fn read_input_bytes(filepath: &str) -> Vec<u8> {
+    let result = if filepath == STANDARD_STREAM {
+        let mut bytes = vec![];
+        io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+    } else {
+        fs::read(filepath)
+    };
+    result.unwrap_or_else(|error| exit_with_error(&format!("cannot read `{}`: {}", display_name(filepath), error)))
+}
+
+fn read_input(filepath: &str, options: &Options) -> Vec<SourceLine> {
+    let bytes = read_input_bytes(filepath);
+    let input_path = Path::new(display_name(filepath));
+    let source = split_source_text(input_path, &String::from_utf8_lossy(&bytes));
+    if !options.preprocess && !is_preprocessed_source(input_path) {
+        return source;
+    }
+
+    let mut cpp = CPreprocessor::new(&options.include_paths);
+    for (name, value) in options.definitions.iter() {
+        cpp.define(name, &value.to_string());
+    }
+    cpp.run(&source).unwrap_or_else(|diagnostic| {
+        eprintln!("{}", diagnostic);
+        process::exit(EXIT_FAILURE);
+    })
+}
+
+fn link_objects(options: &LinkOptions, verbosity: Verbosity) {
+    let objects = options
+        .input_filepaths
+        .iter()
+        .map(|filepath| {
+            report(verbosity, &format!("reading {}", display_name(filepath)));
+            let bytes = read_input_bytes(filepath);
+            read_object(display_name(filepath), &bytes).unwrap_or_else(|message| exit_with_error(&message))
+        })
+        .collect::<Vec<ObjectFile>>();
+
+    report(verbosity, "linking");
+    let image = link(&objects, options.text_start_address, options.data_start_address).unwrap_or_else(|errors| {
+        for error in errors {
+            eprintln!("error: {}", error);
+        }
+        process::exit(EXIT_FAILURE);
+    });
+    image.check_overlaps().unwrap_or_else(|message| exit_with_error(&message));
+
+    let format = find_output_format(&options.format)
+        .unwrap_or_else(|| exit_with_error(&format!("unknown output format `{}`", options.format)));
+    write_output(&options.output_filepath, format, &options.output_options, &image, verbosity);
+    // the objects do not tell which symbols were marked by `.export`
+    write_map(&options.map_options, &SymbolMap::new(&image), &HashSet::new(), verbosity);
+}
+
+// Writes the map and the exported symbols, `marked` naming the symbols to export besides the global ones.
+fn write_map(options: &MapOptions, map: &SymbolMap, marked: &HashSet<String>, verbosity: Verbosity) {
+    if let Some(filepath) = &options.filepath {
+        report(verbosity, &format!("writing map to {}", display_name(filepath)));
+        // the format was checked with the arguments
+        write_file(filepath, map.write(&options.format).unwrap_or_default().as_bytes());
+    }
+    for filepath in options.export_filepaths.iter() {
+        report(verbosity, &format!("exporting symbols to {}", display_name(filepath)));
+        let output = export_symbols(map, marked, Path::new(filepath)).unwrap_or_else(|message| exit_with_error(&message));
+        write_file(filepath, output.as_bytes());
+    }
+}
 
-    println!("[+] Done!");
+// Lists every segment of a memory image, see `read_image` for the accepted formats.
+fn disassemble_image(options: &DisassembleOptions) {
+    let bytes = read_input_bytes(&options.input_filepath);
+    let image = read_image(&bytes, options.text_start_address)
+        .unwrap_or_else(|message| exit_with_error(&format!("{}: {}", display_name(&options.input_filepath), message)));
+    let listing = if options.symbolic {
+        if image.unit == AddressUnit::BYTE {
+            exit_with_error("symbolic disassembly needs a word-addressed image, not an ELF file or a flat binary");
+        }
+        let symbols = options.symbols_filepath.as_ref().map_or(vec![], |filepath| {
+            let text = String::from_utf8_lossy(&read_input_bytes(filepath)).into_owned();
+            read_symbol_file(&text).unwrap_or_else(|message| exit_with_error(&format!("{}: {}", filepath, message)))
+        });
+        disassemble_symbolic(&image.segments, &symbols).unwrap_or_else(|message| exit_with_error(&message))
+    } else {
+        image.segments.iter().map(|segment| disassemble(&segment.words, segment.base, image.unit)).collect::<String>()
+    };
+    write_file(&options.output_filepath, listing.as_bytes());
 }
 
-fn extract_codes(lines: &[Line], data: &[Datum]) -> Vec<String> {
+fn explain_instruction(options: &ExplainOptions) {
+    let explanation = explain(&options.input, options.address).unwrap_or_else(|message| exit_with_error(&message));
+    print!("{}", explanation);
+}
+
+// Runs an image until its `break`, then prints the registers. Stopping anywhere else is an error,
+// reported after the registers.
+fn simulate(options: &SimulateOptions) {
+    let bytes = read_input_bytes(&options.input_filepath);
+    let mut simulator = Simulator::from_bytes(&bytes, options.text_start_address)
+        .unwrap_or_else(|message| exit_with_error(&format!("{}: {}", display_name(&options.input_filepath), message)));
+    if let Some(address) = options.reset_address {
+        simulator.reset_address = address as u32;
+        simulator.reset();
+    }
+    if let Some(address) = options.exception_address {
+        simulator.exception_address = address as u32;
+    }
+    simulator.unimplemented.extend(options.unimplemented.iter());
+    for (line, step) in options.irqs.iter() {
+        simulator.schedule_irq(*line, *step);
+    }
+    if let Some(entry) = options.entry {
+        simulator.pc = entry as u32;
+    }
+    let stop = simulator.run_traced(options.max_steps, |simulator| {
+        if options.trace {
+            let word = simulator.fetch();
+            println!("{:08x}: {:08x}  {}", simulator.pc, word, disassemble_word(word, simulator.pc as i32, simulator.unit));
+        }
+    });
+    let plural = if simulator.steps == 1 { "" } else { "s" };
+    let error = match stop {
+        Stop::BREAK => {
+            println!("break at {:#010x} after {} instruction{}", simulator.pc, simulator.steps, plural);
+            None
+        }
+        Stop::LIMIT => Some(format!("no `break` after {} instruction{}", simulator.steps, plural)),
+        Stop::INVALID(word) => Some(format!("invalid instruction {:#010x} at {:#010x}", word, simulator.pc)),
+    };
+    print!("{}", simulator.dump_registers());
+    if let Some(message) = error {
+        exit_with_error(&message);
+    }
+}
+
+fn extract_codes(lines: &[Line]) -> Vec<SourceLine> {
     lines
         .iter()
         .filter(|line| {
             line.section == Section::TEXT && resolve_section(line.text.as_ref().unwrap()).is_none()
         })
         .flat_map(|line| {
-            if !is_label(&line.text.as_ref().unwrap()) {
+            if !is_label(line.text.as_ref().unwrap()) {
                 if let Some(pseudo_instruction_codes) =
-                disassemble_macro(&line.text.as_ref().unwrap(), &data)
+                disassemble_macro(line.text.as_ref().unwrap())
                 {
                     pseudo_instruction_codes
                 } else {
@@ -73,35 +359,74 @@ fn extract_codes(lines: &[Line], data: &[Datum]) -> Vec<String> {
             } else {
                 vec![line.text.clone().unwrap()]
             }
+            .into_iter()
+            .map(move |code| SourceLine::new(&code, line.location.clone()))
         })
         .collect()
 }
 
-fn disassemble_instructions(data: &[Datum], labels: &[Label], codes: &[String]) -> Vec<Text> {
-    let mut current_address = TEXT_SECTION_MIN_ADDRESS;
-    codes
+fn disassemble_instructions(
+    data: &[Datum],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    codes: &[SourceLine],
+    text_start_address: i32,
+    unit: AddressUnit,
+) -> (Vec<Text>, Vec<Diagnostic>) {
+    let mut current_address = text_start_address;
+    let mut errors = vec![];
+    let texts = codes
         .iter()
-        .filter_map(|code| {
-            if resolve_labels(&code).is_none() {
-                let text = get_text_from_code(&code, current_address, &data, &labels);
-                current_address += WORD_SIZE;
-                Some(text)
-            } else {
-                None
-            }
+        .filter(|code| resolve_labels(&code.text).is_none())
+        .map(|code| {
+            let location = Some(&code.location);
+            // an instruction that does not encode keeps its word, so that the others keep their addresses
+            let text = get_text_from_code(&code.text, current_address, data, labels, symbols, unit, location)
+                .unwrap_or_else(|error| {
+                    errors.push(error);
+                    let mut text = Text::from_word(0, current_address);
+                    text.location = location.cloned();
+                    text
+                });
+            current_address += WORD_SIZE;
+            text
         })
-        .collect()
+        .collect();
+    (texts, errors)
 }
 
-fn write_output(filepath: &str, data: &[Datum], texts: &[Text], text_start_address: i32, data_start_address: i32) {
-    let text_section_size = texts.len() as i32 * WORD_SIZE;
+// Where `name` is first used, by an instruction or else by a datum.
+fn first_reference<'a>(name: &str, texts: &'a [Text], data: &[Datum], lines: &'a [Line]) -> Option<&'a SourceLocation> {
+    let text = texts.iter().find(|text| text.reference.as_ref().is_some_and(|reference| reference.symbol == name));
+    match text {
+        Some(text) => text.location.as_ref(),
+        None => {
+            let datum = data.iter().find(|datum| datum.symbol.as_deref() == Some(name))?;
+            let line = lines.iter().find(|line| line.section.is_data() && line.address == datum.address && line.text.is_some());
+            line.map(|line| &line.location)
+        }
+    }
+}
 
-    assert!(text_start_address + text_section_size < data_start_address, "The .text section overlap the .data section!");
-    let mut result = vec![format!("v2.0 raw\n{}*0 ", text_start_address)];
-    result.extend(texts.iter().map(|text| format!("{} ", text.to_hex())));
-    result.push(format!("\n{}*0 ", data_start_address - text_section_size));
-    result.extend(data.iter().map(|datum| format!("{} ", datum.to_hex())));
-    let mut file = File::create(filepath).expect("Failed to crate output file.");
+fn write_output(
+    filepath: &str,
+    format: &dyn OutputFormat,
+    options: &OutputOptions,
+    image: &MemoryImage,
+    verbosity: Verbosity,
+) {
+    // the output is complete before the file is touched, so a failure leaves no partial file
+    let mut bytes = vec![];
+    format.write(image, options, &mut bytes).unwrap_or_else(|error| exit_with_error(&error.to_string()));
+    report(verbosity, &format!("writing {} output to {}", format.name(), filepath));
+    write_file(filepath, &bytes);
+}
 
-    write!(file, "{}", result.join("").trim_end()).expect("Failed to write output file.");
+fn write_file(filepath: &str, bytes: &[u8]) {
+    let result = if filepath == STANDARD_STREAM {
+        io::stdout().lock().write_all(bytes)
+    } else {
+        fs::write(filepath, bytes)
+    };
+    result.unwrap_or_else(|error| exit_with_error(&format!("cannot write `{}`: {}", filepath, error)));
 }
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use crate::image::MemoryImage;
+use crate::json::{json_array, json_location, json_object, json_string};
+use crate::label::is_label;
+use crate::line::Line;
+use crate::source::SourceLocation;
+use crate::symbol::{Binding, SymbolTable};
+
+// Section names of the symbols that are not addresses in a section, as `nm` and `objdump` write them.
+pub const ABSOLUTE_SECTION: &str = "*ABS*";
+pub const UNDEFINED_SECTION: &str = "*UND*";
+
+pub const MAP_FORMATS: [&str; 3] = ["text", "json", "nm"];
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind {
+    TEXT,
+    DATA,
+    CONSTANT,
+    UNDEFINED,
+}
+
+impl SymbolKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::TEXT => "text",
+            SymbolKind::DATA => "data",
+            SymbolKind::CONSTANT => "constant",
+            SymbolKind::UNDEFINED => "undefined",
+        }
+    }
+}
+
+pub fn binding_name(binding: Binding) -> &'static str {
+    match binding {
+        Binding::LOCAL => "local",
+        Binding::GLOBAL => "global",
+        Binding::WEAK => "weak",
+    }
+}
+
+// Sizes and addresses count words, `end` is the first address after the section.
+pub struct MapSection {
+    pub name: String,
+    pub base: i32,
+    pub size: i32,
+    pub end: i32,
+}
+
+pub struct MapSymbol {
+    pub name: String,
+    // the address, or the value of a constant
+    pub value: i32,
+    pub section: String,
+    pub kind: SymbolKind,
+    pub binding: Binding,
+    // unknown for linked images and for symbols defined on the command line
+    pub location: Option<SourceLocation>,
+}
+
+// Where the sections and symbols of a program ended up, the symbols sorted by name.
+pub struct SymbolMap {
+    pub sections: Vec<MapSection>,
+    pub symbols: Vec<MapSymbol>,
+}
+
+impl SymbolMap {
+    pub fn new(image: &MemoryImage) -> Self {
+        let sections = image
+            .segments
+            .iter()
+            .map(|segment| MapSection {
+                name: segment.name.clone(),
+                base: segment.base,
+                size: segment.words.len() as i32,
+                end: segment.end(),
+            })
+            .collect();
+        let mut symbols = image
+            .symbols
+            .iter()
+            .map(|symbol| MapSymbol {
+                name: symbol.name.clone(),
+                value: symbol.address,
+                section: symbol.section.clone(),
+                kind: if symbol.section == ".text" { SymbolKind::TEXT } else { SymbolKind::DATA },
+                binding: symbol.binding,
+                location: None,
+            })
+            .collect::<Vec<MapSymbol>>();
+        symbols.extend(image.externals.iter().map(|(name, binding)| MapSymbol {
+            name: name.clone(),
+            value: 0,
+            section: UNDEFINED_SECTION.to_string(),
+            kind: SymbolKind::UNDEFINED,
+            binding: *binding,
+            location: None,
+        }));
+        let mut map = Self { sections, symbols };
+        map.sort();
+        map
+    }
+
+    // Adds the `.equ` constants and those defined on the command line, `definitions` giving where
+    // the others were defined.
+    pub fn add_constants(&mut self, symbols: &SymbolTable, definitions: &HashMap<String, SourceLocation>) {
+        for (name, value) in symbols.constants() {
+            self.symbols.push(MapSymbol {
+                name: name.to_string(),
+                value,
+                section: ABSOLUTE_SECTION.to_string(),
+                kind: SymbolKind::CONSTANT,
+                binding: symbols.binding(name),
+                location: definitions.get(name).cloned(),
+            });
+        }
+        self.sort();
+    }
+
+    // Records where the labels and data of `lines` are defined.
+    pub fn locate_labels(&mut self, lines: &[Line]) {
+        for line in lines.iter() {
+            let label = match &line.text {
+                Some(text) if is_label(text) => text.split(':').next().unwrap_or("").trim(),
+                _ => continue,
+            };
+            let symbol = self.symbols.iter_mut().find(|symbol| symbol.name == label && symbol.kind != SymbolKind::CONSTANT);
+            if let Some(symbol) = symbol.filter(|symbol| symbol.location.is_none()) {
+                symbol.location = Some(line.location.clone());
+            }
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&MapSymbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    fn sort(&mut self) {
+        self.symbols.sort_by(|first, second| first.name.cmp(&second.name));
+    }
+
+    // Writes the map in one of `MAP_FORMATS`.
+    pub fn write(&self, format: &str) -> Option<String> {
+        match format {
+            "text" => Some(self.to_text()),
+            "json" => Some(self.to_json()),
+            "nm" => Some(self.to_nm()),
+            _ => None,
+        }
+    }
+
+    // The sections, then the symbols by address.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Sections:\n{:<8} {:<8} {:<8} {}\n", "name", "base", "size", "end");
+        for section in self.sections.iter() {
+            text += &format!("{:<8} {:08x} {:08x} {:08x}\n", section.name, section.base, section.size, section.end);
+        }
+
+        let width = self.symbols.iter().map(|symbol| symbol.name.len()).max().unwrap_or(0).max(4);
+        text += &format!(
+            "\nSymbols:\n{:<8} {:<8} {:<9} {:<7} {:<width$} {}\n",
+            "address",
+            "section",
+            "kind",
+            "binding",
+            "name",
+            "defined at",
+            width = width
+        );
+        for symbol in self.by_address() {
+            let value = if symbol.kind == SymbolKind::UNDEFINED { String::new() } else { format!("{:08x}", symbol.value) };
+            let location = symbol.location.as_ref().map_or(String::new(), |location| location.to_string());
+            let line = format!(
+                "{:<8} {:<8} {:<9} {:<7} {:<width$} {}",
+                value,
+                symbol.section,
+                symbol.kind.name(),
+                binding_name(symbol.binding),
+                symbol.name,
+                location,
+                width = width
+            );
+            text += line.trim_end();
+            text += "\n";
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        let sections = self.sections.iter().map(section_json).collect::<Vec<String>>();
+        let symbols = self.by_address().iter().map(|symbol| symbol_json(symbol)).collect::<Vec<String>>();
+        format!("{{\n  \"sections\": {},\n  \"symbols\": {}\n}}\n", json_array(&sections, 4), json_array(&symbols, 4))
+    }
+
+    // `nm` output: the address, the type letter, uppercase for global symbols, and the name.
+    pub fn to_nm(&self) -> String {
+        self.by_address()
+            .iter()
+            .map(|symbol| {
+                let letter = match (symbol.kind, symbol.section.as_str()) {
+                    (SymbolKind::UNDEFINED, _) if symbol.binding == Binding::WEAK => 'w',
+                    (SymbolKind::UNDEFINED, _) => 'U',
+                    _ if symbol.binding == Binding::WEAK => 'W',
+                    (SymbolKind::CONSTANT, _) => 'a',
+                    (SymbolKind::TEXT, _) => 't',
+                    (_, ".rodata") => 'r',
+                    (_, ".bss") => 'b',
+                    _ => 'd',
+                };
+                let letter = if symbol.binding == Binding::GLOBAL { letter.to_ascii_uppercase() } else { letter };
+                match symbol.kind {
+                    SymbolKind::UNDEFINED => format!("{:8} {} {}\n", "", letter, symbol.name),
+                    _ => format!("{:08x} {} {}\n", symbol.value, letter, symbol.name),
+                }
+            })
+            .collect()
+    }
+
+    // Undefined symbols last, the sort being stable keeps the names in order at each address.
+    pub(crate) fn by_address(&self) -> Vec<&MapSymbol> {
+        let mut symbols = self.symbols.iter().collect::<Vec<&MapSymbol>>();
+        symbols.sort_by_key(|symbol| (symbol.kind == SymbolKind::UNDEFINED, symbol.value as u32));
+        symbols
+    }
+}
+
+pub(crate) fn section_json(section: &MapSection) -> String {
+    json_object(&[
+        ("name", json_string(&section.name)),
+        ("base", section.base.to_string()),
+        ("size", section.size.to_string()),
+        ("end", section.end.to_string()),
+    ])
+}
+
+// A symbol as a JSON object, its `file` and `line` null when its definition is unknown.
+pub(crate) fn symbol_json(symbol: &MapSymbol) -> String {
+    let (file, line) = json_location(symbol.location.as_ref());
+    json_object(&[
+        ("name", json_string(&symbol.name)),
+        ("address", symbol.value.to_string()),
+        ("section", json_string(&symbol.section)),
+        ("kind", json_string(symbol.kind.name())),
+        ("binding", json_string(binding_name(symbol.binding))),
+        ("file", file),
+        ("line", line),
+    ])
+}
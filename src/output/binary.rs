@@ -0,0 +1,40 @@
+use std::io::{self, Write};
+
+use crate::constants::WORD_BYTES;
+use crate::image::{AddressUnit, MemoryImage};
+use crate::output::{OutputFormat, OutputOptions};
+
+// Flat little-endian image from the lowest to the highest section address, gaps padded with `fill`.
+// Addresses in the contents count bytes, like the memory it is copied to.
+pub struct FlatBinary;
+
+impl OutputFormat for FlatBinary {
+    fn name(&self) -> &'static str {
+        "bin"
+    }
+
+    fn description(&self) -> &'static str {
+        "flat little-endian binary image"
+    }
+
+    fn address_unit(&self) -> AddressUnit {
+        AddressUnit::BYTE
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let mut segments = image.segments.iter().filter(|segment| !segment.words.is_empty()).collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.base);
+
+        let mut position = match segments.first() {
+            Some(segment) => segment.base,
+            None => return Ok(()),
+        };
+        for segment in segments {
+            let gap = ((segment.base - position) * WORD_BYTES) as usize;
+            output.write_all(&vec![options.fill; gap])?;
+            output.write_all(&segment.bytes())?;
+            position = segment.end();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::constants::WORD_BYTES;
+use crate::dwarf::{encode_line_program, LineRow};
+use crate::elf::{
+    relocation_type, symbol_binding, ElfFile, ElfRelocation, ElfSection, ElfSymbol, ProgramHeader, StringTable, ET_EXEC,
+    ET_REL, PF_R, PF_W, PF_X, RELA_SIZE, SHF_ALLOC, SHF_EXECINSTR, SHF_INFO_LINK, SHF_WRITE, SHN_UNDEF, SHT_NOBITS,
+    SHT_PROGBITS, SHT_RELA, SHT_STRTAB, SHT_SYMTAB, STT_NOTYPE, STT_SECTION, SYMBOL_SIZE,
+};
+use crate::image::{AddressUnit, ImageSymbol, MemoryImage};
+use crate::output::{entry_address, OutputFormat, OutputOptions};
+use crate::symbol::Binding;
+
+// name, section type, section flags and program header flags
+const SECTIONS: [(&str, u32, u32, u32); 4] = [
+    (".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, PF_R | PF_X),
+    (".data", SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, PF_R | PF_W),
+    (".rodata", SHT_PROGBITS, SHF_ALLOC, PF_R),
+    (".bss", SHT_NOBITS, SHF_ALLOC | SHF_WRITE, PF_R | PF_W),
+];
+
+// ELF32 relocatable object. Symbol values and relocation offsets are byte offsets from the start
+// of their section, and every use of an address gets a relocation.
+pub struct ElfObject;
+
+// Fully linked ELF32 executable with one `PT_LOAD` segment per section, at its laid-out address.
+pub struct ElfExecutable;
+
+impl OutputFormat for ElfObject {
+    fn name(&self) -> &'static str {
+        "elf-obj"
+    }
+
+    fn description(&self) -> &'static str {
+        "ELF32 relocatable object"
+    }
+
+    fn relocatable(&self) -> bool {
+        true
+    }
+
+    fn address_unit(&self) -> AddressUnit {
+        AddressUnit::BYTE
+    }
+
+    fn write(&self, image: &MemoryImage, _options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let segment = |name: &str| image.segments.iter().find(|segment| segment.name == name);
+        let section_offset = |name: &str, address: i32| {
+            let base = segment(name).map_or(address, |segment| segment.base);
+            ((address - base) * WORD_BYTES) as u32
+        };
+
+        let mut file = ElfFile::new(ET_REL);
+        for (name, kind, flags, _) in SECTIONS.iter() {
+            let bytes = segment(name).map_or(vec![], |segment| segment.bytes());
+            file.sections.push(ElfSection::new(name, *kind, *flags, bytes));
+        }
+
+        let section_index = |name: &str| SECTIONS.iter().position(|section| section.0 == name).unwrap() as u16 + 1;
+        let section_symbols = (1..=SECTIONS.len() as u16).collect::<Vec<u16>>();
+        let symbols = ElfSymbolTable::new(image, &section_symbols, &section_index, &|symbol: &ImageSymbol| {
+            section_offset(&symbol.section, symbol.address)
+        });
+
+        let mut relocation_sections = vec![];
+        for (index, (name, _, _, _)) in SECTIONS.iter().enumerate() {
+            let relocations = image
+                .relocations
+                .iter()
+                .filter(|relocation| relocation.section == *name)
+                .flat_map(|relocation| {
+                    ElfRelocation {
+                        offset: section_offset(name, relocation.address),
+                        symbol: symbols.indexes[relocation.symbol.as_str()],
+                        kind: relocation_type(relocation.kind),
+                        addend: 0,
+                    }
+                    .to_bytes()
+                })
+                .collect::<Vec<u8>>();
+            if !relocations.is_empty() {
+                let mut section = ElfSection::new(&format!(".rela{}", name), SHT_RELA, SHF_INFO_LINK, relocations);
+                section.info = index as u32 + 1;
+                section.entry_size = RELA_SIZE;
+                relocation_sections.push(section);
+            }
+        }
+
+        let symtab_index = (file.sections.len() + relocation_sections.len() + 1) as u32;
+        for mut section in relocation_sections {
+            section.link = symtab_index;
+            file.sections.push(section);
+        }
+        symbols.push_sections(&mut file);
+
+        file.write(output)
+    }
+}
+
+impl OutputFormat for ElfExecutable {
+    fn name(&self) -> &'static str {
+        "elf"
+    }
+
+    fn description(&self) -> &'static str {
+        "ELF32 executable"
+    }
+
+    fn address_unit(&self) -> AddressUnit {
+        AddressUnit::BYTE
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let mut file = ElfFile::new(ET_EXEC);
+        file.entry = entry_address(image, options)?;
+
+        let mut section_indexes = HashMap::new();
+        for (name, kind, flags, segment_flags) in SECTIONS.iter() {
+            if let Some(segment) = image.segments.iter().find(|segment| segment.name == *name) {
+                let mut section = ElfSection::new(name, *kind, *flags, segment.bytes());
+                section.address = (segment.base * WORD_BYTES) as u32;
+                file.program_headers.push(ProgramHeader {
+                    section: file.sections.len(),
+                    flags: *segment_flags,
+                });
+                file.sections.push(section);
+                section_indexes.insert(*name, file.sections.len() as u16);
+            }
+        }
+
+        if options.debug_line {
+            let rows = image
+                .line_table
+                .iter()
+                .map(|(address, location)| LineRow {
+                    address: (address * WORD_BYTES) as u32,
+                    file: location.file.display().to_string(),
+                    line: location.line,
+                })
+                .collect::<Vec<LineRow>>();
+            let end = image.segments.iter().find(|segment| segment.name == ".text").map_or(0, |segment| segment.end());
+            let program = encode_line_program(&rows, (end * WORD_BYTES) as u32, WORD_BYTES as u8);
+            let mut section = ElfSection::new(".debug_line", SHT_PROGBITS, 0, program);
+            section.align = 1;
+            file.sections.push(section);
+        }
+
+        let section_index = |name: &str| section_indexes.get(name).copied().unwrap_or(SHN_UNDEF);
+        let symbols = ElfSymbolTable::new(image, &[], &section_index, &|symbol: &ImageSymbol| {
+            (symbol.address * WORD_BYTES) as u32
+        });
+        symbols.push_sections(&mut file);
+
+        file.write(output)
+    }
+}
+
+// `.symtab` contents: the null symbol, the section symbols, the local symbols then the global,
+// weak and undefined ones, as ELF requires locals to come first.
+struct ElfSymbolTable {
+    symbols: Vec<ElfSymbol>,
+    names: StringTable,
+    indexes: HashMap<String, u32>,
+    first_global: u32,
+}
+
+impl ElfSymbolTable {
+    fn new(
+        image: &MemoryImage,
+        section_symbols: &[u16],
+        section_index: &dyn Fn(&str) -> u16,
+        value: &dyn Fn(&ImageSymbol) -> u32,
+    ) -> Self {
+        let mut names = StringTable::new();
+        let mut symbols = vec![ElfSymbol {
+            name: 0,
+            value: 0,
+            size: 0,
+            binding: 0,
+            kind: STT_NOTYPE,
+            section: SHN_UNDEF,
+        }];
+        for section in section_symbols.iter() {
+            symbols.push(ElfSymbol {
+                name: 0,
+                value: 0,
+                size: 0,
+                binding: symbol_binding(Binding::LOCAL),
+                kind: STT_SECTION,
+                section: *section,
+            });
+        }
+
+        let mut indexes = HashMap::new();
+        let (locals, globals): (Vec<_>, Vec<_>) = image.symbols.iter().partition(|symbol| symbol.binding == Binding::LOCAL);
+        let first_global = (symbols.len() + locals.len()) as u32;
+        for symbol in locals.iter().chain(globals.iter()) {
+            indexes.insert(symbol.name.clone(), symbols.len() as u32);
+            symbols.push(ElfSymbol {
+                name: names.add(&symbol.name),
+                value: value(symbol),
+                size: 0,
+                binding: symbol_binding(symbol.binding),
+                kind: STT_NOTYPE,
+                section: section_index(&symbol.section),
+            });
+        }
+        for (name, binding) in image.externals.iter() {
+            indexes.insert(name.clone(), symbols.len() as u32);
+            symbols.push(ElfSymbol {
+                name: names.add(name),
+                value: 0,
+                size: 0,
+                binding: symbol_binding(*binding),
+                kind: STT_NOTYPE,
+                section: SHN_UNDEF,
+            });
+        }
+
+        Self {
+            symbols,
+            names,
+            indexes,
+            first_global,
+        }
+    }
+
+    // Appends `.symtab` and the `.strtab` it links to.
+    fn push_sections(self, file: &mut ElfFile) {
+        let symtab_index = file.sections.len() as u32 + 1;
+        let bytes = self.symbols.iter().flat_map(|symbol| symbol.to_bytes()).collect();
+        let mut symtab = ElfSection::new(".symtab", SHT_SYMTAB, 0, bytes);
+        symtab.link = symtab_index + 1;
+        symtab.info = self.first_global;
+        symtab.entry_size = SYMBOL_SIZE;
+        file.sections.push(symtab);
+
+        let mut strtab = ElfSection::new(".strtab", SHT_STRTAB, 0, self.names.into_bytes());
+        strtab.align = 1;
+        file.sections.push(strtab);
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::{self, Write};
+
+use crate::constants::WORD_BYTES;
+use crate::image::MemoryImage;
+use crate::output::{word_unit, OutputFormat, OutputOptions};
+
+const DATA_RECORD: u8 = 0x00;
+const END_OF_FILE_RECORD: u8 = 0x01;
+const EXTENDED_LINEAR_ADDRESS_RECORD: u8 = 0x04;
+
+// Intel HEX where each address designates one memory word of `word_width` bits, as Quartus
+// expects for on-chip memories. Memory words are written most significant byte first.
+pub struct IntelHex;
+
+impl OutputFormat for IntelHex {
+    fn name(&self) -> &'static str {
+        "ihex"
+    }
+
+    fn description(&self) -> &'static str {
+        "Intel HEX for Quartus on-chip memory initialisation"
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let unit = word_unit(options)?;
+        if options.record_length % unit != 0 {
+            let message = format!(
+                "the record length ({} bytes) must be a multiple of the word width ({} bits)",
+                options.record_length, options.word_width
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        let mut upper_address: Option<u32> = None;
+        for segment in image.segments.iter() {
+            let bytes = segment.bytes();
+            let base = segment.base as u32 * WORD_BYTES as u32 / unit as u32;
+
+            let mut offset = 0;
+            while offset < bytes.len() {
+                let address = base + (offset / unit) as u32;
+                // a record never crosses a 64K boundary
+                let room = (0x10000 - (address & 0xFFFF) as usize) * unit;
+                let length = options.record_length.min(room).min(bytes.len() - offset);
+
+                if upper_address != Some(address >> 16) {
+                    let upper = ((address >> 16) as u16).to_be_bytes();
+                    write_record(output, 0, EXTENDED_LINEAR_ADDRESS_RECORD, &upper)?;
+                    upper_address = Some(address >> 16);
+                }
+
+                let data = bytes[offset..offset + length]
+                    .chunks(unit)
+                    .flat_map(|word| word.iter().rev().copied().collect::<Vec<u8>>())
+                    .collect::<Vec<u8>>();
+                write_record(output, address as u16, DATA_RECORD, &data)?;
+                offset += length;
+            }
+        }
+        write_record(output, 0, END_OF_FILE_RECORD, &[])
+    }
+}
+
+fn write_record(output: &mut dyn Write, address: u16, record_type: u8, data: &[u8]) -> io::Result<()> {
+    let mut record = vec![data.len() as u8];
+    record.extend_from_slice(&address.to_be_bytes());
+    record.push(record_type);
+    record.extend_from_slice(data);
+
+    let checksum = record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    record.push(checksum);
+
+    let hex = record.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
+    writeln!(output, ":{}", hex)
+}
@@ -0,0 +1,32 @@
+use std::io::{self, Write};
+
+use crate::image::MemoryImage;
+use crate::output::{OutputFormat, OutputOptions};
+
+// Logisim memory image: `N*0` skips N words, then one hex word per entry.
+pub struct LogisimRaw;
+
+impl OutputFormat for LogisimRaw {
+    fn name(&self) -> &'static str {
+        "logisim-raw"
+    }
+
+    fn description(&self) -> &'static str {
+        "Logisim `v2.0 raw` memory image"
+    }
+
+    fn write(&self, image: &MemoryImage, _options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let mut position = 0;
+        let mut result = vec!["v2.0 raw".to_string()];
+
+        let mut segments = image.segments.iter().collect::<Vec<_>>();
+        segments.sort_by_key(|segment| segment.base);
+        for segment in segments {
+            let mut line = format!("{}*0 ", segment.base - position);
+            line.extend(segment.words.iter().map(|word| format!("{:08x} ", word)));
+            result.push(line);
+            position = segment.end();
+        }
+        write!(output, "{}", result.join("\n").trim_end())
+    }
+}
@@ -0,0 +1,60 @@
+use std::io::{self, Write};
+
+use crate::disassembler::disassemble_word;
+use crate::image::MemoryImage;
+use crate::output::{memory_depth, memory_words, word_unit, OutputFormat, OutputOptions};
+
+// Quartus Memory Initialization File, one entry per memory word of `word_width` bits.
+pub struct Mif;
+
+impl OutputFormat for Mif {
+    fn name(&self) -> &'static str {
+        "mif"
+    }
+
+    fn description(&self) -> &'static str {
+        "Quartus Memory Initialization File"
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let words = memory_words(image, word_unit(options)?);
+        let depth = memory_depth(&words, options)?;
+
+        let address_digits = format!("{:X}", depth.max(2) - 1).len();
+        let data_digits = options.word_width / 4;
+
+        writeln!(output, "-- Generated by nios2asm")?;
+        writeln!(output, "WIDTH={};", options.word_width)?;
+        writeln!(output, "DEPTH={};", depth)?;
+        writeln!(output)?;
+        writeln!(output, "ADDRESS_RADIX=HEX;")?;
+        writeln!(output, "DATA_RADIX=HEX;")?;
+        writeln!(output)?;
+        writeln!(output, "CONTENT BEGIN")?;
+
+        let mut position = 0;
+        for word in words.iter() {
+            write_gap(output, position, word.address, address_digits)?;
+            write!(output, "\t{:0w$X} : {:0d$X};", word.address, word.value, w = address_digits, d = data_digits)?;
+            match word.instruction {
+                Some((instruction, address)) if options.comments => {
+                    writeln!(output, " -- {}", disassemble_word(instruction, image.unit.from_words(address), image.unit).replace('\t', " "))?
+                }
+                _ => writeln!(output)?,
+            }
+            position = word.address + 1;
+        }
+        write_gap(output, position, depth, address_digits)?;
+        writeln!(output, "END;")
+    }
+}
+
+fn write_gap(output: &mut dyn Write, start: usize, end: usize, digits: usize) -> io::Result<()> {
+    if end > start + 1 {
+        writeln!(output, "\t[{:0w$X}..{:0w$X}] : 0;", start, end - 1, w = digits)
+    } else if end == start + 1 {
+        writeln!(output, "\t{:0w$X} : 0;", start, w = digits)
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{self, Write};
+
+use crate::constants::WORD_BYTES;
+use crate::image::{AddressUnit, MemoryImage};
+use crate::symbol::Binding;
+
+mod binary;
+mod elf;
+mod intel_hex;
+mod logisim;
+mod mif;
+mod readmem;
+mod srec;
+mod vhdl;
+
+use self::binary::FlatBinary;
+use self::elf::{ElfExecutable, ElfObject};
+use self::intel_hex::IntelHex;
+use self::logisim::LogisimRaw;
+use self::mif::Mif;
+use self::readmem::{ReadMemB, ReadMemH};
+use self::srec::SRecord;
+use self::vhdl::VhdlPackage;
+
+pub const DEFAULT_OUTPUT_FORMAT: &str = "logisim-raw";
+// Linked images count bytes, like the objects they come from.
+pub const DEFAULT_LINK_FORMAT: &str = "elf";
+
+// Settings shared by the writers, each format reads the ones that apply to it.
+pub struct OutputOptions {
+    pub record_length: usize,
+    pub word_width: usize,
+    pub depth: Option<usize>,
+    pub comments: bool,
+    pub entry: Option<String>,
+    pub fill: u8,
+    pub debug_line: bool,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            record_length: 16,
+            word_width: 32,
+            depth: None,
+            comments: false,
+            entry: None,
+            fill: 0,
+            debug_line: false,
+        }
+    }
+}
+
+pub trait OutputFormat {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()>;
+
+    // Relocatable formats keep references to undefined symbols for the linker.
+    fn relocatable(&self) -> bool {
+        false
+    }
+
+    // What encoded addresses and branch offsets count, memories are indexed by words.
+    fn address_unit(&self) -> AddressUnit {
+        AddressUnit::WORD
+    }
+}
+
+pub const OUTPUT_FORMATS: &[&dyn OutputFormat] = &[
+    &LogisimRaw,
+    &IntelHex,
+    &Mif,
+    &ReadMemH,
+    &ReadMemB,
+    &VhdlPackage,
+    &SRecord,
+    &FlatBinary,
+    &ElfObject,
+    &ElfExecutable,
+];
+
+pub fn find_output_format(name: &str) -> Option<&'static dyn OutputFormat> {
+    OUTPUT_FORMATS.iter().find(|format| format.name() == name).copied()
+}
+
+// One memory word of `word_width` bits, with the instruction it starts when it holds one.
+pub(crate) struct MemoryWord {
+    pub address: usize,
+    pub value: u32,
+    pub instruction: Option<(u32, i32)>,
+}
+
+// Returns the memory word size in bytes for the word-addressed formats.
+pub(crate) fn word_unit(options: &OutputOptions) -> io::Result<usize> {
+    if ![8, 16, 32].contains(&options.word_width) {
+        let message = format!("unsupported word width of {} bits, expected 8, 16 or 32", options.word_width);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    Ok(options.word_width / 8)
+}
+
+// Splits the image into memory words of `unit` bytes, sorted by address.
+pub(crate) fn memory_words(image: &MemoryImage, unit: usize) -> Vec<MemoryWord> {
+    let mut words = vec![];
+    for segment in image.segments.iter() {
+        let base = segment.base as usize * WORD_BYTES as usize / unit;
+        for (index, chunk) in segment.bytes().chunks(unit).enumerate() {
+            let value = chunk.iter().rev().fold(0u32, |value, byte| (value << 8) | *byte as u32);
+            let offset = index * unit;
+            let instruction = if segment.name == ".text" && offset % WORD_BYTES as usize == 0 {
+                let word_index = offset / WORD_BYTES as usize;
+                Some((segment.words[word_index], segment.base + word_index as i32))
+            } else {
+                None
+            };
+            words.push(MemoryWord {
+                address: base + index,
+                value,
+                instruction,
+            });
+        }
+    }
+    words.sort_by_key(|word| word.address);
+    words
+}
+
+// Without an explicit depth, the memory is the smallest power of two holding the image.
+pub(crate) fn memory_depth(words: &[MemoryWord], options: &OutputOptions) -> io::Result<usize> {
+    let end = words.iter().map(|word| word.address + 1).max().unwrap_or(0);
+    let depth = options.depth.unwrap_or_else(|| end.next_power_of_two());
+    if end > depth {
+        let message = format!("the image needs {} words but the memory depth is {}", end, depth);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    Ok(depth)
+}
+
+// Byte address of the entry point: the `--entry` symbol, `_start` when defined, the first global
+// `.text` symbol, else the start of `.text`.
+pub(crate) fn entry_address(image: &MemoryImage, options: &OutputOptions) -> io::Result<u32> {
+    let address = match &options.entry {
+        Some(name) => match image.find_symbol(name) {
+            Some(symbol) => symbol.address,
+            None => {
+                let message = format!("entry symbol `{}` is not defined", name);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        },
+        None => {
+            let global_text_symbol = image
+                .symbols
+                .iter()
+                .find(|symbol| symbol.section == ".text" && symbol.binding != Binding::LOCAL);
+            match image.find_symbol("_start").or(global_text_symbol) {
+                Some(symbol) => symbol.address,
+                None => image.segments.iter().find(|segment| segment.name == ".text").map_or(0, |segment| segment.base),
+            }
+        }
+    };
+    Ok((address * WORD_BYTES) as u32)
+}
@@ -0,0 +1,58 @@
+use std::io::{self, Write};
+
+use crate::image::MemoryImage;
+use crate::output::{memory_words, word_unit, OutputFormat, OutputOptions};
+
+// Verilog `$readmemh` image: an `@address` marker before each contiguous run, one hex word per line.
+pub struct ReadMemH;
+
+// Verilog `$readmemb` image, the same layout as `ReadMemH` with binary words.
+pub struct ReadMemB;
+
+impl OutputFormat for ReadMemH {
+    fn name(&self) -> &'static str {
+        "readmemh"
+    }
+
+    fn description(&self) -> &'static str {
+        "Verilog `$readmemh` memory image"
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        write_readmem(image, options, output, |value, width| format!("{:0w$x}", value, w = width / 4))
+    }
+}
+
+impl OutputFormat for ReadMemB {
+    fn name(&self) -> &'static str {
+        "readmemb"
+    }
+
+    fn description(&self) -> &'static str {
+        "Verilog `$readmemb` memory image"
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        write_readmem(image, options, output, |value, width| format!("{:0w$b}", value, w = width))
+    }
+}
+
+fn write_readmem(
+    image: &MemoryImage,
+    options: &OutputOptions,
+    output: &mut dyn Write,
+    format_word: fn(u32, usize) -> String,
+) -> io::Result<()> {
+    writeln!(output, "// Generated by nios2asm")?;
+
+    let mut position = None;
+    for word in memory_words(image, word_unit(options)?) {
+        // `@` addresses are always hexadecimal, in memory words
+        if position != Some(word.address) {
+            writeln!(output, "@{:08x}", word.address)?;
+        }
+        writeln!(output, "{}", format_word(word.value, options.word_width))?;
+        position = Some(word.address + 1);
+    }
+    Ok(())
+}
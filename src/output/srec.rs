@@ -0,0 +1,67 @@
+use std::io::{self, Write};
+
+use crate::constants::WORD_BYTES;
+use crate::image::{AddressUnit, MemoryImage};
+use crate::output::{entry_address, OutputFormat, OutputOptions};
+
+// Motorola S-records over byte addresses. The address size, and so the data (S1/S2/S3) and
+// start (S9/S8/S7) record types, is the smallest one holding every address of the image. Loaders
+// copy the data as it is, so addresses in the contents count bytes too.
+pub struct SRecord;
+
+impl OutputFormat for SRecord {
+    fn name(&self) -> &'static str {
+        "srec"
+    }
+
+    fn description(&self) -> &'static str {
+        "Motorola S-records"
+    }
+
+    fn address_unit(&self) -> AddressUnit {
+        AddressUnit::BYTE
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let entry = entry_address(image, options)?;
+        let end = image.segments.iter().map(|segment| segment.end() * WORD_BYTES).max().unwrap_or(0) as u32;
+        let highest_address = end.saturating_sub(1).max(entry);
+        let address_size = match highest_address {
+            0..=0xFFFF => 2,
+            0x10000..=0xFF_FFFF => 3,
+            _ => 4,
+        };
+        // the count byte covers the address, the data and the checksum
+        let maximum_length = 0xFF - address_size - 1;
+        if options.record_length > maximum_length {
+            let message = format!(
+                "the record length ({} bytes) exceeds the {} bytes an S{} record holds",
+                options.record_length,
+                maximum_length,
+                address_size - 1
+            );
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        write_record(output, 0, 2, 0, b"nios2asm")?;
+        for segment in image.segments.iter() {
+            let base = (segment.base * WORD_BYTES) as u32;
+            for (index, chunk) in segment.bytes().chunks(options.record_length).enumerate() {
+                let address = base + (index * options.record_length) as u32;
+                write_record(output, address_size - 1, address_size, address, chunk)?;
+            }
+        }
+        write_record(output, 11 - address_size, address_size, entry, &[])
+    }
+}
+
+fn write_record(output: &mut dyn Write, kind: usize, address_size: usize, address: u32, data: &[u8]) -> io::Result<()> {
+    let mut record = vec![(address_size + data.len() + 1) as u8];
+    record.extend_from_slice(&address.to_be_bytes()[4 - address_size..]);
+    record.extend_from_slice(data);
+    let checksum = !record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    record.push(checksum);
+
+    let hex = record.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
+    writeln!(output, "S{}{}", kind, hex)
+}
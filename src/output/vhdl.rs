@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+
+use crate::image::MemoryImage;
+use crate::output::{memory_depth, memory_words, word_unit, OutputFormat, OutputOptions};
+
+const PACKAGE_NAME: &str = "program_rom";
+
+// VHDL package declaring the program as a constant ROM array, ready for synthesis.
+pub struct VhdlPackage;
+
+impl OutputFormat for VhdlPackage {
+    fn name(&self) -> &'static str {
+        "vhdl"
+    }
+
+    fn description(&self) -> &'static str {
+        "VHDL package with a constant ROM array"
+    }
+
+    fn write(&self, image: &MemoryImage, options: &OutputOptions, output: &mut dyn Write) -> io::Result<()> {
+        let words = memory_words(image, word_unit(options)?);
+        let depth = memory_depth(&words, options)?;
+        let data_digits = options.word_width / 4;
+
+        writeln!(output, "-- Generated by nios2asm")?;
+        writeln!(output, "library ieee;")?;
+        writeln!(output, "use ieee.std_logic_1164.all;")?;
+        writeln!(output)?;
+        writeln!(output, "package {} is", PACKAGE_NAME)?;
+        writeln!(output, "    constant ROM_DEPTH : natural := {};", depth)?;
+        writeln!(output, "    constant ROM_WIDTH : natural := {};", options.word_width)?;
+        writeln!(output)?;
+        writeln!(output, "    type rom_t is array (0 to ROM_DEPTH - 1) of std_logic_vector(ROM_WIDTH - 1 downto 0);")?;
+        writeln!(output)?;
+        writeln!(output, "    constant ROM : rom_t := (")?;
+        for word in words.iter() {
+            writeln!(output, "        {} => x\"{:0d$X}\",", word.address, word.value, d = data_digits)?;
+        }
+        writeln!(output, "        others => (others => '0')")?;
+        writeln!(output, "    );")?;
+        writeln!(output, "end package {};", PACKAGE_NAME)
+    }
+}
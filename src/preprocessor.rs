@@ -0,0 +1,508 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::asm_macro::{MacroDefinition, Repetition};
+use crate::diagnostic::Diagnostic;
+use crate::expression::evaluate;
+use crate::listing::ListingEntry;
+use crate::section::{resolve_section, Section};
+use crate::source::{find_source_file, read_source_file, SourceLine, SourceLocation};
+use crate::symbol::{Binding, SymbolTable};
+use crate::utils::{split_arguments, split_statement};
+
+struct Conditional {
+    parent_active: bool,
+    active: bool,
+    taken: bool,
+    in_else: bool,
+    location: SourceLocation,
+}
+
+#[derive(Default)]
+pub struct Preprocessor {
+    macros: HashMap<String, MacroDefinition>,
+    expansion_count: usize,
+    expansion_stack: Vec<String>,
+    symbols: SymbolTable,
+    // where each `.equ` symbol was last defined
+    definitions: HashMap<String, SourceLocation>,
+    labels: HashSet<String>,
+    warnings: Vec<Diagnostic>,
+    include_paths: Vec<PathBuf>,
+    section: Option<Section>,
+    // every line processed so far, recorded once `record_listing` is called
+    listing: Option<Vec<ListingEntry>>,
+}
+
+impl Preprocessor {
+    pub fn new(symbols: SymbolTable, include_paths: &[PathBuf]) -> Self {
+        Self {
+            symbols,
+            include_paths: include_paths.to_vec(),
+            ..Self::default()
+        }
+    }
+
+    pub fn run(&mut self, lines: &[SourceLine]) -> Result<Vec<SourceLine>, Diagnostic> {
+        let mut output = vec![];
+        self.process(lines, &mut output)?;
+        Ok(output)
+    }
+
+    // Records the lines processed by the following runs, see `take_listing`.
+    pub fn record_listing(&mut self) {
+        self.listing = Some(vec![]);
+    }
+
+    // Entries of the lines processed since the last call, with output line indexes relative to the
+    // output of the run that processed them.
+    pub fn take_listing(&mut self) -> Vec<ListingEntry> {
+        self.listing.as_mut().map_or(vec![], std::mem::take)
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn definitions(&self) -> &HashMap<String, SourceLocation> {
+        &self.definitions
+    }
+
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    // Returns true when an `.exitm` stopped the processing of the current expansion.
+    fn process(&mut self, lines: &[SourceLine], output: &mut Vec<SourceLine>) -> Result<bool, Diagnostic> {
+        let mut conditionals: Vec<Conditional> = vec![];
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line = &lines[index];
+            let (label, directive, arguments) = split_statement(&line.text);
+            let active = conditionals.last().map_or(true, |conditional| conditional.active);
+            let entry = self.record(line, output.len());
+
+            if self.process_conditional(directive, arguments, &line.location, active, &mut conditionals)? || !active {
+                index += 1;
+                continue;
+            }
+
+            match directive {
+                ".macro" => {
+                    let end = find_block_end(lines, index, &[".macro"], ".endm")?;
+                    let definition = MacroDefinition::parse(arguments, &lines[index + 1..end], &line.location)?;
+                    self.macros.insert(definition.name.clone(), definition);
+                    self.place_label(label, &mut [], &line.location, output);
+                    for line in lines[index + 1..=end].iter() {
+                        self.record(line, output.len());
+                    }
+                    index = end;
+                }
+                ".rept" | ".irp" | ".irpc" => {
+                    let end = find_block_end(lines, index, &[".rept", ".irp", ".irpc"], ".endr")?;
+                    let repetition = Repetition::parse(directive, arguments, &self.symbols, &line.location)?;
+                    let mut expansion = repetition.expand(&lines[index + 1..end]);
+                    self.place_label(label, &mut expansion, &line.location, output);
+                    if self.process(&expansion, output)? {
+                        return Ok(true);
+                    }
+                    index = end;
+                }
+                ".endr" => return Err(Diagnostic::error("`.endr` without matching `.rept`, `.irp` or `.irpc`", Some(&line.location))),
+                ".endm" => return Err(Diagnostic::error("`.endm` without matching `.macro`", Some(&line.location))),
+                ".exitm" => {
+                    if self.expansion_stack.is_empty() {
+                        return Err(Diagnostic::error("`.exitm` outside of a macro", Some(&line.location)));
+                    }
+                    return Ok(true);
+                }
+                ".equ" | ".set" | ".equiv" => {
+                    match split_arguments(arguments)[..] {
+                        [ref name, ref value] => self.define_symbol(directive, name, value, &line.location)?,
+                        _ => {
+                            let message = format!("`{}` expects a symbol name and a value", directive);
+                            return Err(Diagnostic::error(&message, Some(&line.location)));
+                        }
+                    };
+                }
+                ".error" => {
+                    let message = unquote(arguments).unwrap_or(".error directive invoked in source file");
+                    return Err(Diagnostic::error(message, Some(&line.location)));
+                }
+                ".include" => {
+                    let included = self.read_included_file(arguments, &line.location)?;
+                    if self.process(&included, output)? {
+                        return Ok(true);
+                    }
+                }
+                ".incbin" => self.include_binary(label, arguments, &line.location, output)?,
+                ".space" | ".skip" => self.reserve_space(directive, label, arguments, &line.location, output)?,
+                ".global" | ".globl" | ".local" | ".weak" | ".extern" => {
+                    let names = split_arguments(arguments);
+                    if names.is_empty() {
+                        let message = format!("`{}` expects at least one symbol name", directive);
+                        return Err(Diagnostic::error(&message, Some(&line.location)));
+                    }
+                    // undefined symbols are external anyway, `.extern` is only documentation
+                    let binding = match directive {
+                        ".local" => Binding::LOCAL,
+                        ".weak" => Binding::WEAK,
+                        _ => Binding::GLOBAL,
+                    };
+                    if directive != ".extern" {
+                        for name in names.iter() {
+                            self.symbols.bind(name, binding);
+                        }
+                    }
+                }
+                ".export" => {
+                    let names = split_arguments(arguments);
+                    if names.is_empty() {
+                        return Err(Diagnostic::error("`.export` expects at least one symbol name", Some(&line.location)));
+                    }
+                    for name in names.iter() {
+                        self.symbols.export(name);
+                    }
+                }
+                ".warning" => {
+                    let message = unquote(arguments).unwrap_or(".warning directive invoked in source file");
+                    self.warnings.push(Diagnostic::warning(message, Some(&line.location)));
+                }
+                name if arguments.starts_with('=') && !arguments.starts_with("==") && label.is_none() => {
+                    self.define_symbol(".set", name, &arguments[1..], &line.location)?;
+                }
+                name if self.macros.contains_key(name) => self.expand_macro(name, label, arguments, &line.location, output)?,
+                _ => {
+                    if let Some(label) = label {
+                        self.labels.insert(label.to_string());
+                    }
+                    if let Some(section) = resolve_section(&line.text) {
+                        self.section = Some(section);
+                    }
+                    output.push(line.clone());
+                }
+            }
+            if let (Some(listing), Some(entry)) = (self.listing.as_mut(), entry) {
+                listing[entry].output.end = output.len();
+            }
+            index += 1;
+        }
+
+        match conditionals.first() {
+            Some(conditional) => Err(Diagnostic::error("missing `.endif` for this conditional", Some(&conditional.location))),
+            None => Ok(false),
+        }
+    }
+
+    // Returns the index of the listing entry of `line`, whose output starts at `output_start`.
+    fn record(&mut self, line: &SourceLine, output_start: usize) -> Option<usize> {
+        let macro_depth = self.expansion_stack.len();
+        self.listing.as_mut().map(|listing| {
+            listing.push(ListingEntry {
+                line: line.clone(),
+                macro_depth,
+                output: output_start..output_start,
+            });
+            listing.len() - 1
+        })
+    }
+
+    // Returns true when the directive was a conditional directive.
+    fn process_conditional(
+        &self,
+        directive: &str,
+        arguments: &str,
+        location: &SourceLocation,
+        active: bool,
+        conditionals: &mut Vec<Conditional>,
+    ) -> Result<bool, Diagnostic> {
+        match directive {
+            ".if" | ".ifne" | ".ifeq" | ".ifgt" | ".ifge" | ".iflt" | ".ifle" | ".ifdef" | ".ifndef" | ".ifnotdef"
+            | ".ifb" | ".ifnb" | ".ifc" | ".ifnc" => {
+                let condition = active && self.evaluate_condition(directive, arguments, location)?;
+                conditionals.push(Conditional {
+                    parent_active: active,
+                    active: condition,
+                    taken: condition,
+                    in_else: false,
+                    location: location.clone(),
+                });
+            }
+            ".elseif" => {
+                let conditional = match conditionals.last_mut() {
+                    Some(conditional) if !conditional.in_else => conditional,
+                    Some(_) => return Err(Diagnostic::error("`.elseif` after `.else`", Some(location))),
+                    None => return Err(Diagnostic::error("`.elseif` without matching `.if`", Some(location))),
+                };
+                let condition = conditional.parent_active
+                    && !conditional.taken
+                    && self.evaluate_condition(".if", arguments, location)?;
+                conditional.active = condition;
+                conditional.taken |= condition;
+            }
+            ".else" => {
+                let conditional = match conditionals.last_mut() {
+                    Some(conditional) if !conditional.in_else => conditional,
+                    Some(_) => return Err(Diagnostic::error("duplicate `.else`", Some(location))),
+                    None => return Err(Diagnostic::error("`.else` without matching `.if`", Some(location))),
+                };
+                conditional.active = conditional.parent_active && !conditional.taken;
+                conditional.taken = true;
+                conditional.in_else = true;
+            }
+            ".endif" => {
+                if conditionals.pop().is_none() {
+                    return Err(Diagnostic::error("`.endif` without matching `.if`", Some(location)));
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn evaluate_condition(&self, directive: &str, arguments: &str, location: &SourceLocation) -> Result<bool, Diagnostic> {
+        match directive {
+            ".ifdef" => Ok(self.is_defined(arguments.trim())),
+            ".ifndef" | ".ifnotdef" => Ok(!self.is_defined(arguments.trim())),
+            ".ifb" => Ok(arguments.trim().is_empty()),
+            ".ifnb" => Ok(!arguments.trim().is_empty()),
+            ".ifc" | ".ifnc" => match split_arguments(arguments)[..] {
+                [ref left, ref right] => {
+                    let equal = unquote(left).unwrap_or("") == unquote(right).unwrap_or("");
+                    Ok(equal == (directive == ".ifc"))
+                }
+                _ => {
+                    let message = format!("`{}` expects two comma-separated strings", directive);
+                    Err(Diagnostic::error(&message, Some(location)))
+                }
+            },
+            _ => {
+                let value = self.evaluate(arguments, location)?;
+                Ok(match directive {
+                    ".ifeq" => value == 0,
+                    ".ifgt" => value > 0,
+                    ".ifge" => value >= 0,
+                    ".iflt" => value < 0,
+                    ".ifle" => value <= 0,
+                    _ => value != 0,
+                })
+            }
+        }
+    }
+
+    fn evaluate(&self, text: &str, location: &SourceLocation) -> Result<i32, Diagnostic> {
+        evaluate(text, &|name| self.symbols.get(name)).map_err(|message| Diagnostic::error(&message, Some(location)))
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.symbols.contains(name) || self.labels.contains(name)
+    }
+
+    fn define_symbol(&mut self, directive: &str, name: &str, value: &str, location: &SourceLocation) -> Result<(), Diagnostic> {
+        let name = name.trim();
+        if directive == ".equiv" && self.is_defined(name) {
+            let message = format!("symbol `{}` is already defined", name);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+        let value = self.evaluate(value, location)?;
+        self.symbols.define(name, value);
+        self.definitions.insert(name.to_string(), location.clone());
+        Ok(())
+    }
+
+    fn resolve_included_file(&self, arguments: &str, location: &SourceLocation) -> Result<PathBuf, Diagnostic> {
+        let name = match unquote(arguments) {
+            Some(name) => name,
+            None => return Err(Diagnostic::error("missing file name", Some(location))),
+        };
+        find_source_file(name, &location.file, &self.include_paths).map_err(|error| Diagnostic::error(&error.to_string(), Some(location)))
+    }
+
+    fn read_included_file(&self, arguments: &str, location: &SourceLocation) -> Result<Vec<SourceLine>, Diagnostic> {
+        let path = self.resolve_included_file(arguments, location)?;
+
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        let mut chain = vec![location];
+        chain.extend(location.include_chain());
+        if chain.iter().any(|parent| fs::canonicalize(parent.file.as_path()).is_ok_and(|file| file == canonical)) {
+            let files = chain
+                .iter()
+                .rev()
+                .map(|parent| parent.file.display().to_string())
+                .chain(std::iter::once(path.display().to_string()))
+                .collect::<Vec<String>>();
+            let message = format!("include cycle detected ({})", files.join(" -> "));
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+
+        let mut file = File::open(&path).map_err(|error| {
+            let message = format!("cannot read `{}`: {}", path.display(), error);
+            Diagnostic::error(&message, Some(location))
+        })?;
+        Ok(read_source_file(&path, &mut file, Some(Rc::new(location.clone()))))
+    }
+
+    fn include_binary(
+        &mut self,
+        label: Option<&str>,
+        arguments: &str,
+        location: &SourceLocation,
+        output: &mut Vec<SourceLine>,
+    ) -> Result<(), Diagnostic> {
+        if !matches!(self.section, Some(Section::DATA) | Some(Section::RODATA)) {
+            return Err(Diagnostic::error("`.incbin` is only allowed in the .data and .rodata sections", Some(location)));
+        }
+
+        let arguments = split_arguments(arguments);
+        let path = self.resolve_included_file(arguments.first().map_or("", |name| name.as_str()), location)?;
+        let bytes = fs::read(&path).map_err(|error| {
+            let message = format!("cannot read `{}`: {}", path.display(), error);
+            Diagnostic::error(&message, Some(location))
+        })?;
+
+        let mut sizes = vec![];
+        for (name, argument) in ["skip", "count"].iter().zip(arguments.iter().skip(1)) {
+            let size = self.evaluate(argument, location)?;
+            if size < 0 {
+                let message = format!("`.incbin` {} {} is negative", name, size);
+                return Err(Diagnostic::error(&message, Some(location)));
+            }
+            sizes.push(size as usize);
+        }
+        let skip = sizes.first().copied().unwrap_or(0);
+        let count = sizes.get(1).copied().unwrap_or_else(|| bytes.len().saturating_sub(skip));
+        let end = match skip.checked_add(count) {
+            Some(end) if end <= bytes.len() => end,
+            _ => {
+                let message = format!("`.incbin` range exceeds the {} bytes of `{}`", bytes.len(), path.display());
+                return Err(Diagnostic::error(&message, Some(location)));
+            }
+        };
+
+        self.emit_words(label, &bytes[skip..end], location, output);
+        Ok(())
+    }
+
+    fn reserve_space(
+        &mut self,
+        directive: &str,
+        label: Option<&str>,
+        arguments: &str,
+        location: &SourceLocation,
+        output: &mut Vec<SourceLine>,
+    ) -> Result<(), Diagnostic> {
+        if !self.section.is_some_and(|section| section.is_data()) {
+            let message = format!("`{}` is only allowed in the .data, .rodata and .bss sections", directive);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+
+        let arguments = split_arguments(arguments);
+        let size = match arguments.first() {
+            Some(size) => self.evaluate(size, location)?,
+            None => {
+                let message = format!("`{}` expects a size in bytes", directive);
+                return Err(Diagnostic::error(&message, Some(location)));
+            }
+        };
+        if size < 0 {
+            let message = format!("`{}` size {} is negative", directive, size);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+        let fill = match arguments.get(1) {
+            Some(fill) => self.evaluate(fill, location)?,
+            None => 0,
+        };
+        if !(-128..=255).contains(&fill) {
+            let message = format!("`{}` fill value {} does not fit in a byte", directive, fill);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+        self.emit_words(label, &vec![fill as u8; size as usize], location, output);
+        Ok(())
+    }
+
+    // Emits `bytes` as little-endian `.word` definitions, the last one padded with zeros.
+    fn emit_words(&mut self, label: Option<&str>, bytes: &[u8], location: &SourceLocation, output: &mut Vec<SourceLine>) {
+        let mut label = label.map(|label| label.to_string());
+        if let Some(label) = &label {
+            self.labels.insert(label.clone());
+        }
+        for word in bytes.chunks(4) {
+            let mut buffer = [0u8; 4];
+            buffer[..word.len()].copy_from_slice(word);
+            let text = format!("{}:\t.word\t0x{:08x}", label.take().unwrap_or_default(), u32::from_le_bytes(buffer));
+            output.push(SourceLine::new(text.trim_start_matches(':'), location.clone()));
+        }
+    }
+
+    fn expand_macro(
+        &mut self,
+        name: &str,
+        label: Option<&str>,
+        arguments: &str,
+        location: &SourceLocation,
+        output: &mut Vec<SourceLine>,
+    ) -> Result<(), Diagnostic> {
+        if self.expansion_stack.iter().any(|expanding| expanding == name) {
+            let chain = self.expansion_stack.join(" -> ");
+            let message = format!("macro `{}` recursively expands itself ({} -> {})", name, chain, name);
+            return Err(Diagnostic::error(&message, Some(location)));
+        }
+
+        let mut body = self.macros[name].expand(arguments, self.expansion_count, location)?;
+        self.expansion_count += 1;
+        self.place_label(label, &mut body, location, output);
+
+        self.expansion_stack.push(name.to_string());
+        let result = self.process(&body, output);
+        self.expansion_stack.pop();
+        result.map(|_| ())
+    }
+
+    // A label in front of a macro call or a block names what it expands to: on a line of its own
+    // in `.text`, on the first expanded line in the data sections, where labels share the datum's line.
+    fn place_label(&mut self, label: Option<&str>, expansion: &mut [SourceLine], location: &SourceLocation, output: &mut Vec<SourceLine>) {
+        let label = match label {
+            Some(label) => label,
+            None => return,
+        };
+        match expansion.first_mut() {
+            Some(first) if self.section.is_some_and(|section| section.is_data()) => first.text = format!("{}:{}", label, first.text),
+            _ => {
+                self.labels.insert(label.to_string());
+                output.push(SourceLine::new(&format!("{}:", label), location.clone()));
+            }
+        }
+    }
+}
+
+fn unquote(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        Some(&text[1..text.len() - 1])
+    } else {
+        Some(text)
+    }
+}
+
+fn find_block_end(lines: &[SourceLine], start: usize, openings: &[&str], closing: &str) -> Result<usize, Diagnostic> {
+    let mut depth = 0;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let (_, directive, _) = split_statement(&line.text);
+        if openings.contains(&directive) {
+            depth += 1;
+        } else if directive == closing {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(index);
+            }
+        }
+    }
+    let (_, opening, _) = split_statement(&lines[start].text);
+    let message = format!("missing `{}` for this `{}`", closing, opening);
+    Err(Diagnostic::error(&message, Some(&lines[start].location)))
+}
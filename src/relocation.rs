@@ -0,0 +1,79 @@
+use crate::image::AddressUnit;
+use crate::utils::get_address_difference;
+
+// Fields left for the linker, named after the `R_NIOS2_*` relocation types.
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelocationKind {
+    S16,
+    PCREL16,
+    CALL26,
+    HI16,
+    LO16,
+    HIADJ16,
+    BFD_RELOC_32,
+}
+
+impl RelocationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RelocationKind::S16 => "R_NIOS2_S16",
+            RelocationKind::PCREL16 => "R_NIOS2_PCREL16",
+            RelocationKind::CALL26 => "R_NIOS2_CALL26",
+            RelocationKind::HI16 => "R_NIOS2_HI16",
+            RelocationKind::LO16 => "R_NIOS2_LO16",
+            RelocationKind::HIADJ16 => "R_NIOS2_HIADJ16",
+            RelocationKind::BFD_RELOC_32 => "R_NIOS2_BFD_RELOC32",
+        }
+    }
+
+    // `%hi`, `%lo` and `%hiadj` operand modifiers.
+    pub fn from_modifier(modifier: &str) -> Option<Self> {
+        match modifier {
+            "%hi" => Some(RelocationKind::HI16),
+            "%lo" => Some(RelocationKind::LO16),
+            "%hiadj" => Some(RelocationKind::HIADJ16),
+            _ => None,
+        }
+    }
+
+    // Applies a `%hi`, `%lo` or `%hiadj` modifier to `value`.
+    pub fn select_bits(&self, value: i32) -> i32 {
+        match self {
+            RelocationKind::HI16 => (value >> 16) & 0xFFFF,
+            RelocationKind::LO16 => value & 0xFFFF,
+            RelocationKind::HIADJ16 => ((value >> 16) + ((value >> 15) & 1)) & 0xFFFF,
+            _ => value,
+        }
+    }
+
+    // Stores `value`, the address of the symbol, into the field of the instruction or datum `word`
+    // found at `address`, encoded the way the assembler encodes it for `unit`. Both addresses are
+    // in assembler address units.
+    pub fn apply(&self, word: u32, address: i32, value: i32, unit: AddressUnit) -> u32 {
+        let immediate = |field: i32| (word & !(0xFFFF << 6)) | ((field as u32 & 0xFFFF) << 6);
+        let target = unit.from_words(value);
+        match self {
+            RelocationKind::S16 => immediate(target),
+            RelocationKind::PCREL16 => immediate(get_address_difference(address, target, unit)),
+            // the 26-bit field holds the word address in either unit
+            RelocationKind::CALL26 => (word & 0x3F) | ((value as u32 & 0x3FF_FFFF) << 6),
+            RelocationKind::HI16 | RelocationKind::LO16 | RelocationKind::HIADJ16 => immediate(self.select_bits(target)),
+            RelocationKind::BFD_RELOC_32 => target as u32,
+        }
+    }
+}
+
+// A symbol whose address an instruction or a datum depends on.
+pub struct SymbolReference {
+    pub kind: RelocationKind,
+    pub symbol: String,
+}
+
+// Addresses are expressed in assembler address units, like the rest of the memory image.
+pub struct Relocation {
+    pub kind: RelocationKind,
+    pub section: String,
+    pub address: i32,
+    pub symbol: String,
+}
@@ -1,14 +1,44 @@
-#[derive(Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Section {
     NONE,
     DATA,
     TEXT,
+    RODATA,
+    BSS,
 }
 
+impl Section {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::NONE => "",
+            Section::DATA => ".data",
+            Section::TEXT => ".text",
+            Section::RODATA => ".rodata",
+            Section::BSS => ".bss",
+        }
+    }
+
+    // Sections holding `.word` definitions rather than instructions.
+    pub fn is_data(&self) -> bool {
+        matches!(self, Section::DATA | Section::RODATA | Section::BSS)
+    }
+}
+
+// Accepts both the `.data` shorthand and the `.section .data[, flags]` form.
 pub fn resolve_section(code: &str) -> Option<Section> {
-    match code {
-        "\t.data" => Some(Section::DATA),
-        "\t.text" => Some(Section::TEXT),
+    let code = code.trim();
+    let name = match code.strip_prefix(".section") {
+        Some(arguments) if arguments.starts_with(char::is_whitespace) => {
+            arguments.split(',').next().unwrap_or("").trim().trim_matches('"')
+        }
+        _ => code,
+    };
+    match name {
+        ".data" => Some(Section::DATA),
+        ".text" => Some(Section::TEXT),
+        ".rodata" => Some(Section::RODATA),
+        ".bss" => Some(Section::BSS),
         _ => None,
     }
 }
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use crate::constants::WORD_BYTES;
+use crate::image::Segment;
+
+const PAGE_BYTES: u32 = 4096;
+
+// Little-endian byte-addressable memory covering the whole 32-bit address space. Pages are
+// allocated on the first write, reading a byte never written gives zero.
+#[derive(Default)]
+pub struct Memory {
+    pages: HashMap<u32, Vec<u8>>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Copies the words of `segment` from its base, which counts assembler address units.
+    pub fn load_segment(&mut self, segment: &Segment) {
+        let base = (segment.base as u32).wrapping_mul(WORD_BYTES as u32);
+        for (index, byte) in segment.bytes().iter().enumerate() {
+            self.write_byte(base.wrapping_add(index as u32), *byte);
+        }
+    }
+
+    pub fn read_byte(&self, address: u32) -> u8 {
+        self.pages.get(&(address / PAGE_BYTES)).map_or(0, |page| page[(address % PAGE_BYTES) as usize])
+    }
+
+    pub fn write_byte(&mut self, address: u32, value: u8) {
+        let page = self.pages.entry(address / PAGE_BYTES).or_insert_with(|| vec![0; PAGE_BYTES as usize]);
+        page[(address % PAGE_BYTES) as usize] = value;
+    }
+
+    pub fn read_half(&self, address: u32) -> u16 {
+        u16::from_le_bytes([self.read_byte(address), self.read_byte(address.wrapping_add(1))])
+    }
+
+    pub fn write_half(&mut self, address: u32, value: u16) {
+        for (index, byte) in value.to_le_bytes().iter().enumerate() {
+            self.write_byte(address.wrapping_add(index as u32), *byte);
+        }
+    }
+
+    pub fn read_word(&self, address: u32) -> u32 {
+        let mut bytes = [0; 4];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.read_byte(address.wrapping_add(index as u32));
+        }
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn write_word(&mut self, address: u32, value: u32) {
+        for (index, byte) in value.to_le_bytes().iter().enumerate() {
+            self.write_byte(address.wrapping_add(index as u32), *byte);
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::HashSet;
+
+use crate::constants::{BA_REGISTER, EA_REGISTER, RA_REGISTER, ZERO_REGISTER};
+use crate::disassembler::{control_register_name, decode_word, find_instruction, register_name};
+use crate::image::{AddressUnit, MemoryImage};
+use crate::input::{elf_entry_point, read_image};
+
+mod memory;
+
+pub use self::memory::Memory;
+
+// Control registers, numbered as `rdctl` and `wrctl` encode them.
+pub const STATUS: usize = 0;
+pub const ESTATUS: usize = 1;
+pub const BSTATUS: usize = 2;
+pub const IENABLE: usize = 3;
+pub const IPENDING: usize = 4;
+pub const CPUID: usize = 5;
+pub const EXCEPTION: usize = 7;
+
+pub const STATUS_PIE: u32 = 0x1;
+pub const STATUS_U: u32 = 0x2;
+
+// Causes written to bits 6..2 of the `exception` register.
+pub const CAUSE_INTERRUPT: u32 = 2;
+pub const CAUSE_TRAP: u32 = 3;
+pub const CAUSE_UNIMPLEMENTED: u32 = 4;
+pub const CAUSE_SUPERVISOR_ONLY: u32 = 10;
+
+// Reset at zero and the exception handler eight words in, as in the Nios II reference designs.
+// Both count words, `Simulator::from_image` scales them to the unit of the image.
+pub const DEFAULT_RESET_ADDRESS: u32 = 0x0;
+pub const DEFAULT_EXCEPTION_ADDRESS: u32 = 0x8;
+
+// Instructions raising a supervisor-only instruction exception in user mode.
+const SUPERVISOR_ONLY: [&str; 8] = ["bret", "eret", "initd", "initi", "rdctl", "rdprs", "wrctl", "wrprs"];
+
+// Instructions run by `sim` before it gives up waiting for a `break`.
+pub const DEFAULT_MAX_STEPS: u64 = 1_000_000;
+
+// Why the simulator stopped, the program counter being left at the instruction concerned.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    BREAK,
+    LIMIT,
+    // a word that encodes no instruction of `INSTRUCTION_TABLE`
+    INVALID(u32),
+}
+
+// A Nios II R1 core. The program counter and the addresses computed by the instructions count
+// `unit`: bytes as on the hardware, or 32-bit words like the assembler's default output, where
+// address `A` is the word at byte `A * 4` of the memory and byte and halfword accesses reach the
+// low bytes of that word.
+pub struct Simulator {
+    pub registers: [u32; 32],
+    pub pc: u32,
+    pub unit: AddressUnit,
+    pub control: [u32; 32],
+    pub memory: Memory,
+    pub reset_address: u32,
+    pub exception_address: u32,
+    // where `break` jumps to, `None` stopping the simulator at the `break` like a debugger would
+    pub break_address: Option<u32>,
+    // the 32 hardware interrupt request lines, `ipending` showing those enabled by `ienable`
+    pub irq: u32,
+    // mnemonics raising an unimplemented instruction exception, as on cores without them
+    pub unimplemented: HashSet<&'static str>,
+    // instructions executed so far
+    pub steps: u64,
+    // IRQ lines `schedule_irq` raises at a step, and those of them raised until the `eret` of
+    // the handler servicing them
+    scheduled: Vec<(u64, u32)>,
+    held: u32,
+    serviced: u32,
+}
+
+impl Simulator {
+    pub fn new(entry: u32) -> Self {
+        Self {
+            registers: [0; 32],
+            pc: entry,
+            unit: AddressUnit::WORD,
+            control: [0; 32],
+            memory: Memory::new(),
+            reset_address: DEFAULT_RESET_ADDRESS,
+            exception_address: DEFAULT_EXCEPTION_ADDRESS,
+            break_address: None,
+            irq: 0,
+            unimplemented: HashSet::new(),
+            steps: 0,
+            scheduled: vec![],
+            held: 0,
+            serviced: 0,
+        }
+    }
+
+    // Loads the segments of an assembled or linked image, starting at `entry`, in the unit of
+    // the image. The `.reset` and `.exceptions` sections the Nios II toolchain places at the
+    // vectors give their addresses.
+    pub fn from_image(image: &MemoryImage, entry: i32) -> Self {
+        let mut simulator = Self::new(entry as u32);
+        simulator.unit = image.unit;
+        simulator.reset_address = image.unit.from_words(DEFAULT_RESET_ADDRESS as i32) as u32;
+        simulator.exception_address = image.unit.from_words(DEFAULT_EXCEPTION_ADDRESS as i32) as u32;
+        for segment in image.segments.iter() {
+            simulator.memory.load_segment(segment);
+            match segment.name.as_str() {
+                ".reset" => simulator.reset_address = image.unit.from_words(segment.base) as u32,
+                ".exceptions" => simulator.exception_address = image.unit.from_words(segment.base) as u32,
+                _ => {}
+            }
+        }
+        simulator
+    }
+
+    // Restarts from the reset address in supervisor mode with interrupts disabled. The registers
+    // and the memory are kept, as the hardware does.
+    pub fn reset(&mut self) {
+        self.pc = self.reset_address;
+        self.control = [0; 32];
+        self.irq &= !self.held;
+        self.held = 0;
+        self.serviced = 0;
+    }
+
+    // Raises IRQ `line` before the instruction run after `step` instructions. The line stays
+    // raised until the `eret` of the handler servicing it.
+    pub fn schedule_irq(&mut self, line: u32, step: u64) {
+        self.scheduled.push((step, line & 0x1F));
+    }
+
+    // Loads an image file as `read_image` does, starting at the entry point of an ELF executable
+    // or else at the first word of the image.
+    pub fn from_bytes(bytes: &[u8], base: i32) -> Result<Self, String> {
+        let image = read_image(bytes, base)?;
+        let entry = elf_entry_point(bytes).unwrap_or_else(|| image.unit.from_words(entry_point(&image)));
+        Ok(Self::from_image(&image, entry))
+    }
+
+    pub fn fetch(&self) -> u32 {
+        self.memory.read_word(self.pc.wrapping_mul(self.unit.bytes() as u32))
+    }
+
+    // Runs until a `break`, an invalid instruction or `max_steps` instructions.
+    pub fn run(&mut self, max_steps: u64) -> Stop {
+        self.run_traced(max_steps, |_| {})
+    }
+
+    // Same as `run`, calling `trace` before each instruction.
+    pub fn run_traced(&mut self, max_steps: u64, mut trace: impl FnMut(&Self)) -> Stop {
+        let limit = self.steps.saturating_add(max_steps);
+        while self.steps < limit {
+            // an interrupt taken first is traced with the handler instruction it leads to
+            self.take_interrupt();
+            trace(self);
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+        }
+        Stop::LIMIT
+    }
+
+    // Takes a pending interrupt, then executes one instruction. An invalid word, or a `break`
+    // without a break address, stops the simulator before it runs.
+    pub fn step(&mut self) -> Option<Stop> {
+        self.take_interrupt();
+        let word = self.fetch();
+        let fields = decode_word(word);
+        let instruction = match find_instruction(fields.opcode, fields.opx) {
+            Some(instruction) => instruction,
+            None => return Some(Stop::INVALID(word)),
+        };
+        let next = self.pc.wrapping_add(self.unit.per_word() as u32);
+        if self.unimplemented.contains(instruction.name) {
+            self.raise_exception(CAUSE_UNIMPLEMENTED, next);
+            self.steps += 1;
+            return None;
+        }
+        if self.control[STATUS] & STATUS_U != 0 && SUPERVISOR_ONLY.contains(&instruction.name) {
+            self.raise_exception(CAUSE_SUPERVISOR_ONLY, next);
+            self.steps += 1;
+            return None;
+        }
+        let a = self.registers[fields.a as usize];
+        let b = self.registers[fields.b as usize];
+        let simm = fields.imm16 as i16 as i32 as u32;
+        let zimm = fields.imm16 as u32;
+        let imm5 = fields.imm5 as u32;
+        // the byte address of a load or a store
+        let address = a.wrapping_add(simm).wrapping_mul(self.unit.bytes() as u32);
+        let branch = next.wrapping_add(simm);
+        let (i_type, r_type) = (fields.b as usize, fields.c as usize);
+
+        // the 26-bit field holds a word address within the region of the program counter
+        let region = 0x0400_0000u32.wrapping_mul(self.unit.per_word() as u32);
+        let jump = (self.pc & !region.wrapping_sub(1)) | (fields.imm26 as u32).wrapping_mul(self.unit.per_word() as u32);
+
+        let mut pc = next;
+        match instruction.name {
+            "call" => {
+                self.set_register(RA_REGISTER as usize, next);
+                pc = jump;
+            }
+            "jmpi" => pc = jump,
+
+            "addi" => self.set_register(i_type, a.wrapping_add(simm)),
+            "andi" => self.set_register(i_type, a & zimm),
+            "ori" => self.set_register(i_type, a | zimm),
+            "xori" => self.set_register(i_type, a ^ zimm),
+            "andhi" => self.set_register(i_type, a & (zimm << 16)),
+            "orhi" => self.set_register(i_type, a | (zimm << 16)),
+            "xorhi" => self.set_register(i_type, a ^ (zimm << 16)),
+            "muli" => self.set_register(i_type, a.wrapping_mul(simm)),
+            "cmpeqi" => self.set_register(i_type, (a == simm) as u32),
+            "cmpnei" => self.set_register(i_type, (a != simm) as u32),
+            "cmpgei" => self.set_register(i_type, (a as i32 >= simm as i32) as u32),
+            "cmplti" => self.set_register(i_type, ((a as i32) < simm as i32) as u32),
+            "cmpgeui" => self.set_register(i_type, (a >= zimm) as u32),
+            "cmpltui" => self.set_register(i_type, (a < zimm) as u32),
+
+            "ldb" | "ldbio" => self.set_register(i_type, self.memory.read_byte(address) as i8 as i32 as u32),
+            "ldbu" | "ldbuio" => self.set_register(i_type, self.memory.read_byte(address) as u32),
+            "ldh" | "ldhio" => self.set_register(i_type, self.memory.read_half(address) as i16 as i32 as u32),
+            "ldhu" | "ldhuio" => self.set_register(i_type, self.memory.read_half(address) as u32),
+            "ldw" | "ldwio" => self.set_register(i_type, self.memory.read_word(address)),
+            "stb" | "stbio" => self.memory.write_byte(address, b as u8),
+            "sth" | "sthio" => self.memory.write_half(address, b as u16),
+            "stw" | "stwio" => self.memory.write_word(address, b),
+
+            "br" => pc = branch,
+            "beq" if a == b => pc = branch,
+            "bne" if a != b => pc = branch,
+            "bge" if a as i32 >= b as i32 => pc = branch,
+            "blt" if (a as i32) < b as i32 => pc = branch,
+            "bgeu" if a >= b => pc = branch,
+            "bltu" if a < b => pc = branch,
+            "beq" | "bne" | "bge" | "blt" | "bgeu" | "bltu" => {}
+
+            // there is a single register set, the previous one is the current one
+            "rdprs" => self.set_register(i_type, a.wrapping_add(simm)),
+            "wrprs" => self.set_register(r_type, a),
+
+            "add" => self.set_register(r_type, a.wrapping_add(b)),
+            "sub" => self.set_register(r_type, a.wrapping_sub(b)),
+            "and" => self.set_register(r_type, a & b),
+            "or" => self.set_register(r_type, a | b),
+            "xor" => self.set_register(r_type, a ^ b),
+            "nor" => self.set_register(r_type, !(a | b)),
+            "cmpeq" => self.set_register(r_type, (a == b) as u32),
+            "cmpne" => self.set_register(r_type, (a != b) as u32),
+            "cmpge" => self.set_register(r_type, (a as i32 >= b as i32) as u32),
+            "cmplt" => self.set_register(r_type, ((a as i32) < b as i32) as u32),
+            "cmpgeu" => self.set_register(r_type, (a >= b) as u32),
+            "cmpltu" => self.set_register(r_type, (a < b) as u32),
+
+            "sll" => self.set_register(r_type, a << (b & 0x1F)),
+            "slli" => self.set_register(r_type, a << imm5),
+            "srl" => self.set_register(r_type, a >> (b & 0x1F)),
+            "srli" => self.set_register(r_type, a >> imm5),
+            "sra" => self.set_register(r_type, (a as i32 >> (b & 0x1F)) as u32),
+            "srai" => self.set_register(r_type, (a as i32 >> imm5) as u32),
+            "rol" => self.set_register(r_type, a.rotate_left(b & 0x1F)),
+            "roli" => self.set_register(r_type, a.rotate_left(imm5)),
+            "ror" => self.set_register(r_type, a.rotate_right(b & 0x1F)),
+
+            // the `mulx` variants keep the high half of the 64-bit product
+            "mul" => self.set_register(r_type, a.wrapping_mul(b)),
+            "mulxuu" => self.set_register(r_type, ((a as u64 * b as u64) >> 32) as u32),
+            "mulxsu" => self.set_register(r_type, ((a as i32 as i64 * b as i64) >> 32) as u32),
+            "mulxss" => self.set_register(r_type, ((a as i32 as i64 * b as i32 as i64) >> 32) as u32),
+            // the result of a division by zero is undefined, the simulator gives all ones
+            "div" | "divu" if b == 0 => self.set_register(r_type, u32::MAX),
+            "div" => self.set_register(r_type, (a as i32).wrapping_div(b as i32) as u32),
+            "divu" => self.set_register(r_type, a / b),
+
+            "nextpc" => self.set_register(r_type, next),
+            "callr" => {
+                pc = a;
+                self.set_register(RA_REGISTER as usize, next);
+            }
+            "jmp" => pc = a,
+            "ret" => pc = self.registers[RA_REGISTER as usize],
+
+            "rdctl" => self.set_register(r_type, self.control[imm5 as usize]),
+            "wrctl" => self.write_control(imm5 as usize, a),
+            "trap" => {
+                self.raise_exception(CAUSE_TRAP, next);
+                pc = self.exception_address;
+            }
+            "eret" => {
+                self.control[STATUS] = self.control[ESTATUS];
+                pc = self.registers[EA_REGISTER as usize];
+                // the handler is done with the scheduled lines it serviced
+                self.irq &= !self.serviced;
+                self.held &= !self.serviced;
+                self.serviced = 0;
+            }
+            "bret" => {
+                self.control[STATUS] = self.control[BSTATUS];
+                pc = self.registers[BA_REGISTER as usize];
+            }
+            "break" => match self.break_address {
+                Some(break_address) => {
+                    self.control[BSTATUS] = self.control[STATUS];
+                    self.control[STATUS] &= !(STATUS_PIE | STATUS_U);
+                    self.set_register(BA_REGISTER as usize, next);
+                    pc = break_address;
+                }
+                None => return Some(Stop::BREAK),
+            },
+
+            // caches and memory ordering are not modelled
+            "flushd" | "flushda" | "flushi" | "flushp" | "initd" | "initda" | "initi" | "sync" => {}
+            _ => return Some(Stop::INVALID(word)),
+        }
+        self.pc = pc;
+        self.steps += 1;
+        None
+    }
+
+    // Raises the scheduled lines that are due, then takes an interrupt when `status.PIE` is set
+    // and `ipending` is not zero. As for any exception, `ea` is the address after the instruction
+    // not run, so a handler returns to `ea` less one word. Returns whether it did.
+    pub fn take_interrupt(&mut self) -> bool {
+        let steps = self.steps;
+        let due = self.scheduled.iter().filter(|(step, _)| *step <= steps).fold(0, |lines, (_, line)| lines | 1 << line);
+        self.scheduled.retain(|(step, _)| *step > steps);
+        self.irq |= due;
+        self.held |= due;
+
+        self.control[IPENDING] = self.irq & self.control[IENABLE];
+        if self.control[STATUS] & STATUS_PIE == 0 || self.control[IPENDING] == 0 {
+            return false;
+        }
+        self.serviced |= self.control[IPENDING] & self.held;
+        self.raise_exception(CAUSE_INTERRUPT, self.pc.wrapping_add(self.unit.per_word() as u32));
+        true
+    }
+
+    // Saves `status` to `estatus` and `return_address` to `ea`, and enters the exception handler in
+    // supervisor mode with interrupts disabled.
+    fn raise_exception(&mut self, cause: u32, return_address: u32) {
+        self.control[ESTATUS] = self.control[STATUS];
+        self.control[STATUS] &= !(STATUS_PIE | STATUS_U);
+        self.control[EXCEPTION] = cause << 2;
+        self.set_register(EA_REGISTER as usize, return_address);
+        self.pc = self.exception_address;
+    }
+
+    fn set_register(&mut self, register: usize, value: u32) {
+        if register != ZERO_REGISTER as usize {
+            self.registers[register] = value;
+        }
+    }
+
+    // `ipending`, `cpuid`, `exception` and the reserved registers are read-only.
+    fn write_control(&mut self, register: usize, value: u32) {
+        match register {
+            STATUS | ESTATUS | BSTATUS => self.control[register] = value & (STATUS_PIE | STATUS_U),
+            IENABLE => {
+                self.control[register] = value;
+                self.control[IPENDING] = self.irq & value;
+            }
+            _ => {}
+        }
+    }
+
+    // The general-purpose registers four per line, then the program counter and the control
+    // registers, all in hexadecimal.
+    pub fn dump_registers(&self) -> String {
+        let mut dump = String::new();
+        for (index, value) in self.registers.iter().enumerate() {
+            let separator = if index % 4 == 3 { "\n" } else { "  " };
+            dump += &format!("{:>4} {:08x}{}", register_name(index as i32), value, separator);
+        }
+        dump += &format!("{:>4} {:08x}", "pc", self.pc);
+        for register in [STATUS, ESTATUS, BSTATUS, IENABLE, IPENDING, EXCEPTION] {
+            dump += &format!("  {} {:08x}", control_register_name(register as i32), self.control[register]);
+        }
+        dump + "\n"
+    }
+}
+
+// Where a program assembled in memory starts: `_start` when it is defined, else the first word
+// of `.text`.
+pub fn entry_point(image: &MemoryImage) -> i32 {
+    match image.find_symbol("_start") {
+        Some(symbol) => symbol.address,
+        None => image
+            .segments
+            .iter()
+            .find(|segment| segment.name == ".text")
+            .or_else(|| image.segments.first())
+            .map_or(0, |segment| segment.base),
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::utils::read_lines;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceLocation {
+    pub file: Rc<PathBuf>,
+    pub line: usize,
+    pub included_from: Option<Rc<SourceLocation>>,
+}
+
+impl SourceLocation {
+    pub fn new(file: Rc<PathBuf>, line: usize, included_from: Option<Rc<SourceLocation>>) -> Self {
+        Self {
+            file,
+            line,
+            included_from,
+        }
+    }
+
+    pub fn include_chain(&self) -> Vec<&SourceLocation> {
+        let mut chain = vec![];
+        let mut current = self.included_from.as_deref();
+        while let Some(location) = current {
+            chain.push(location);
+            current = location.included_from.as_deref();
+        }
+        chain
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceLine {
+    pub text: String,
+    pub location: SourceLocation,
+}
+
+impl SourceLine {
+    pub fn new(text: &str, location: SourceLocation) -> Self {
+        Self {
+            text: text.to_string(),
+            location,
+        }
+    }
+}
+
+pub fn read_source_file(path: &Path, input_file: &mut File, included_from: Option<Rc<SourceLocation>>) -> Vec<SourceLine> {
+    let file = Rc::new(path.to_path_buf());
+    read_lines(input_file)
+        .enumerate()
+        .map(|(index, text)| SourceLine::new(&text, SourceLocation::new(file.clone(), index + 1, included_from.clone())))
+        .collect()
+}
+
+// Same as `read_source_file` for a source that was already read, like the standard input.
+pub fn split_source_text(path: &Path, text: &str) -> Vec<SourceLine> {
+    let file = Rc::new(path.to_path_buf());
+    text.lines()
+        .enumerate()
+        .map(|(index, text)| SourceLine::new(text, SourceLocation::new(file.clone(), index + 1, None)))
+        .collect()
+}
+
+pub fn find_source_file(name: &str, including_file: &Path, include_paths: &[PathBuf]) -> io::Result<PathBuf> {
+    let directory = including_file.parent().unwrap_or_else(|| Path::new(""));
+    std::iter::once(directory)
+        .chain(include_paths.iter().map(|path| path.as_path()))
+        .map(|directory| directory.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot find `{}`", name)))
+}
@@ -0,0 +1,56 @@
+use std::collections::{HashMap, HashSet};
+
+// Symbol binding set by `.global`, `.local` and `.weak`, symbols are local by default.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    LOCAL,
+    GLOBAL,
+    WEAK,
+}
+
+#[derive(Default)]
+pub struct SymbolTable {
+    constants: HashMap<String, i32>,
+    bindings: HashMap<String, Binding>,
+    // names marked by `.export`, see `export_symbols`
+    exports: HashSet<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(&mut self, name: &str, value: i32) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<i32> {
+        self.constants.get(name).copied()
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.constants.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.constants.contains_key(name)
+    }
+
+    pub fn bind(&mut self, name: &str, binding: Binding) {
+        self.bindings.insert(name.to_string(), binding);
+    }
+
+    pub fn binding(&self, name: &str) -> Binding {
+        self.bindings.get(name).copied().unwrap_or(Binding::LOCAL)
+    }
+
+    pub fn export(&mut self, name: &str) {
+        self.exports.insert(name.to_string());
+    }
+
+    pub fn exports(&self) -> &HashSet<String> {
+        &self.exports
+    }
+}
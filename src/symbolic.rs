@@ -0,0 +1,344 @@
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::constants::{WORD_SIZE, ZERO_REGISTER};
+use crate::disassembler::{branch_target, decode_word, disassemble_word, find_instruction, format_instruction, DecodedWord};
+use crate::image::{AddressUnit, ImageSymbol, Segment};
+use crate::instruction::{Instruction, InstructionFormat};
+use crate::map::ABSOLUTE_SECTION;
+use crate::symbol::{Binding, SymbolTable};
+use crate::text::get_text_from_code;
+
+// Sections of the segments following the text segment, which the assembler lays out one after
+// the other from the data base.
+const DATA_SECTIONS: [&str; 3] = [".data", ".rodata", ".bss"];
+
+// Code from a `call` target to the first `ret` that follows it, `end` excluded.
+pub struct Function {
+    pub name: String,
+    pub start: i32,
+    pub end: i32,
+}
+
+// Symbols in the `nm` format, `ADDRESS [TYPE] NAME` per line with a hexadecimal address. An
+// uppercase type is a global symbol, `W` or `V` a weak one, and undefined `U` symbols are skipped.
+pub fn read_symbol_file(text: &str) -> Result<Vec<ImageSymbol>, String> {
+    let mut symbols = vec![];
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let (address, kind, name) = match line.split_whitespace().collect::<Vec<&str>>()[..] {
+            [] => continue,
+            // undefined symbols have no address
+            [kind, _] if kind == "U" || kind == "w" || kind == "v" => continue,
+            [address, name] => (address, 'n', name),
+            [address, kind, name] if kind.len() == 1 => (address, kind.chars().next().unwrap(), name),
+            _ => return Err(format!("line {}: invalid symbol `{}`", index + 1, line.trim())),
+        };
+        let section = match kind.to_ascii_lowercase() {
+            'u' => continue,
+            't' => ".text",
+            'd' => ".data",
+            'r' => ".rodata",
+            'b' => ".bss",
+            'a' => ABSOLUTE_SECTION,
+            'n' | 'w' | 'v' => "",
+            _ => return Err(format!("line {}: invalid symbol type `{}`", index + 1, kind)),
+        };
+        let binding = match kind {
+            'W' | 'w' | 'V' | 'v' => Binding::WEAK,
+            'n' => Binding::LOCAL,
+            _ if kind.is_ascii_uppercase() => Binding::GLOBAL,
+            _ => Binding::LOCAL,
+        };
+        let address = u32::from_str_radix(address.trim_start_matches("0x"), 16)
+            .map_err(|_| format!("line {}: invalid address `{}`", index + 1, address))?;
+        if !is_symbol_name(name) {
+            return Err(format!("line {}: invalid symbol name `{}`", index + 1, name));
+        }
+        symbols.push(ImageSymbol {
+            name: name.to_string(),
+            address: address as i32,
+            section: section.to_string(),
+            binding,
+        });
+    }
+    Ok(symbols)
+}
+
+fn is_symbol_name(name: &str) -> bool {
+    let mut characters = name.chars();
+    characters.next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == '.')
+        && characters.all(|character| character.is_ascii_alphanumeric() || character == '_' || character == '$')
+}
+
+// Source that assembles back to `segments`, the first one being the text segment, with labels
+// for the targets of branches and calls, for the addresses built by `%hi`/`%lo` pairs and for
+// `symbols`. Words that do not assemble back to themselves are written as `.word`. A gap between
+// two data segments is filled with zero words, which preserves the memory contents.
+pub fn disassemble_symbolic(segments: &[Segment], symbols: &[ImageSymbol]) -> Result<String, String> {
+    let text = match segments.first() {
+        Some(text) => text,
+        None => return Ok(String::new()),
+    };
+    let analysis = Analysis::new(segments, symbols);
+    let mut output = format!("# assemble with --text-base 0x{:x}", text.base);
+    if let Some(data) = segments.get(1) {
+        output += &format!(" --data-base 0x{:x}", data.base);
+    }
+    output += "\n";
+
+    for symbol in symbols.iter().filter(|symbol| analysis.contains(symbol.address)) {
+        if analysis.names.get(&symbol.address) == Some(&symbol.name) && symbol.binding != Binding::LOCAL {
+            let directive = if symbol.binding == Binding::WEAK { ".weak" } else { ".global" };
+            output += &format!("\t{}\t{}\n", directive, symbol.name);
+        }
+    }
+    for (address, name) in analysis.names.iter().filter(|(address, _)| !analysis.contains(**address)) {
+        output += &format!("\t.equ\t{}, 0x{:x}\n", name, address);
+    }
+    for (name, address) in analysis.aliases.iter() {
+        output += &format!("\t.equ\t{}, 0x{:x}\n", name, address);
+    }
+
+    output += "\t.text\n";
+    let target_name = |address: i32| analysis.names.get(&address).cloned();
+    for (index, word) in text.words.iter().enumerate() {
+        let address = text.base + index as i32 * WORD_SIZE;
+        if let Some(name) = analysis.names.get(&address) {
+            output += &format!("{}:\n", name);
+        }
+        let instruction = if analysis.assembles_back[index] {
+            let immediate = analysis.immediates.get(&address).map(|(modifier, target)| {
+                format!("{}({})", modifier, analysis.names[target])
+            });
+            format_instruction(*word, address, AddressUnit::WORD, &target_name, immediate.as_deref())
+        } else {
+            format!(".word\t0x{:08x}", word)
+        };
+        output += &format!("\t{}\n", instruction);
+        if let Some(function) = analysis.functions.iter().find(|function| function.end == address + WORD_SIZE) {
+            output += &format!("# end of {}\n", function.name);
+        }
+    }
+
+    let mut section = 0;
+    let mut end: Option<i32> = None;
+    for segment in segments.iter().skip(1) {
+        match end {
+            None => output += &format!("\t{}\n", DATA_SECTIONS[0]),
+            Some(end) if segment.base == end && section + 1 < DATA_SECTIONS.len() => {
+                section += 1;
+                output += &format!("\t{}\n", DATA_SECTIONS[section]);
+            }
+            Some(end) if segment.base >= end => {
+                output += &"\t.word\t0x00000000\n".repeat(((segment.base - end) / WORD_SIZE) as usize);
+            }
+            Some(_) => return Err(format!("segment at 0x{:x} overlaps the previous one", segment.base)),
+        }
+        for (index, word) in segment.words.iter().enumerate() {
+            let address = segment.base + index as i32 * WORD_SIZE;
+            let name = analysis.names.get(&address).map_or("", |name| name.as_str());
+            let separator = if name.is_empty() { "" } else { ":" };
+            output += &format!("{}{}\t.word\t0x{:08x}\n", name, separator, word);
+        }
+        end = Some(segment.end());
+    }
+    Ok(output)
+}
+
+// Finds the functions of the text segment, see `Function`.
+pub fn find_functions(segments: &[Segment], symbols: &[ImageSymbol]) -> Vec<Function> {
+    if segments.is_empty() {
+        return vec![];
+    }
+    Analysis::new(segments, symbols).functions
+}
+
+struct Analysis<'a> {
+    segments: &'a [Segment],
+    // for each word of the text segment, whether its disassembly encodes back to it
+    assembles_back: Vec<bool>,
+    names: BTreeMap<i32, String>,
+    // further names of an address and the absolute symbols, from the symbol file
+    aliases: Vec<(String, i32)>,
+    // `%hi`, `%hiadj` or `%lo` and the address it selects bits of, by instruction address
+    immediates: HashMap<i32, (&'static str, i32)>,
+    functions: Vec<Function>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(segments: &'a [Segment], symbols: &[ImageSymbol]) -> Self {
+        let text = &segments[0];
+        let assembles_back = text
+            .words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| assembles_back(*word, text.base + index as i32 * WORD_SIZE))
+            .collect();
+        let mut analysis = Self {
+            segments,
+            assembles_back,
+            names: BTreeMap::new(),
+            aliases: vec![],
+            immediates: HashMap::new(),
+            functions: vec![],
+        };
+        for symbol in symbols.iter() {
+            // a constant is no label, even when its value is an address of the image
+            if symbol.section == ABSOLUTE_SECTION {
+                analysis.aliases.push((symbol.name.clone(), symbol.address));
+                continue;
+            }
+            match analysis.names.entry(symbol.address) {
+                Entry::Vacant(entry) => {
+                    entry.insert(symbol.name.clone());
+                }
+                Entry::Occupied(_) => analysis.aliases.push((symbol.name.clone(), symbol.address)),
+            }
+        }
+
+        let mut calls = BTreeSet::new();
+        let mut targets = BTreeSet::new();
+        for index in 0..text.words.len() {
+            let address = text.base + index as i32 * WORD_SIZE;
+            let (decoded, instruction) = match analysis.instruction(index) {
+                Some(instruction) => instruction,
+                None => continue,
+            };
+            if matches!(decoded.format, InstructionFormat::JUMP) {
+                if instruction.name == "call" {
+                    calls.insert(decoded.imm26);
+                }
+                targets.insert(decoded.imm26);
+            } else if instruction.is_conditional_branch() || instruction.is_relative_branch() {
+                targets.insert(branch_target(address, decoded.imm16 as i16 as i32, AddressUnit::WORD));
+            } else if instruction.name == "orhi" && decoded.a == ZERO_REGISTER && decoded.b != ZERO_REGISTER {
+                analysis.find_address_pair(index, decoded.b, decoded.imm16);
+            }
+        }
+        targets.extend(analysis.immediates.values().map(|(_, target)| *target));
+        targets.extend(segments.iter().skip(1).map(|segment| segment.base));
+
+        targets.retain(|target| analysis.contains(*target));
+        for target in targets {
+            let name = if calls.contains(&target) {
+                format!("func_{:x}", target)
+            } else if text.contains(target) {
+                format!(".L{:x}", target)
+            } else {
+                format!("data_{:x}", target)
+            };
+            analysis.names.entry(target).or_insert(name);
+        }
+        analysis.functions = analysis.find_functions(&calls);
+        analysis
+    }
+
+    // Names at addresses inside the segments are labels, the other ones are `.equ` constants.
+    fn contains(&self, address: i32) -> bool {
+        self.segments.iter().any(|segment| segment.contains(address))
+    }
+
+    fn instruction(&self, index: usize) -> Option<(DecodedWord, &'static Instruction)> {
+        if !self.assembles_back[index] {
+            return None;
+        }
+        let decoded = decode_word(self.segments[0].words[index]);
+        find_instruction(decoded.opcode, decoded.opx).map(|instruction| (decoded, instruction))
+    }
+
+    // Follows the register set by `orhi register, zero, high` to the `addi`, `ori`, loads and stores
+    // using it as base, up to the next jump or the next write to the register.
+    fn find_address_pair(&mut self, index: usize, register: i32, high: i32) {
+        let text = &self.segments[0];
+        let high_address = text.base + index as i32 * WORD_SIZE;
+        for next in index + 1..text.words.len() {
+            let (decoded, instruction) = match self.instruction(next) {
+                Some(instruction) => instruction,
+                None => return,
+            };
+            if is_control_transfer(&decoded, instruction) {
+                return;
+            }
+            let name = instruction.name;
+            let uses_low = matches!(decoded.format, InstructionFormat::IMMEDIATE)
+                && decoded.a == register
+                && (name == "addi" || name == "ori" || name.starts_with("ld") || name.starts_with("st"));
+            if uses_low {
+                let (high_modifier, address) = if name == "ori" {
+                    ("%hi", (high << 16) | decoded.imm16)
+                } else {
+                    ("%hiadj", (high << 16).wrapping_add(decoded.imm16 as i16 as i32))
+                };
+                if self.contains(address) || self.names.contains_key(&address) {
+                    let low_address = text.base + next as i32 * WORD_SIZE;
+                    // the low half of any address built from `high` gives back the immediate
+                    self.immediates.entry(high_address).or_insert((high_modifier, address));
+                    self.immediates.entry(low_address).or_insert(("%lo", address));
+                }
+            }
+            if written_register(&decoded, instruction) == Some(register) {
+                return;
+            }
+        }
+    }
+
+    fn find_functions(&self, calls: &BTreeSet<i32>) -> Vec<Function> {
+        let text = &self.segments[0];
+        let starts = calls.iter().copied().filter(|address| text.contains(*address)).collect::<Vec<i32>>();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(index, start)| {
+                let limit = starts.get(index + 1).copied().unwrap_or_else(|| text.end());
+                let ret = (*start..limit).step_by(WORD_SIZE as usize).find(|address| {
+                    let index = ((address - text.base) / WORD_SIZE) as usize;
+                    self.instruction(index).is_some_and(|(_, instruction)| instruction.name == "ret")
+                });
+                Function {
+                    name: self.names[start].clone(),
+                    start: *start,
+                    end: ret.map_or(limit, |ret| ret + WORD_SIZE),
+                }
+            })
+            .collect()
+    }
+}
+
+// Whether the disassembly of `word` encodes back to it, the encoder ignoring the fields that the
+// instruction does not use.
+fn assembles_back(word: u32, address: i32) -> bool {
+    let decoded = decode_word(word);
+    let instruction = match find_instruction(decoded.opcode, decoded.opx) {
+        Some(instruction) => instruction,
+        None => return false,
+    };
+    // branch targets are written as unsigned addresses
+    let is_branch = instruction.is_conditional_branch() || instruction.is_relative_branch();
+    if is_branch && branch_target(address, decoded.imm16 as i16 as i32, AddressUnit::WORD) < 0 {
+        return false;
+    }
+    let unit = AddressUnit::WORD;
+    get_text_from_code(&disassemble_word(word, address, unit), address, &[], &[], &SymbolTable::new(), unit, None)
+        .is_ok_and(|text| text.to_word() == word)
+}
+
+fn is_control_transfer(decoded: &DecodedWord, instruction: &Instruction) -> bool {
+    matches!(decoded.format, InstructionFormat::JUMP)
+        || instruction.is_conditional_branch()
+        || instruction.is_relative_branch()
+        || matches!(instruction.name, "jmp" | "callr" | "ret" | "eret" | "bret" | "trap" | "break")
+}
+
+fn written_register(decoded: &DecodedWord, instruction: &Instruction) -> Option<i32> {
+    let name = instruction.name;
+    match decoded.format {
+        InstructionFormat::REGISTER => match name {
+            "wrctl" | "wrprs" | "flushi" | "initi" | "flushp" | "sync" => None,
+            _ => Some(decoded.c),
+        },
+        InstructionFormat::IMMEDIATE if name.starts_with("st") || name.starts_with("flush") || name.starts_with("init") => None,
+        InstructionFormat::IMMEDIATE => Some(decoded.b),
+        _ => None,
+    }
+}
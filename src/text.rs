@@ -1,11 +1,19 @@
 use regex::Regex;
 use std::str;
-use crate::constants::{INSTRUCTION_TABLE, ZERO_REGISTER, AT_REGISTER, SP_REGISTER, RA_REGISTER};
+use std::sync::OnceLock;
+use crate::constants::{BA_REGISTER, CONTROL_REGISTER_NAMES, EA_REGISTER, INSTRUCTION_TABLE, RA_REGISTER, REGISTER_NAMES};
 use crate::datum::{find_datum, Datum};
+use crate::diagnostic::Diagnostic;
+use crate::expression::evaluate;
+use crate::image::AddressUnit;
 use crate::instruction::{convert_opcode_to_format, Instruction, InstructionFormat};
 use crate::label::{find_label, Label};
+use crate::relocation::{RelocationKind, SymbolReference};
+use crate::source::SourceLocation;
+use crate::symbol::SymbolTable;
 use crate::utils::{convert_int_to_binary, convert_string_to_hex, convert_string_to_int, get_address_difference};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone)]
 enum ArgumentType {
     NUMBER,
@@ -23,9 +31,12 @@ pub struct Text {
     opcode: i32,
     immediate: i32,
     address: i32,
+    pub reference: Option<SymbolReference>,
+    pub location: Option<SourceLocation>,
 }
 
 impl Text {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         ra: i32,
         rb: i32,
@@ -45,6 +56,8 @@ impl Text {
             opcode,
             immediate,
             address,
+            reference: None,
+            location: None,
         }
     }
 
@@ -76,128 +89,252 @@ impl Text {
         }
     }
 
+    // Splits an arbitrary word into the fields of its format, so that `to_word` gives it back.
+    pub fn from_word(word: u32, address: i32) -> Self {
+        let field = |shift: u32, mask: u32| ((word >> shift) & mask) as i32;
+        let (a, b, c) = (field(27, 0x1F), field(22, 0x1F), field(17, 0x1F));
+        let opcode = field(0, 0x3F);
+        match convert_opcode_to_format(opcode) {
+            InstructionFormat::REGISTER => Self::new(c, a, b, field(6, 0x1F), field(11, 0x3F), opcode, 0, address),
+            InstructionFormat::JUMP => Self::new(0, 0, 0, 0, 0, opcode, 0, field(6, 0x3FFFFFF)),
+            _ => Self::new(b, a, 0, 0, 0, opcode, field(6, 0xFFFF), address),
+        }
+    }
+
     pub fn to_hex(&self) -> String {
-        return convert_string_to_hex(&self.to_binary(), 4);
+        convert_string_to_hex(&self.to_binary(), 4)
+    }
+
+    pub fn to_word(&self) -> u32 {
+        u32::from_str_radix(&self.to_binary(), 2).unwrap()
     }
 }
 
+// Encodes one instruction of the source, errors being reported at `location`.
 pub fn get_text_from_code(
     text: &str,
     current_address: i32,
     data: &[Datum],
     labels: &[Label],
-) -> Text {
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+    location: Option<&SourceLocation>,
+) -> Result<Text, Diagnostic> {
+    let mut text = encode(text, current_address, data, labels, symbols, unit)
+        .map_err(|message| Diagnostic::error(&message, location))?;
+    text.location = location.cloned();
+    Ok(text)
+}
+
+fn encode(
+    text: &str,
+    current_address: i32,
+    data: &[Datum],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+) -> Result<Text, String> {
     let contents = text.trim_start().split('\t').collect::<Vec<&str>>();
-    if contents.len() > 0 {
-        let name = contents[0];
-        let instruction = INSTRUCTION_TABLE.get(name).unwrap_or_else(|| panic!("Unknown instruction {}.", name));
-
-        // has any arguments
-        if contents.len() > 1 {
-            let argument_texts = contents[1]
-                .split(',')
-                .map(|arg| arg.trim())
-                .collect::<Vec<&str>>();
-
-            let arguments = resolve_arguments(&argument_texts, &data, &labels);
-            get_text_by_format(&instruction, &arguments, current_address)
-        } else {
-            let arguments = [];
-            get_text_by_format(&instruction, &arguments, current_address)
-        }
+    let name = contents[0];
+    // a raw word, for words that are not instructions or not in the form the encoder writes them
+    if name == ".word" && contents.len() > 1 {
+        let value = evaluate(contents[1].trim(), &|symbol| symbols.get(symbol))?;
+        return Ok(Text::from_word(value as u32, current_address));
+    }
+    let instruction = INSTRUCTION_TABLE.get(name).ok_or_else(|| format!("unknown instruction `{}`", name))?;
+
+    // has any arguments
+    if contents.len() > 1 {
+        let argument_texts = contents[1]
+            .split(',')
+            .map(|arg| arg.trim())
+            .collect::<Vec<&str>>();
 
+        let (arguments, operand) = resolve_arguments(&argument_texts, data, labels, symbols, unit)?;
+        let mut text = get_text_by_format(instruction, &arguments, current_address, unit)?;
+        text.reference = operand.and_then(|(symbol, kind)| resolve_reference(instruction, symbol, kind, labels));
+        Ok(text)
     } else {
-        panic!("Invalid instruction {}.", text);
+        let arguments = [];
+        get_text_by_format(instruction, &arguments, current_address, unit)
     }
 }
 
-fn get_text_by_format(instruction: &Instruction, arguments: &[i32], current_address: i32) -> Text {
-    let first_arg = *arguments.get(0).unwrap_or(&0);
+// Address operands are in `unit`, `current_address` in assembler address units.
+fn get_text_by_format(
+    instruction: &Instruction,
+    arguments: &[i32],
+    current_address: i32,
+    unit: AddressUnit,
+) -> Result<Text, String> {
+    let first_arg = *arguments.first().unwrap_or(&0);
     let second_arg = *arguments.get(1).unwrap_or(&0);
     let third_arg = *arguments.get(2).unwrap_or(&0);
 
-    match convert_opcode_to_format(instruction.opcode) {
+    Ok(match convert_opcode_to_format(instruction.opcode) {
         InstructionFormat::REGISTER => {
+            // the registers implied by `ret`, `eret`, `bret`, `trap` and `break` are encoded as on a Nios II
             if instruction.is_register_jump() {
-                instruction.to_register_format_text(first_arg, 0, 0, 0)
+                instruction.to_register_format_text(0, RA_REGISTER, 0, 0)
+            } else if instruction.name == "eret" {
+                instruction.to_register_format_text(0, EA_REGISTER, BA_REGISTER, 0)
+            } else if instruction.name == "bret" {
+                instruction.to_register_format_text(0, BA_REGISTER, 0, 0)
+            } else if instruction.is_register_operand_only() {
+                // `callr` also writes the return address
+                let link = if instruction.name == "callr" { RA_REGISTER } else { 0 };
+                instruction.to_register_format_text(link, first_arg, 0, 0)
+            } else if instruction.is_immediate_shift() {
+                instruction.to_register_format_text(first_arg, second_arg, 0, third_arg)
+            } else if instruction.name == "trap" {
+                instruction.to_register_format_text(EA_REGISTER, 0, 0, first_arg)
+            } else if instruction.name == "break" {
+                instruction.to_register_format_text(BA_REGISTER, 0, 0, first_arg)
+            } else if instruction.name == "rdctl" {
+                instruction.to_register_format_text(first_arg, 0, 0, second_arg)
+            } else if instruction.name == "wrctl" {
+                instruction.to_register_format_text(0, second_arg, 0, first_arg)
             } else {
                 instruction.to_register_format_text(first_arg, second_arg, third_arg, 0)
             }
         }
-        InstructionFormat::JUMP => instruction.to_jump_format_text(first_arg),
+        // the 26-bit field holds a word address
+        InstructionFormat::JUMP => instruction.to_jump_format_text((first_arg as u32 / unit.per_word() as u32) as i32),
         InstructionFormat::IMMEDIATE => {
             if instruction.is_conditional_branch() {
-                let difference = get_address_difference(current_address, third_arg);
+                let difference = get_address_difference(current_address, third_arg, unit);
                 instruction.to_immediate_format_text(second_arg, first_arg, difference)
             } else if instruction.is_relative_branch() {
-                let difference = get_address_difference(current_address, first_arg);
+                let difference = get_address_difference(current_address, first_arg, unit);
                 instruction.to_immediate_format_text(0, 0, difference)
             } else if arguments.len() < 3 {
-                instruction.to_immediate_format_text(0, second_arg, first_arg)
+                // `flushd`, `flushda`, `initd` and `initda` only take a `offset(base)` operand
+                instruction.to_immediate_format_text(0, first_arg, second_arg)
             } else {
                 instruction.to_immediate_format_text(first_arg, second_arg, third_arg)
             }
         }
-        InstructionFormat::PSEUDO => panic!("A pseudo instruction found."),
-    }
+        InstructionFormat::PSEUDO => return Err(format!("`{}` cannot take these operands", instruction.name)),
+    })
 }
 
-fn resolve_arguments(argument_codes: &[&str], data: &[Datum], labels: &[Label]) -> Vec<i32> {
-    argument_codes
-        .iter()
-        .flat_map(|argument_text| match resolve_argument_type(argument_text) {
-            ArgumentType::NUMBER => vec![convert_string_to_int(argument_text)],
-            ArgumentType::REGISTER => {
-                let reg_name = &argument_text[0..argument_text.len()];
-                match reg_name {
-                    "zero" => vec![ZERO_REGISTER],
-                    "sp" => vec![SP_REGISTER],
-                    "ra" => vec![RA_REGISTER],
-                    "at" => vec![AT_REGISTER],
-                    _ => vec![convert_string_to_int(&argument_text[1..argument_text.len()])]
-                }
-            }
-            ArgumentType::LABEL => {
-                if let Some(datum) = find_datum(argument_text, &data) {
-                    vec![datum.address]
-                } else if let Some(label) = find_label(argument_text, &labels) {
-                    vec![label.address]
-                } else {
-                    panic!("Failed to resolve argument value.");
+// Branches to local labels are resolved here, every other use of an address is left to the linker.
+fn resolve_reference(
+    instruction: &Instruction,
+    symbol: String,
+    kind: Option<RelocationKind>,
+    labels: &[Label],
+) -> Option<SymbolReference> {
+    let kind = match kind {
+        Some(kind) => kind,
+        None => match convert_opcode_to_format(instruction.opcode) {
+            InstructionFormat::JUMP => RelocationKind::CALL26,
+            InstructionFormat::IMMEDIATE if instruction.is_conditional_branch() || instruction.is_relative_branch() => {
+                if find_label(&symbol, labels).is_some() {
+                    return None;
                 }
+                RelocationKind::PCREL16
             }
-            ArgumentType::STACK => {
-                if let [offset, base] = argument_text.split('(').collect::<Vec<&str>>()[..] {
-                    let reg_name = &base[0..(base.len() - 1)];
-                    let offset = convert_string_to_int(offset);
-
-                    match reg_name {
-                        "zero" => vec![ZERO_REGISTER, offset],
-                        "sp" => vec![SP_REGISTER, offset],
-                        "ra" => vec![RA_REGISTER, offset],
-                        "at" => vec![AT_REGISTER, offset],
-                        _ => vec![convert_string_to_int(&base[1..(base.len() - 1)]), offset]
-                    }
+            InstructionFormat::IMMEDIATE => RelocationKind::S16,
+            _ => return None,
+        },
+    };
+    Some(SymbolReference { kind, symbol })
+}
+
+type SymbolOperand = (String, Option<RelocationKind>);
+
+fn resolve_arguments(
+    argument_codes: &[&str],
+    data: &[Datum],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+) -> Result<(Vec<i32>, Option<SymbolOperand>), String> {
+    let mut operand = None;
+    let arguments = argument_codes
+        .iter()
+        .map(|argument_text| match resolve_argument_type(argument_text) {
+            Some(ArgumentType::NUMBER) => Ok(vec![parse_number(argument_text)?]),
+            Some(ArgumentType::REGISTER) => Ok(vec![register_number(argument_text)?]),
+            Some(ArgumentType::LABEL) => Ok(vec![resolve_symbol(argument_text, data, labels, symbols, unit, &mut operand)?]),
+            Some(ArgumentType::STACK) => {
+                let (offset, base) = argument_text.rsplit_once('(').unwrap();
+                let reg_name = &base[0..(base.len() - 1)];
+                let offset = if offset.starts_with('%') {
+                    resolve_symbol(offset, data, labels, symbols, unit, &mut operand)?
                 } else {
-                    panic!("Failed to resolve argument value.");
-                }
+                    parse_number(offset)?
+                };
+                Ok(vec![register_number(reg_name)?, offset])
             }
+            None => Err(format!("invalid operand `{}`", argument_text)),
         })
-        .collect()
+        .collect::<Result<Vec<Vec<i32>>, String>>()?;
+    Ok((arguments.concat(), operand))
+}
+
+// Resolves `symbol` or `%modifier(symbol)`, addresses being given in `unit`, and records the
+// address the instruction depends on. An undefined symbol resolves to zero and is left for the linker.
+fn resolve_symbol(
+    text: &str,
+    data: &[Datum],
+    labels: &[Label],
+    symbols: &SymbolTable,
+    unit: AddressUnit,
+    operand: &mut Option<SymbolOperand>,
+) -> Result<i32, String> {
+    let (kind, name) = match text.strip_suffix(')').and_then(|text| text.split_once('(')) {
+        Some((modifier, name)) => {
+            let kind = RelocationKind::from_modifier(modifier).ok_or_else(|| format!("unknown operator `{}`", modifier))?;
+            (Some(kind), name.trim())
+        }
+        None => (None, text),
+    };
+
+    let value = if let Some(datum) = find_datum(name, data) {
+        *operand = Some((name.to_string(), kind));
+        unit.from_words(datum.address)
+    } else if let Some(label) = find_label(name, labels) {
+        *operand = Some((name.to_string(), kind));
+        unit.from_words(label.address)
+    } else if let Some(value) = symbols.get(name) {
+        value
+    } else {
+        *operand = Some((name.to_string(), kind));
+        0
+    };
+    Ok(kind.map_or(value, |kind| kind.select_bits(value)))
+}
+
+// `rN`, `ctlN` or one of the names of `REGISTER_NAMES` and `CONTROL_REGISTER_NAMES`.
+fn register_number(name: &str) -> Result<i32, String> {
+    match REGISTER_NAMES.iter().chain(CONTROL_REGISTER_NAMES.iter()).find(|(register, _)| *register == name) {
+        Some((_, number)) => Ok(*number),
+        None => convert_string_to_int(name.trim_start_matches("ctl").trim_start_matches('r'))
+            .ok_or_else(|| format!("invalid register `{}`", name)),
+    }
+}
+
+fn parse_number(text: &str) -> Result<i32, String> {
+    convert_string_to_int(text).ok_or_else(|| format!("invalid number `{}`", text))
 }
 
-fn resolve_argument_type(text: &str) -> ArgumentType {
-    let arguments = [
-        (Regex::new(r"^-?\d+\(r\d+\)|\(sp\)|\(zero\)|\(ra\)|\(at\)").unwrap(), ArgumentType::STACK),
-        (Regex::new(r"^(r\d+)|(sp)|(zero)|(ra)|(at)").unwrap(), ArgumentType::REGISTER),
-        (Regex::new(r"^[\.A-Za-z%d]\w*").unwrap(), ArgumentType::LABEL),
-        (Regex::new(r"^-?(0x)?\d+").unwrap(), ArgumentType::NUMBER),
-    ];
+fn resolve_argument_type(text: &str) -> Option<ArgumentType> {
+    static ARGUMENTS: OnceLock<Vec<(Regex, ArgumentType)>> = OnceLock::new();
+    let arguments = ARGUMENTS.get_or_init(|| {
+        let registers = "r\\d+|zero|at|et|bt|gp|sp|ea|ba|ra";
+        let control_registers = "ctl\\d+|status|estatus|bstatus|ienable|ipending|cpuid|exception";
+        vec![
+            (Regex::new(&format!(r"^(-?\d+|%\w+\([\w.$]+\))\(({})\)$", registers)).unwrap(), ArgumentType::STACK),
+            (Regex::new(&format!("^({}|{})$", registers, control_registers)).unwrap(), ArgumentType::REGISTER),
+            (Regex::new(r"^[\.A-Za-z_%]\w*").unwrap(), ArgumentType::LABEL),
+            (Regex::new(r"^-?(0x)?\d+").unwrap(), ArgumentType::NUMBER),
+        ]
+    });
 
     arguments
         .iter()
         .find(|arg| arg.0.is_match(text))
-        .expect("Failed to resolve argument.")
-        .clone()
-        .1
+        .map(|arg| arg.1.clone())
 }
@@ -3,11 +3,12 @@ use std::io::{BufRead, BufReader, Lines, Seek, SeekFrom};
 use std::iter::Map;
 
 use crate::constants::WORD_SIZE;
+use crate::image::AddressUnit;
 use std::ops::Neg;
 
 type LinesMap<'a> = Map<Lines<BufReader<&'a mut File>>, fn(std::io::Result<String>) -> String>;
 
-pub fn read_lines(file: &mut File) -> LinesMap {
+pub fn read_lines(file: &mut File) -> LinesMap<'_> {
     if file.seek(SeekFrom::Start(0)).is_ok() {
         BufReader::new(file).lines().map(|line| line.unwrap())
     } else {
@@ -15,15 +16,13 @@ pub fn read_lines(file: &mut File) -> LinesMap {
     }
 }
 
-pub fn convert_string_to_int(code: &str) -> i32 {
+pub fn convert_string_to_int(code: &str) -> Option<i32> {
     if code.starts_with("0x") {
-        i32::from_str_radix(code.trim_start_matches("0x"), 16).unwrap()
+        i32::from_str_radix(code.trim_start_matches("0x"), 16).ok()
     } else if code.starts_with('-') {
-        i32::from_str_radix(code.trim_start_matches('-'), 10)
-            .unwrap()
-            .neg()
+        code.trim_start_matches('-').parse::<i32>().ok().map(i32::neg)
     } else {
-        i32::from_str_radix(code, 10).unwrap()
+        code.parse::<i32>().ok()
     }
 }
 
@@ -32,11 +31,56 @@ pub fn convert_string_to_hex(val: &str, len: usize) -> String {
     format!("{:01$x}", n, len * 2)
 }
 
-pub fn get_address_difference(current_address: i32, target_address: i32) -> i32 {
-    (target_address - current_address) / WORD_SIZE - 1
+// Branch offsets count from the instruction after the branch, `target_address` is in `unit`.
+pub fn get_address_difference(current_address: i32, target_address: i32, unit: AddressUnit) -> i32 {
+    target_address.wrapping_sub(unit.from_words(current_address + WORD_SIZE))
 }
 
 pub fn convert_int_to_binary(number: i32, bit: i32) -> String {
     let binary = format!("{:032b}", number);
     binary[(binary.len() - (bit as usize))..].to_string()
 }
+
+pub fn split_statement(code: &str) -> (Option<&str>, &str, &str) {
+    let code = code.trim();
+    let (label, code) = match code.find(|c: char| c.is_whitespace()) {
+        Some(index) if code[..index].ends_with(':') => (Some(&code[..index - 1]), code[index..].trim_start()),
+        None if code.ends_with(':') => (Some(&code[..code.len() - 1]), ""),
+        _ => (None, code),
+    };
+    match code.find(|c: char| c.is_whitespace()) {
+        Some(index) => (label, &code[..index], code[index..].trim()),
+        None => (label, code, ""),
+    }
+}
+
+pub fn split_arguments(code: &str) -> Vec<String> {
+    let mut arguments = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+
+    for char in code.chars() {
+        match char {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                arguments.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(char);
+    }
+    if !current.trim().is_empty() || !arguments.is_empty() {
+        arguments.push(current.trim().to_string());
+    }
+    arguments
+}
+
+pub fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(1);
+}
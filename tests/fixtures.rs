@@ -28,4 +28,810 @@ loop:
 	br	loop
 ";
 
-pub const OUTPUT_CASE_1: &str = "v2.0 raw\n0*0 07008004 01000104 00000100 00000346 e73ffe04 e7c00115 2000009e 00800044 00000146 e1000015 213fffc4 00000100 e1000017 2085383a e7c00117 e7000204 0000283a 003fffc6 \n238*0 0000000a ffffffec";
+pub const OUTPUT_CASE_1: &str = "v2.0 raw\n0*0 07008004 01000104 00000100 00000346 e73ffe04 e7c00115 2000009e 00800044 00000146 e1000015 213fffc4 00000100 e1000017 2085383a e7c00117 e7000204 f800283a 003fffc6 \n238*0 0000000a ffffffec";
+
+pub const INPUT_MACRO: &str = "
+	.macro	SAVE	reg:req, offset=0
+	stw	\\reg, \\offset(sp)
+	.endm
+	.macro	SPIN	label, rest:vararg
+\\label\\@:
+	add	\\rest
+	br	\\label\\@
+	.exitm
+	br	\\label\\@
+	.endm
+	.text
+main:
+	SAVE	ra, 4
+	SAVE	r4
+	SAVE	offset=8, reg=r5
+	SPIN	.Lspin, r2, r3, r4
+	SPIN	.Lspin, r5, r6, r7
+";
+
+pub const INPUT_MACRO_EXPANDED: &str = "
+	.text
+main:
+	stw	ra, 4(sp)
+	stw	r4, 0(sp)
+	stw	r5, 8(sp)
+.Lspin0:
+	add	r2, r3, r4
+	br	.Lspin0
+.Lspin1:
+	add	r5, r6, r7
+	br	.Lspin1
+";
+
+pub const INPUT_MACRO_RECURSIVE: &str = "
+	.macro	SPIN
+	SPIN
+	.endm
+	.text
+main:
+	SPIN
+";
+
+pub const INPUT_REPETITION: &str = "
+	.macro	PUSH	regs:vararg
+	.irp	reg, \\regs
+	addi	sp, sp, -4
+	stw	\\reg, 0(sp)
+	.endr
+	.endm
+	.data
+table:	.word	sevens
+sevens:	.rept	3
+	.word	7
+	.endr
+	.text
+main:
+	PUSH	r16, r17, ra
+count:	.irpc	n, 123
+	addi	r\\n, r0, \\n
+	.endr
+	br	count
+";
+
+pub const INPUT_REPETITION_EXPANDED: &str = "
+	.data
+table:	.word	sevens
+sevens:	.word	7
+	.word	7
+	.word	7
+	.text
+main:
+	addi	sp, sp, -4
+	stw	r16, 0(sp)
+	addi	sp, sp, -4
+	stw	r17, 0(sp)
+	addi	sp, sp, -4
+	stw	ra, 0(sp)
+count:
+	addi	r1, r0, 1
+	addi	r2, r0, 2
+	addi	r3, r0, 3
+	br	count
+";
+
+pub const INPUT_REPETITION_ERROR: &str = "
+	.macro	SAVE	reg:req
+	stw	\\reg, 0(sp)
+	.endm
+	.text
+main:
+	.rept	2
+	SAVE
+	.endr
+";
+
+pub const INPUT_CONDITIONAL: &str = "
+	.equ	STACK_TOP, 0x200
+	.ifdef	DE1_SOC
+	.set	STACK_TOP, STACK_TOP * 2
+	.endif
+	.text
+main:
+	addi	sp, r0, STACK_TOP
+	.if	STACK_TOP > 0x300
+	addi	r4, r0, 1
+	.elseif	STACK_TOP == 0x200
+	addi	r4, r0, 2
+	.else
+	.error	\"unreachable\"
+	.endif
+	.ifndef	main
+	.warning	\"main is not defined\"
+	.endif
+";
+
+pub const INPUT_CONDITIONAL_LOGISIM: &str = "
+	.text
+main:
+	addi	sp, r0, 0x200
+	addi	r4, r0, 2
+";
+
+pub const INPUT_CONDITIONAL_DE1_SOC: &str = "
+	.text
+main:
+	addi	sp, r0, 0x400
+	addi	r4, r0, 1
+";
+
+pub const INPUT_CONDITIONAL_ERROR: &str = "
+	.text
+main:
+	.ifdef	SIMULATION
+	.error	\"simulation builds are not supported\"
+	.endif
+";
+
+pub const INPUT_INCLUDE_MAIN: &str = "
+	.include	\"macros.s\"
+	.include	\"constants.s\"
+	.data
+blob:	.incbin	\"blob.bin\", 1, 5
+	.text
+main:
+	addi	sp, r0, STACK_TOP
+	PUSH	ra
+";
+
+pub const INPUT_INCLUDE_MACROS: &str = "
+	.macro	PUSH	reg
+	addi	sp, sp, -4
+	stw	\\reg, 0(sp)
+	.endm
+";
+
+pub const INPUT_INCLUDE_CONSTANTS: &str = "
+	.equ	STACK_TOP, 0x200
+";
+
+pub const INPUT_INCLUDE_BINARY: &[u8] = &[0xff, 0x01, 0x02, 0x03, 0x04, 0x05, 0xff];
+
+pub const INPUT_INCLUDE_EXPANDED: &str = "
+	.data
+blob:	.word	0x04030201
+	.word	0x00000005
+	.text
+main:
+	addi	sp, r0, 0x200
+	addi	sp, sp, -4
+	stw	ra, 0(sp)
+";
+
+pub const INPUT_INCLUDE_CYCLE: &str = "
+	.text
+	.include	\"cycle.s\"
+";
+
+pub const INPUT_INCLUDE_ERROR: &str = "
+	.error	\"included error\"
+";
+
+pub const INPUT_CPP_MAIN: &str = "#include \"board.h\"
+	.text
+main:
+#if defined(DE1_SOC) && STACK_TOP > 0x100
+	addi	sp, r0, STACK_TOP
+#else
+	addi	sp, r0, 0x100
+#endif
+	LOAD(r4, COUNT)	/* comment */
+# this line is an assembler comment
+#line 100 \"original.s\"
+	LOAD(r5, \\
+	COUNT)
+	.error	\"mapped\"
+";
+
+pub const INPUT_CPP_HEADER: &str = "#ifndef BOARD_H
+#define BOARD_H
+#define DE1_SOC
+#define STACK_TOP 0x200
+#define COUNT 5
+#define LOAD(reg, value) addi	reg, r0, value
+#endif
+";
+
+pub const INPUT_OUTPUT_FORMATS: &str = "
+	.data
+value:	.word	0x11223344
+	.text
+main:
+	addi	sp, r0, 0x200
+	br	main
+";
+
+pub const OUTPUT_INTEL_HEX: &str = ":020000040000FA
+:0800000007008004003FFF86A9
+:040100001122334451
+:00000001FF
+";
+
+pub const OUTPUT_INTEL_HEX_BYTES: &str = ":020000040000FA
+:040000000480000771
+:0400040086FF3F0034
+:04040000443322114E
+:00000001FF
+";
+
+pub const OUTPUT_MIF: &str = "-- Generated by nios2asm
+WIDTH=32;
+DEPTH=512;
+
+ADDRESS_RADIX=HEX;
+DATA_RADIX=HEX;
+
+CONTENT BEGIN
+	000 : 07008004; -- addi sp, zero, 512
+	001 : 003FFF86; -- br 0x0
+	[002..0FF] : 0;
+	100 : 11223344;
+	[101..1FF] : 0;
+END;
+";
+
+pub const OUTPUT_MIF_HALFWORDS: &str = "-- Generated by nios2asm
+WIDTH=16;
+DEPTH=768;
+
+ADDRESS_RADIX=HEX;
+DATA_RADIX=HEX;
+
+CONTENT BEGIN
+	000 : 8004;
+	001 : 0700;
+	002 : FF86;
+	003 : 003F;
+	[004..1FF] : 0;
+	200 : 3344;
+	201 : 1122;
+	[202..2FF] : 0;
+END;
+";
+
+pub const OUTPUT_READMEMH: &str = "// Generated by nios2asm
+@00000000
+07008004
+003fff86
+@00000100
+11223344
+";
+
+pub const OUTPUT_READMEMB: &str = "// Generated by nios2asm
+@00000000
+00000111000000001000000000000100
+00000000001111111111111110000110
+@00000100
+00010001001000100011001101000100
+";
+
+pub const OUTPUT_VHDL_PACKAGE: &str = "-- Generated by nios2asm
+library ieee;
+use ieee.std_logic_1164.all;
+
+package program_rom is
+    constant ROM_DEPTH : natural := 512;
+    constant ROM_WIDTH : natural := 32;
+
+    type rom_t is array (0 to ROM_DEPTH - 1) of std_logic_vector(ROM_WIDTH - 1 downto 0);
+
+    constant ROM : rom_t := (
+        0 => x\"07008004\",
+        1 => x\"003FFF86\",
+        256 => x\"11223344\",
+        others => (others => '0')
+    );
+end package program_rom;
+";
+
+pub const OUTPUT_SREC: &str = "S00B00006E696F733261736DC8
+S10B00000480000706FE3F0026
+S1070400443322114A
+S9030400F8
+";
+
+pub const INPUT_ELF_OBJECT: &str = "
+	.global	main, table
+	.weak	hook
+	.data
+counter:	.word	0
+table:	.word	main
+	.word	helper
+	.section	.rodata
+message:	.word	0x6c6c6568
+	.bss
+buffer:	.space	16
+	.text
+main:
+	xorhi	r2, r0, %hiadj(counter)
+	addi	r2, r2, %lo(counter)
+	ldw	r3, %lo(message)(r2)
+	addi	r4, r0, buffer
+	call	helper
+	call	hook
+	beq	r2, r0, done
+	br	main
+done:
+	ret
+";
+
+pub const INPUT_ELF_EXECUTABLE: &str = "
+	.global	_start
+	.data
+counter:	.word	5
+	.bss
+buffer:	.space	8
+	.text
+	nop
+_start:
+	addi	r2, r0, counter
+	call	work
+	br	_start
+work:
+	ldw	r3, 0(r2)
+	ret
+";
+
+pub const INPUT_LINK_MAIN: &str = "
+	.global	_start
+	.data
+count:	.word	3
+	.text
+_start:
+	xorhi	r4, r0, %hiadj(banner)
+	addi	r4, r4, %lo(banner)
+	call	uart_puts
+	ldw	r5, %lo(count)(r0)
+	beq	r5, r0, done
+	br	_start
+done:
+	br	done
+";
+
+pub const INPUT_LINK_UART: &str = "
+	.global	uart_puts
+	.global	banner
+	.data
+status:	.word	0
+handler:	.word	uart_puts
+	.rodata
+banner:	.word	0x6c6c6548
+	.text
+uart_puts:
+	ldw	r2, 0(r4)
+	stw	r2, %lo(status)(r0)
+	ret
+";
+
+pub const INPUT_MULTI_FIRST: &str = "
+	.text
+main:
+	addi	r4, r0, 3
+.L1:
+	addi	r4, r4, -1
+	bne	r4, r0, .L1
+	call	count
+	br	main
+	.data
+first:	.word	.L1
+";
+
+pub const INPUT_MULTI_SECOND: &str = "count:
+	addi	r2, r0, 0
+.L1:
+	addi	r2, r2, 1
+	blt	r2, r4, .L1
+	ret
+	.data
+second:	.word	.L1
+";
+
+pub const INPUT_DISASSEMBLY: &str = "
+	.text
+main:
+	addi	r2, r0, -5
+	ori	r3, r2, 0xff00
+	orhi	r4, r0, 0x1234
+	andi	r5, r4, 0xf
+	muli	r6, r5, 7
+	cmplti	r7, r6, -3
+	cmpeqi	r8, r7, 1
+	slli	r9, r8, 4
+	srai	r10, r9, 2
+	roli	r11, r10, 31
+	rdctl	r12, status
+	wrctl	ienable, r12
+	rdctl	r13, ctl6
+	ldw	r2, -8(sp)
+	stb	r3, 3(gp)
+	ldbuio	r4, 0(ea)
+	flushd	12(r5)
+	initd	0(r6)
+	bgeu	r2, r3, main
+	bltu	r2, r3, end
+	beq	r2, r3, main
+	call	end
+	jmp	r7
+	callr	r8
+	nextpc	r9
+	trap	3
+	break
+	eret
+	sub	r2, r3, r4
+	mul	r5, et, bt
+end:
+	ret
+";
+
+pub const OUTPUT_DISASSEMBLY: &str = "00000000:	00bffec4	addi	r2, zero, -5
+00000001:	10ffc014	ori	r3, r2, 0xff00
+00000002:	01048d34	orhi	r4, zero, 0x1234
+00000003:	214003cc	andi	r5, r4, 0xf
+00000004:	298001e4	muli	r6, r5, 7
+00000005:	31ffff50	cmplti	r7, r6, -3
+00000006:	3a000060	cmpeqi	r8, r7, 1
+00000007:	4012913a	slli	r9, r8, 4
+00000008:	4815d0ba	srai	r10, r9, 2
+00000009:	501617fa	roli	r11, r10, 31
+0000000a:	0019303a	rdctl	r12, status
+0000000b:	600170fa	wrctl	ienable, r12
+0000000c:	001b31ba	rdctl	r13, ctl6
+0000000d:	e0bffe17	ldw	r2, -8(sp)
+0000000e:	d0c000c5	stb	r3, 3(gp)
+0000000f:	e9000023	ldbuio	r4, 0(ea)
+00000010:	2800033b	flushd	12(r5)
+00000011:	30000033	initd	0(r6)
+00000012:	10fffb6e	bgeu	r2, r3, 0x0
+00000013:	10c002b6	bltu	r2, r3, 0x1e
+00000014:	10fffae6	beq	r2, r3, 0x0
+00000015:	00000780	call	0x1e
+00000016:	3800683a	jmp	r7
+00000017:	403ee83a	callr	r8
+00000018:	0012e03a	nextpc	r9
+00000019:	003b68fa	trap	3
+0000001a:	003da03a	break
+0000001b:	ef80083a	eret
+0000001c:	1905c83a	sub	r2, r3, r4
+0000001d:	c64b383a	mul	r5, et, bt
+0000001e:	f800283a	ret
+";
+
+pub const INPUT_SYMBOLIC: &str = "
+	.global	main
+	.text
+main:
+	orhi	r2, zero, %hiadj(table)
+	ldw	r3, %lo(table)(r2)
+	addi	r2, r2, %lo(count)
+	orhi	r4, zero, %hi(message)
+	ori	r4, r4, %lo(message)
+	call	print
+	jmpi	main
+print:
+	ldw	r5, 0(r4)
+	beq	r5, zero, .L1
+	addi	r4, r4, 1
+	br	print
+.L1:
+	ret
+	.data
+table:	.word	1
+	.word	2
+count:	.word	3
+	.rodata
+message:	.word	0x6c6c6568
+";
+
+pub const SYMBOLS_SYMBOLIC: &str = "00000000 T main
+00000100 D table
+# addresses outside of the image become constants
+00000102 d count
+ffff0000 A UART
+";
+
+pub const OUTPUT_SYMBOLIC: &str = "# assemble with --text-base 0x0 --data-base 0x100
+	.global	main
+	.global	table
+	.equ	UART, 0xffff0000
+	.text
+main:
+	orhi	r2, zero, %hiadj(table)
+	ldw	r3, %lo(table)(r2)
+	addi	r2, r2, %lo(count)
+	orhi	r4, zero, %hi(data_103)
+	ori	r4, r4, %lo(data_103)
+	call	func_7
+	jmpi	main
+func_7:
+	ldw	r5, 0(r4)
+	beq	r5, zero, .Lb
+	addi	r4, r4, 1
+	br	func_7
+.Lb:
+	ret
+# end of func_7
+	.data
+table:	.word	0x00000001
+	.word	0x00000002
+count:	.word	0x00000003
+	.rodata
+data_103:	.word	0x6c6c6568
+";
+
+pub const OUTPUT_EXPLAIN: &str = "addi\tr4, zero, 10
+format: I
+binary: 00000 00100 0000000000001010 000100
+field  bits   binary                        decimal  hex        meaning
+A      31..27 00000                               0  0x0        zero
+B      26..22 00100                               4  0x4        r4
+IMM16  21..6  0000000000001010                   10  0xa
+OP     5..0   000100                              4  0x4        addi
+hex: 01000284
+";
+
+pub const INPUT_LISTING_INCLUDE: &str = "\t.equ\tSTACK, 0x2000
+\t.macro\tPUSH reg
+\taddi\tsp, sp, -4
+\tstw\t\\reg, 0(sp)
+\t.endm
+";
+
+pub const INPUT_LISTING: &str = "\t.include \"defs.inc\"
+\t.global\tmain
+\t.data
+count:\t.word\t3
+buffer:\t.space\t6
+\t.text
+main:
+\taddi\tsp, r0, STACK
+\tPUSH\tr4
+\tnop
+\tldw\tr2, %lo(count)(r0)
+\tcall\thelper
+\tbr\tmain
+helper:
+\tret
+";
+
+pub const OUTPUT_LISTING: &str = "==== main.s
+    1                        \t.include \"defs.inc\"
+==== defs.inc (included from main.s:1)
+    1 >                      \t.equ\tSTACK, 0x2000
+    2 >                      \t.macro\tPUSH reg
+    3 >                      \taddi\tsp, sp, -4
+    4 >                      \tstw\t\\reg, 0(sp)
+    5 >                      \t.endm
+==== main.s
+    2                        \t.global\tmain
+    3                        \t.data
+    4     00000100 03000000  count:\t.word\t3
+    5     00000101 00000000  buffer:\t.space\t6
+          00000102 00000000
+    6                        \t.text
+    7                        main:
+    8     00000000 07080004  \taddi\tsp, r0, STACK
+    9                        \tPUSH\tr4
+==== defs.inc (included from main.s:1)
+    3 >+  00000001 e73fff04  \taddi\tsp, sp, -4
+    4 >+  00000002 e1000015  \tstw\tr4, 0(sp)
+==== main.s
+   10     00000003 0001883a  \tnop
+   11     00000004 00804017  \tldw\tr2, %lo(count)(r0)
+   12     00000005 000001c0  \tcall\thelper
+   13     00000006 003ffe46  \tbr\tmain
+   14                        helper:
+   15     00000007 f800283a  \tret
+
+Symbols:
+name    value     section  binding defined at       referenced at
+STACK   00002000  *ABS*    local   defs.inc:1       main.s:8
+buffer  00000101  .data    local   main.s:5
+count   00000100  .data    local   main.s:4         main.s:11
+helper  00000007  .text    local   main.s:14        main.s:12
+main    00000000  .text    global  main.s:7         main.s:13
+";
+
+pub const INPUT_MAP: &str = "\t.text
+\t.equ\tLIMIT, 10
+\t.global\tmain
+\t.weak\thelper
+main:
+\tori\tr2, zero, data1
+\tldw\tr3, 0(r2)
+\tcall\thelper
+loop:
+\tbr\tloop
+\t.data
+data1:\t.word\t5
+\t.rodata
+table:\t.word\tLIMIT
+\t.word\t3
+";
+
+pub const OUTPUT_MAP: &str = "Sections:
+name     base     size     end
+.text    00000000 00000004 00000004
+.data    00000100 00000001 00000101
+.rodata  00000101 00000002 00000103
+
+Symbols:
+address  section  kind      binding name   defined at
+00000000 .text    text      global  main   main.s:5
+00000001 *ABS*    constant  local   DEBUG
+00000003 .text    text      local   loop   main.s:9
+0000000a *ABS*    constant  local   LIMIT  main.s:2
+00000100 .data    data      local   data1  main.s:12
+00000101 .rodata  data      local   table  main.s:14
+         *UND*    undefined weak    helper
+";
+
+pub const OUTPUT_MAP_NM: &str = "00000000 T main
+00000001 a DEBUG
+00000003 t loop
+0000000a a LIMIT
+00000100 d data1
+00000101 r table
+         w helper
+";
+
+pub const INPUT_EXPORT: &str = "\t.text
+\t.equ\tUART_BASE, 0x4000
+\t.equ\tBAUD_DIVISOR, 434
+\t.global\tmain, UART_BASE
+\t.export\tbuffer
+main:
+\tcall\tputs
+\tret
+puts:
+\tret
+\t.data
+buffer:\t.word\t0
+count:\t.word\t0
+";
+
+pub const OUTPUT_EXPORT_C: &str = "/* Generated by nios2asm */
+#ifndef SYMBOLS_H
+#define SYMBOLS_H
+
+#define SYM_UART_BASE 0x00004000
+#define SYM_buffer 0x00000400
+#define SYM_main 0x00000000
+
+#endif /* SYMBOLS_H */
+";
+
+pub const OUTPUT_EXPORT_SV: &str = "// Generated by nios2asm
+package symbols_pkg;
+    localparam logic [31:0] SYM_UART_BASE = 32'h00004000;
+    localparam logic [31:0] SYM_buffer = 32'h00000100;
+    localparam logic [31:0] SYM_main = 32'h00000000;
+endpackage : symbols_pkg
+";
+
+pub const OUTPUT_EXPORT_VHDL: &str = "-- Generated by nios2asm
+library ieee;
+use ieee.std_logic_1164.all;
+
+package symbols_pkg is
+    constant SYM_UART_BASE : std_logic_vector(31 downto 0) := x\"00004000\";
+    constant SYM_buffer : std_logic_vector(31 downto 0) := x\"00000100\";
+    constant SYM_main : std_logic_vector(31 downto 0) := x\"00000000\";
+end package symbols_pkg;
+";
+
+pub const INPUT_EMIT: &str = "\t.text
+\t.equ\tLIMIT, 10
+\t.global\tmain
+main:
+\taddi\tr2, zero, LIMIT
+\tldw\tr3, %lo(count)(zero)
+\tnop
+\t.warning \"unfinished\"
+loop:
+\tbr\tloop
+\t.data
+count:\t.word\t5
+\t.word\tmain
+";
+
+pub const OUTPUT_EMIT: &str = "{
+  \"statements\": [
+    {\"address\": 0, \"source\": \"addi\\tr2, zero, LIMIT\", \"mnemonic\": \"addi\", \"operands\": [\"r2\", \"zero\", \"LIMIT\"], \"format\": \"I\", \"word\": 8389252, \"file\": \"main.s\", \"line\": 5, \"column\": 2, \"end_column\": 22},
+    {\"address\": 1, \"source\": \"ldw\\tr3, %lo(count)(zero)\", \"mnemonic\": \"ldw\", \"operands\": [\"r3\", \"%lo(count)(zero)\"], \"format\": \"I\", \"word\": 12599319, \"file\": \"main.s\", \"line\": 6, \"column\": 2, \"end_column\": 26},
+    {\"address\": 2, \"source\": \"nop\", \"mnemonic\": \"add\", \"operands\": [\"r0\", \"r0\", \"r0\"], \"format\": \"R\", \"word\": 100410, \"file\": \"main.s\", \"line\": 7, \"column\": 2, \"end_column\": 5},
+    {\"address\": 3, \"source\": \"br\\tloop\", \"mnemonic\": \"br\", \"operands\": [\"loop\"], \"format\": \"I\", \"word\": 4194246, \"file\": \"main.s\", \"line\": 10, \"column\": 2, \"end_column\": 9}
+  ],
+  \"data\": [
+    {\"name\": \"count\", \"labelled\": true, \"section\": \".data\", \"address\": 256, \"word\": 5, \"symbol\": null, \"file\": \"main.s\", \"line\": 12},
+    {\"name\": \"count_257\", \"labelled\": false, \"section\": \".data\", \"address\": 257, \"word\": 0, \"symbol\": \"main\", \"file\": \"main.s\", \"line\": 13}
+  ],
+  \"sections\": [
+    {\"name\": \".text\", \"base\": 0, \"size\": 4, \"end\": 4},
+    {\"name\": \".data\", \"base\": 256, \"size\": 2, \"end\": 258}
+  ],
+  \"symbols\": [
+    {\"name\": \"main\", \"address\": 0, \"section\": \".text\", \"kind\": \"text\", \"binding\": \"global\", \"file\": \"main.s\", \"line\": 4},
+    {\"name\": \"loop\", \"address\": 3, \"section\": \".text\", \"kind\": \"text\", \"binding\": \"local\", \"file\": \"main.s\", \"line\": 9},
+    {\"name\": \"LIMIT\", \"address\": 10, \"section\": \"*ABS*\", \"kind\": \"constant\", \"binding\": \"local\", \"file\": \"main.s\", \"line\": 2},
+    {\"name\": \"count\", \"address\": 256, \"section\": \".data\", \"kind\": \"data\", \"binding\": \"local\", \"file\": \"main.s\", \"line\": 12}
+  ],
+  \"diagnostics\": [
+    {\"severity\": \"warning\", \"message\": \"unfinished\", \"file\": \"main.s\", \"line\": 8}
+  ]
+}
+";
+
+pub const INPUT_SIMULATOR: &str = "\t.text
+_start:
+\taddi\tr2, r0, 7
+\taddi\tr3, r0, -3
+\tmul\tr4, r2, r3
+\tdiv\tr5, r2, r0
+\tdivu\tr6, r4, r2
+\tmulxss\tr7, r3, r3
+\tmulxuu\tr8, r3, r3
+\tcmplt\tr9, r3, r2
+\tcmpltu\tr10, r3, r2
+\troli\tr11, r2, 31
+\tcall\tsquare
+\tnextpc\tr13
+\tstw\tr4, 256(r0)
+\tldb\tr14, 256(r0)
+\tldhu\tr15, 256(r0)
+\torhi\tr16, r0, 0x8000
+\taddi\tr17, r0, -1
+\tdiv\tr18, r16, r17
+\tbreak
+square:
+\tmul\tr12, r2, r2
+\tret
+";
+
+pub const OUTPUT_SIMULATOR: &str = "break at 0x00000012 after 20 instructions
+zero 00000000    at 00000000    r2 00000007    r3 fffffffd
+  r4 ffffffeb    r5 ffffffff    r6 24924921    r7 00000000
+  r8 fffffffa    r9 00000001   r10 00000000   r11 80000003
+ r12 00000031   r13 0000000c   r14 ffffffeb   r15 0000ffeb
+ r16 80000000   r17 ffffffff   r18 80000000   r19 00000000
+ r20 00000000   r21 00000000   r22 00000000   r23 00000000
+  et 00000000    bt 00000000    gp 00000000   r27 00000000
+  sp 00000000    ea 00000000    ba 00000000    ra 0000000b
+  pc 00000012  status 00000000  estatus 00000000  bstatus 00000000  ienable 00000000  ipending 00000000  exception 00000000
+";
+
+// the handler counts its runs in r21, and returns to the instruction an interrupt stopped
+pub const INPUT_SIMULATOR_EXCEPTIONS: &str = "\t.text
+_start:
+\tbr\tmain
+\tnop
+\tnop
+\tnop
+\tnop
+\tnop
+\tnop
+\tnop
+handler:
+\trdctl\tet, exception
+\trdctl\tr20, ipending
+\taddi\tr21, r21, 1
+\tcmpeqi\tr22, et, 8
+\tbeq\tr22, zero, done
+\taddi\tea, ea, -1
+done:
+\teret
+main:
+\taddi\tr2, zero, 8
+\twrctl\tienable, r2
+\taddi\tr2, zero, 1
+\twrctl\tstatus, r2
+\ttrap
+\tmul\tr3, r2, r2
+wait:
+\tbeq\tr20, zero, wait
+\tbreak
+";
@@ -1,28 +1,908 @@
 use assert_cmd::prelude::*;
+use std::convert::TryInto;
+use std::fs;
 use std::io::{Read, Write};
 use std::process::Command;
-use tempfile::NamedTempFile;
+use tempfile::{tempdir, NamedTempFile};
 
 mod fixtures;
 
 const BIN_NAME: &str = "nios2asm";
 
+fn assemble(input: &str) -> String {
+    assemble_with(input, &[])
+}
+
+fn assemble_with(input: &str, options: &[&str]) -> String {
+    String::from_utf8(assemble_bytes_with(input, options)).unwrap()
+}
+
+fn assemble_bytes_with(input: &str, options: &[&str]) -> Vec<u8> {
+    let mut input_file = NamedTempFile::new().unwrap();
+    input_file.write_all(input.as_bytes()).unwrap();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(options)
+        .args([input_file.path(), output_file.path()])
+        .assert()
+        .success();
+
+    fs::read(output_file.path()).unwrap()
+}
+
+fn assemble_failure(input: &str) -> String {
+    assemble_failure_with(input, &[])
+}
+
+fn assemble_failure_with(input: &str, options: &[&str]) -> String {
+    let mut input_file = NamedTempFile::new().unwrap();
+    input_file.write_all(input.as_bytes()).unwrap();
+
+    let output_file = NamedTempFile::new().unwrap();
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(options)
+        .args([input_file.path(), output_file.path()])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    String::from_utf8(output.stderr).unwrap()
+}
+
 #[test]
 fn test_main_case_1() {
     use fixtures::{INPUT_CASE_1, OUTPUT_CASE_1};
 
+    assert_eq!(assemble(INPUT_CASE_1), OUTPUT_CASE_1);
+
+    // a blank line ends the section, the line after it is not assembled
+    let section = "\t.text\n\taddi\tr2, r0, 1\n";
+    assert_eq!(assemble(&format!("{}\n\taddi\tr3, r0, 2\n", section)), assemble(section));
+}
+
+#[test]
+fn test_macro_expansion() {
+    use fixtures::{INPUT_MACRO, INPUT_MACRO_EXPANDED};
+
+    assert_eq!(assemble(INPUT_MACRO), assemble(INPUT_MACRO_EXPANDED));
+}
+
+#[test]
+fn test_macro_recursion() {
+    use fixtures::INPUT_MACRO_RECURSIVE;
+
+    let stderr = assemble_failure(INPUT_MACRO_RECURSIVE);
+    assert!(stderr.contains(":3: error: macro `SPIN` recursively expands itself"), "{}", stderr);
+}
+
+#[test]
+fn test_repetition_blocks() {
+    use fixtures::{INPUT_REPETITION, INPUT_REPETITION_EXPANDED};
+
+    assert_eq!(assemble(INPUT_REPETITION), assemble(INPUT_REPETITION_EXPANDED));
+}
+
+#[test]
+fn test_repetition_error_location() {
+    use fixtures::INPUT_REPETITION_ERROR;
+
+    let stderr = assemble_failure(INPUT_REPETITION_ERROR);
+    assert!(stderr.contains(":8: error: missing value for required parameter `reg`"), "{}", stderr);
+
+    let stderr = assemble_failure("\t.text\n\t.rept\t2 - 3\n\tnop\n\t.endr\n");
+    assert!(stderr.contains(":2: error: negative repeat count -1"), "{}", stderr);
+}
+
+#[test]
+fn test_conditional_assembly() {
+    use fixtures::{INPUT_CONDITIONAL, INPUT_CONDITIONAL_DE1_SOC, INPUT_CONDITIONAL_LOGISIM};
+
+    assert_eq!(assemble(INPUT_CONDITIONAL), assemble(INPUT_CONDITIONAL_LOGISIM));
+    assert_eq!(assemble_with(INPUT_CONDITIONAL, &["--defsym", "DE1_SOC=1"]), assemble(INPUT_CONDITIONAL_DE1_SOC));
+    assert_eq!(assemble_with(INPUT_CONDITIONAL, &["-DDE1_SOC"]), assemble(INPUT_CONDITIONAL_DE1_SOC));
+}
+
+#[test]
+fn test_conditional_error_directive() {
+    use fixtures::INPUT_CONDITIONAL_ERROR;
+
+    assemble(INPUT_CONDITIONAL_ERROR);
+    let stderr = assemble_failure_with(INPUT_CONDITIONAL_ERROR, &["-D", "SIMULATION"]);
+    assert!(stderr.contains(":5: error: simulation builds are not supported"), "{}", stderr);
+
+    // `.word` values are checked like the conditions
+    let stderr = assemble_failure("\t.text\n\taddi\tr2, r0, 1\n\t.data\nvalue:\t.word\tSIZE * 2\n");
+    assert!(stderr.contains(":4: error: symbol `SIZE` is not defined"), "{}", stderr);
+    let stderr = assemble_failure("\t.text\n\taddi\tr2, r0, 1\n\t.data\nvalue:\t.word\t0x10000 * 0x10000\n");
+    assert!(stderr.contains(":4: error: value 4294967296 of `0x10000 * 0x10000` does not fit in 32 bits"), "{}", stderr);
+    assert_eq!(assemble("\t.text\n\t.data\nvalue:\t.word\t0xffffffff\n"), assemble("\t.text\n\t.data\nvalue:\t.word\t-1\n"));
+}
+
+#[test]
+fn test_include_files() {
+    use fixtures::{INPUT_INCLUDE_BINARY, INPUT_INCLUDE_CONSTANTS, INPUT_INCLUDE_EXPANDED, INPUT_INCLUDE_MACROS, INPUT_INCLUDE_MAIN};
+
+    let directory = tempdir().unwrap();
+    let search_directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_INCLUDE_MAIN).unwrap();
+    fs::write(directory.path().join("macros.s"), INPUT_INCLUDE_MACROS).unwrap();
+    fs::write(directory.path().join("blob.bin"), INPUT_INCLUDE_BINARY).unwrap();
+    fs::write(search_directory.path().join("constants.s"), INPUT_INCLUDE_CONSTANTS).unwrap();
+    let output_path = directory.path().join("main.out");
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("-I")
+        .arg(search_directory.path())
+        .args([directory.path().join("main.s"), output_path.clone()])
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(output_path).unwrap(), assemble(INPUT_INCLUDE_EXPANDED));
+}
+
+#[test]
+fn test_include_diagnostics() {
+    use fixtures::{INPUT_INCLUDE_CYCLE, INPUT_INCLUDE_ERROR};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_INCLUDE_CYCLE).unwrap();
+    fs::write(directory.path().join("cycle.s"), "\t.include\t\"main.s\"\n").unwrap();
+    fs::write(directory.path().join("error.s"), INPUT_INCLUDE_ERROR).unwrap();
+    fs::write(directory.path().join("error_main.s"), "\t.include\t\"error.s\"\n").unwrap();
+
+    let assert = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args([directory.path().join("main.s"), directory.path().join("main.out")])
+        .assert()
+        .failure();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("cycle.s:1: error: include cycle detected"), "{}", stderr);
+    assert!(stderr.contains("included from "), "{}", stderr);
+
+    let assert = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args([directory.path().join("error_main.s"), directory.path().join("main.out")])
+        .assert()
+        .failure();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("error.s:2: error: included error"), "{}", stderr);
+    assert!(stderr.contains("included from ") && stderr.contains("error_main.s:1"), "{}", stderr);
+
+    fs::write(directory.path().join("blob.bin"), [1, 2, 3]).unwrap();
+    let incbin = |arguments: &str| {
+        let source = format!("\t.text\n\t.data\nblob:\t.incbin\t\"blob.bin\"{}\n", arguments);
+        fs::write(directory.path().join("incbin.s"), source).unwrap();
+        let assert = Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .args([directory.path().join("incbin.s"), directory.path().join("main.out")])
+            .assert()
+            .failure();
+        String::from_utf8(assert.get_output().stderr.clone()).unwrap()
+    };
+    assert!(incbin(", -1, 2").contains("incbin.s:3: error: `.incbin` skip -1 is negative"));
+    assert!(incbin(", 1, -2").contains("incbin.s:3: error: `.incbin` count -2 is negative"));
+    assert!(incbin(", 2, 2").contains("incbin.s:3: error: `.incbin` range exceeds the 3 bytes of"));
+}
+
+#[test]
+fn test_c_preprocessor() {
+    use fixtures::{INPUT_CPP_HEADER, INPUT_CPP_MAIN};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.S"), INPUT_CPP_MAIN).unwrap();
+    fs::write(directory.path().join("board.h"), INPUT_CPP_HEADER).unwrap();
+
+    let assert = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args([directory.path().join("main.S"), directory.path().join("main.out")])
+        .assert()
+        .failure();
+    let stderr = String::from_utf8(assert.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("original.s:102: error: mapped"), "{}", stderr);
+
+    let source = INPUT_CPP_MAIN.replace("\t.error\t\"mapped\"\n", "");
+    fs::write(directory.path().join("main.s"), source).unwrap();
+    let output_path = directory.path().join("main.out");
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg("--cpp")
+        .args([directory.path().join("main.s"), output_path.clone()])
+        .assert()
+        .success();
+
+    let expected = "\t.text\nmain:\n\taddi\tsp, r0, 0x200\n\taddi\tr4, r0, 5\n\taddi\tr5, r0, 5\n";
+    assert_eq!(fs::read_to_string(output_path).unwrap(), assemble(expected));
+}
+
+#[test]
+fn test_output_format_selection() {
+    use fixtures::{INPUT_CASE_1, OUTPUT_CASE_1};
+
+    assert_eq!(assemble_with(INPUT_CASE_1, &["-O", "logisim-raw"]), OUTPUT_CASE_1);
+    assert_eq!(assemble_with(INPUT_CASE_1, &["--format=logisim-raw"]), OUTPUT_CASE_1);
+
+    let stderr = assemble_failure_with(INPUT_CASE_1, &["--format", "punched-cards"]);
+    assert!(stderr.contains("unknown output format `punched-cards`"), "{}", stderr);
+
+    let assert = Command::cargo_bin(BIN_NAME).unwrap().arg("--help").assert().success();
+    let stdout = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(stdout.contains("logisim-raw"), "{}", stdout);
+}
+
+#[test]
+fn test_output_layout_with_start_addresses() {
+    use fixtures::INPUT_CASE_1;
+
     let mut input_file = NamedTempFile::new().unwrap();
     input_file.write_all(INPUT_CASE_1.as_bytes()).unwrap();
-
     let mut output_file = NamedTempFile::new().unwrap();
 
     Command::cargo_bin(BIN_NAME)
         .unwrap()
-        .args(&[input_file.path(), output_file.path()])
+        .args([input_file.path(), output_file.path()])
+        .args(["16", "0x100"])
         .assert()
         .success();
 
     let mut actual = String::new();
     output_file.read_to_string(&mut actual).unwrap();
-    assert_eq!(actual, OUTPUT_CASE_1);
-}
\ No newline at end of file
+    let lines = actual.lines().collect::<Vec<&str>>();
+    assert!(lines[1].starts_with("16*0 07008004 01000104 00000500 "), "{}", actual);
+    assert_eq!(lines[2], "222*0 0000000a ffffffec");
+}
+
+#[test]
+fn test_intel_hex_output() {
+    use fixtures::{INPUT_OUTPUT_FORMATS, OUTPUT_INTEL_HEX, OUTPUT_INTEL_HEX_BYTES};
+
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "ihex"]), OUTPUT_INTEL_HEX);
+    let options = ["-O", "ihex", "--word-width", "8", "--record-length", "4"];
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &options), OUTPUT_INTEL_HEX_BYTES);
+}
+
+#[test]
+fn test_mif_output() {
+    use fixtures::{INPUT_OUTPUT_FORMATS, OUTPUT_MIF, OUTPUT_MIF_HALFWORDS};
+
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "mif", "--comments"]), OUTPUT_MIF);
+    let options = ["-O", "mif", "--word-width", "16", "--depth", "0x300"];
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &options), OUTPUT_MIF_HALFWORDS);
+
+    let stderr = assemble_failure_with(INPUT_OUTPUT_FORMATS, &["-O", "mif", "--depth", "16"]);
+    assert!(stderr.contains("the image needs 257 words but the memory depth is 16"));
+}
+
+#[test]
+fn test_hdl_outputs() {
+    use fixtures::{INPUT_OUTPUT_FORMATS, OUTPUT_READMEMB, OUTPUT_READMEMH, OUTPUT_VHDL_PACKAGE};
+
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "readmemh"]), OUTPUT_READMEMH);
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "readmemb"]), OUTPUT_READMEMB);
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "vhdl"]), OUTPUT_VHDL_PACKAGE);
+}
+
+#[test]
+fn test_srec_and_binary_outputs() {
+    use fixtures::{INPUT_OUTPUT_FORMATS, OUTPUT_SREC};
+
+    assert_eq!(assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "srec", "--entry", "value"]), OUTPUT_SREC);
+    let stderr = assemble_failure_with(INPUT_OUTPUT_FORMATS, &["-O", "srec", "--entry", "missing"]);
+    assert!(stderr.contains("entry symbol `missing` is not defined"));
+    assemble_with(INPUT_OUTPUT_FORMATS, &["-O", "srec", "--record-length", "252"]);
+    let stderr = assemble_failure_with(INPUT_OUTPUT_FORMATS, &["-O", "srec", "--record-length", "253"]);
+    assert!(stderr.contains("the record length (253 bytes) exceeds the 252 bytes an S1 record holds"), "{}", stderr);
+
+    let bytes = assemble_bytes_with(INPUT_OUTPUT_FORMATS, &["-O", "bin", "--fill", "0xff"]);
+    assert_eq!(bytes.len(), 0x404);
+    assert_eq!(bytes[..8], [0x04, 0x80, 0x00, 0x07, 0x06, 0xfe, 0x3f, 0x00]);
+    assert!(bytes[8..0x400].iter().all(|byte| *byte == 0xff));
+    assert_eq!(bytes[0x400..], [0x44, 0x33, 0x22, 0x11]);
+
+    // both are loaded at byte addresses, so `value` is 0x404 in `.word` and `%lo` and `br` goes back 8 bytes
+    let source = "\t.data\nptr:\t.word\tvalue\nvalue:\t.word\t7\n\t.text\nmain:\n\tldw\tr2, %lo(value)(r0)\n\tbr\tmain\n";
+    let srec = assemble_with(source, &["-O", "srec"]);
+    assert!(srec.contains("S10B00001701810006FE3F0018\nS10B04000404000007000000E1\n"), "{}", srec);
+    let bytes = assemble_bytes_with(source, &["-O", "bin"]);
+    assert_eq!(bytes[..8], [0x17, 0x01, 0x81, 0x00, 0x06, 0xfe, 0x3f, 0x00]);
+    assert_eq!(bytes[0x400..], [0x04, 0x04, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00]);
+}
+
+// Sections of a little-endian ELF32 file as (name, type, contents).
+fn read_elf_sections(bytes: &[u8]) -> Vec<(String, u32, Vec<u8>)> {
+    let half = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+    let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+    let header = |index: usize| word(32) + index * 40;
+    let names = word(header(half(50)) + 16);
+    let name = |offset: usize| read_c_string(&bytes[names + offset..]);
+
+    (0..half(48))
+        .map(|index| {
+            let (kind, offset, size) = (word(header(index) + 4), word(header(index) + 16), word(header(index) + 20));
+            let contents = if kind == 8 { vec![] } else { bytes[offset..offset + size].to_vec() };
+            (name(word(header(index))), kind as u32, contents)
+        })
+        .collect()
+}
+
+fn read_c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap();
+    String::from_utf8(bytes[..end].to_vec()).unwrap()
+}
+
+#[test]
+fn test_elf_relocatable_object() {
+    use fixtures::INPUT_ELF_OBJECT;
+
+    let bytes = assemble_bytes_with(INPUT_ELF_OBJECT, &["-O", "elf-obj"]);
+    assert_eq!(bytes[..6], [0x7f, b'E', b'L', b'F', 1, 1]);
+    assert_eq!(bytes[16..20], [1, 0, 113, 0]);
+
+    let sections = read_elf_sections(&bytes);
+    let names = sections.iter().map(|section| section.0.as_str()).collect::<Vec<&str>>();
+    let expected = ["", ".text", ".data", ".rodata", ".bss", ".rela.text", ".rela.data", ".symtab", ".strtab", ".shstrtab"];
+    assert_eq!(names, expected);
+    let contents = |name: &str| &sections.iter().find(|section| section.0 == name).unwrap().2;
+
+    // (name, binding, section index) of every named symbol
+    let symbols = contents(".symtab")
+        .chunks(16)
+        .map(|symbol| {
+            let name = u32::from_le_bytes(symbol[..4].try_into().unwrap()) as usize;
+            let section = u16::from_le_bytes([symbol[14], symbol[15]]);
+            (read_c_string(&contents(".strtab")[name..]), symbol[12] >> 4, section)
+        })
+        .filter(|symbol| !symbol.0.is_empty())
+        .collect::<Vec<(String, u8, u16)>>();
+    let expected = [
+        ("done", 0, 1),
+        ("counter", 0, 2),
+        ("message", 0, 3),
+        ("buffer", 0, 4),
+        ("main", 1, 1),
+        ("table", 1, 2),
+        ("helper", 1, 0),
+        ("hook", 2, 0),
+    ];
+    let expected = expected.iter().map(|(name, binding, section)| (name.to_string(), *binding, *section)).collect::<Vec<_>>();
+    assert_eq!(symbols, expected);
+
+    let relocation_types = |name: &str| contents(name).chunks(12).map(|relocation| relocation[4]).collect::<Vec<u8>>();
+    // HIADJ16, LO16, LO16, S16, CALL26, CALL26 then BFD_RELOC_32 for both words
+    assert_eq!(relocation_types(".rela.text"), [11, 10, 10, 1, 4, 4]);
+    assert_eq!(relocation_types(".rela.data"), [12, 12]);
+
+    let stderr = assemble_failure(INPUT_ELF_OBJECT);
+    assert!(stderr.contains("undefined symbol `helper`"), "{}", stderr);
+
+    // the contents count addresses and branch offsets in bytes, like the symbol table
+    let bytes = assemble_bytes_with("\t.text\n\tnop\nloop:\n\tbr\tloop\n\t.data\npointer:\t.word\tloop\n", &["-O", "elf-obj"]);
+    let sections = read_elf_sections(&bytes);
+    let contents = |name: &str| &sections.iter().find(|section| section.0 == name).unwrap().2;
+    let word = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let loop_value = contents(".symtab")
+        .chunks(16)
+        .find(|symbol| read_c_string(&contents(".strtab")[word(symbol) as usize..]) == "loop")
+        .map(|symbol| word(&symbol[4..]))
+        .unwrap();
+    assert_eq!(loop_value, 4);
+    assert_eq!(word(contents(".data")), loop_value);
+    assert_eq!(word(&contents(".text")[4..]) >> 6 & 0xffff, 0xfffc);
+
+    let stderr = assemble_failure("\t.data\nbuffer:\t.space\t2 - 6\n");
+    assert!(stderr.contains(":2: error: `.space` size -4 is negative"), "{}", stderr);
+    let stderr = assemble_failure("\t.data\nbuffer:\t.skip\t4, 256\n");
+    assert!(stderr.contains(":2: error: `.skip` fill value 256 does not fit in a byte"), "{}", stderr);
+    let stderr = assemble_failure("\t.data\nbuffer:\t.space\t4, -129\n");
+    assert!(stderr.contains(":2: error: `.space` fill value -129 does not fit in a byte"), "{}", stderr);
+}
+
+#[test]
+fn test_elf_executable() {
+    use fixtures::INPUT_ELF_EXECUTABLE;
+
+    let bytes = assemble_bytes_with(INPUT_ELF_EXECUTABLE, &["-O", "elf", "-g"]);
+    let half = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+    let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+    assert_eq!(half(16), 2);
+    // `_start` is the second instruction
+    assert_eq!(word(24), 4);
+
+    // (address, file size, memory size) of each `PT_LOAD` segment
+    let segments = (0..half(44))
+        .map(|index| word(28) + index * 32)
+        .map(|header| (word(header + 8), word(header + 16), word(header + 20)))
+        .collect::<Vec<(usize, usize, usize)>>();
+    assert_eq!(segments, [(0, 24, 24), (0x400, 4, 4), (0x404, 0, 8)]);
+
+    let sections = read_elf_sections(&bytes);
+    let names = sections.iter().map(|section| section.0.as_str()).collect::<Vec<&str>>();
+    assert_eq!(names, ["", ".text", ".data", ".bss", ".debug_line", ".symtab", ".strtab", ".shstrtab"]);
+
+    let bytes = assemble_bytes_with(INPUT_ELF_EXECUTABLE, &["-O", "elf", "--entry", "work"]);
+    assert_eq!(u32::from_le_bytes(bytes[24..28].try_into().unwrap()), 0x10);
+    assert!(!read_elf_sections(&bytes).iter().any(|section| section.0 == ".debug_line"));
+
+    // the contents count addresses and branch offsets in bytes, like the headers and the symbol table
+    let source = "\t.global\t_start\n\t.text\n\tnop\n_start:\n\tbr\t_start\n\tcall\t_start\n\t.data\nentry:\t.word\t_start\n";
+    let bytes = assemble_bytes_with(source, &["-O", "elf"]);
+    let word = |bytes: &[u8]| u32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let sections = read_elf_sections(&bytes);
+    let contents = |name: &str| &sections.iter().find(|section| section.0 == name).unwrap().2;
+    let start_value = contents(".symtab")
+        .chunks(16)
+        .find(|symbol| read_c_string(&contents(".strtab")[word(symbol) as usize..]) == "_start")
+        .map(|symbol| word(&symbol[4..]))
+        .unwrap();
+    assert_eq!(word(&bytes[24..]), 4);
+    assert_eq!(start_value, 4);
+    assert_eq!(word(contents(".data")), 4);
+    assert_eq!(word(&contents(".text")[4..]) >> 6 & 0xffff, 0xfffc);
+    // `call` holds the target divided by four, as on a Nios II
+    assert_eq!(word(&contents(".text")[8..]) >> 6, 1);
+}
+
+#[test]
+fn test_link_objects() {
+    use fixtures::{INPUT_LINK_MAIN, INPUT_LINK_UART};
+
+    let directory = tempdir().unwrap();
+    let object = |name: &str, source: &str| {
+        let source_path = directory.path().join(format!("{}.s", name));
+        let object_path = directory.path().join(format!("{}.o", name));
+        fs::write(&source_path, source).unwrap();
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .args(["-O", "elf-obj"])
+            .args([&source_path, &object_path])
+            .assert()
+            .success();
+        object_path.display().to_string()
+    };
+    let main = object("main", INPUT_LINK_MAIN);
+    let uart = object("uart", INPUT_LINK_UART);
+    let output_path = directory.path().join("linked.elf").display().to_string();
+    let link_with = |options: &[&str], objects: &[&str]| {
+        Command::cargo_bin(BIN_NAME).unwrap().arg("link").args(options).args(["-o", &output_path]).args(objects).assert()
+    };
+    let link = |objects: &[&str]| link_with(&["-O", "elf"], objects);
+
+    // the linked units match the same sources assembled as one, in `elf` by default and in the
+    // other formats counting bytes
+    let source = format!("{}{}", INPUT_LINK_MAIN, INPUT_LINK_UART);
+    link_with(&[], &[&main, &uart]).success();
+    assert_eq!(fs::read(&output_path).unwrap(), assemble_bytes_with(&source, &["-O", "elf"]));
+    for format in ["srec", "bin"] {
+        link_with(&["-O", format], &[&main, &uart]).success();
+        assert_eq!(fs::read(&output_path).unwrap(), assemble_bytes_with(&source, &["-O", format]), "{}", format);
+    }
+    // the objects count bytes, formats counting words would get byte branch offsets
+    let output = link_with(&["-O", "logisim-raw"], &[&main, &uart]).code(2);
+    let message = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(message.contains("the `logisim-raw` format cannot hold a linked image, use srec, bin, elf"), "{}", message);
+
+    let stderr = |output: assert_cmd::assert::Assert| String::from_utf8(output.failure().get_output().stderr.clone()).unwrap();
+    let message = stderr(link(&[&main, &uart, &uart]));
+    assert!(message.contains(&format!("duplicate symbol `uart_puts` defined in {} and {}", uart, uart)), "{}", message);
+    assert!(message.contains(&format!("duplicate symbol `banner` defined in {} and {}", uart, uart)), "{}", message);
+    let message = stderr(link(&[&main]));
+    assert!(message.contains(&format!("undefined symbol `banner` referenced by {}", main)), "{}", message);
+    assert!(message.contains(&format!("undefined symbol `uart_puts` referenced by {}", main)), "{}", message);
+}
+
+#[test]
+fn test_multiple_input_files() {
+    use fixtures::{INPUT_MULTI_FIRST, INPUT_MULTI_SECOND};
+
+    let directory = tempdir().unwrap();
+    let first_path = directory.path().join("first.s");
+    let second_path = directory.path().join("second.s");
+    let output_path = directory.path().join("program.raw");
+    fs::write(&first_path, INPUT_MULTI_FIRST).unwrap();
+    fs::write(&second_path, INPUT_MULTI_SECOND).unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .arg(&first_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg(&second_path)
+        .assert()
+        .success();
+
+    // the second file starts in `.text` again and its `.L1` is not the one of the first file
+    let expected = format!("{}\t.text\n{}", INPUT_MULTI_FIRST, INPUT_MULTI_SECOND.replace(".L1", ".L2"));
+    assert_eq!(fs::read_to_string(output_path).unwrap(), assemble(&expected));
+}
+
+#[test]
+fn test_command_line() {
+    use fixtures::{INPUT_CASE_1, OUTPUT_CASE_1};
+
+    let run = |args: &[&str], input: &str| {
+        assert_cmd::Command::cargo_bin(BIN_NAME).unwrap().args(args).write_stdin(input).assert()
+    };
+
+    // usage errors exit with 2, errors in the source with 1
+    run(&[], "").code(2);
+    run(&["--bogus", "input.s", "output.raw"], "").code(2);
+    run(&["asm", "-o", "-", "missing.s"], "").code(1);
+    run(&["asm", "-o", "-", "-"], "\t.text\n\tfoo\tr1\n").code(1);
+    run(&["--help"], "").success();
+
+    // errors in instructions and data are reported at their line
+    let stderr = |input: &str| String::from_utf8(run(&["-o", "-", "-"], input).code(1).get_output().stderr.clone()).unwrap();
+    let message = stderr("\t.text\n\tnop\n\tfoo\tr1\n");
+    assert!(message.contains(":3: error: unknown instruction `foo`"), "{}", message);
+    let message = stderr("\t.text\n\taddi\tr2, r1, %bad(x)\n");
+    assert!(message.contains(":2: error: unknown operator `%bad`"), "{}", message);
+    let message = stderr("\t.text\n\tldw\tr2, 12x(r1)\n");
+    assert!(message.contains(":2: error: invalid number `12x(r1)`"), "{}", message);
+    let message = stderr("\t.data\n\t.word\t1\n");
+    assert!(message.contains(":2: error: the first datum needs a label on its line"), "{}", message);
+
+    let stdout = |output: assert_cmd::assert::Assert| String::from_utf8(output.success().get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout(run(&["--version"], "")), format!("nios2asm {}\n", env!("CARGO_PKG_VERSION")));
+    // nothing but the output goes to the standard output
+    assert_eq!(stdout(run(&["asm", "-o", "-", "-"], INPUT_CASE_1)), OUTPUT_CASE_1);
+    assert_eq!(stdout(run(&["-q", "-o", "-", "-"], INPUT_CASE_1)), OUTPUT_CASE_1);
+
+    let binary = assemble_bytes_with("\t.text\nmain:\n\taddi\tr2, r0, 5\n\tbr\tmain\n", &["-O", "bin"]);
+    let mut disassembly = assert_cmd::Command::cargo_bin(BIN_NAME).unwrap();
+    let output = disassembly.args(["disasm", "--text-base", "0x10", "-"]).write_stdin(binary).assert();
+    // a flat binary counts bytes, like the `bin` output it comes from
+    assert_eq!(stdout(output), "00000040:\t00800144\taddi\tr2, zero, 5\n00000044:\t003ffe06\tbr\t0x40\n");
+}
+
+#[test]
+fn test_disassembler() {
+    use fixtures::{INPUT_DISASSEMBLY, OUTPUT_DISASSEMBLY};
+
+    let disassemble = |input: Vec<u8>| {
+        let mut command = assert_cmd::Command::cargo_bin(BIN_NAME).unwrap();
+        let output = command.args(["disasm", "-"]).write_stdin(input).assert().success();
+        String::from_utf8(output.get_output().stdout.clone()).unwrap()
+    };
+    let image = assemble(INPUT_DISASSEMBLY);
+    let listing = disassemble(image.clone().into_bytes());
+    assert_eq!(listing, OUTPUT_DISASSEMBLY);
+
+    // the disassembly assembles back to the same words
+    let source = listing.lines().map(|line| format!("\t{}\n", line.splitn(3, '\t').nth(2).unwrap())).collect::<String>();
+    assert_eq!(assemble(&format!("\t.text\n{}", source)), image);
+
+    // hexadecimal words are accepted as well
+    let listing = disassemble(b"00bffec4\n0x10ffc014\n".to_vec());
+    assert_eq!(listing, OUTPUT_DISASSEMBLY.lines().take(2).map(|line| format!("{}\n", line)).collect::<String>());
+}
+
+#[test]
+fn test_logisim_raw_reader() {
+    use fixtures::{INPUT_CASE_1, OUTPUT_CASE_1};
+    use nios2asm::input::{read_image, read_logisim_raw};
+
+    // the assembler output reads back as the sections it was written from
+    let segments = read_logisim_raw(OUTPUT_CASE_1).unwrap();
+    let layout = segments.iter().map(|segment| (segment.name.as_str(), segment.base, segment.words.len())).collect::<Vec<_>>();
+    assert_eq!(layout, [(".text", 0, 18), (".data", 0x100, 2)]);
+    let text = assemble_with(INPUT_CASE_1, &["-O", "readmemh"]);
+    let words = text.lines().skip(2).take(18).map(|word| u32::from_str_radix(word, 16).unwrap()).collect::<Vec<u32>>();
+    assert_eq!(segments[0].words, words);
+    assert_eq!(segments[1].words, [10, 0xffffffec]);
+
+    // explicit zeros and runs of other values are contents, `#` starts a comment
+    let image = read_image(b"v2.0 raw\n# boot code\n1*0 3*2a 0\n2*0\tff # tail\n", 0).unwrap();
+    let layout = image.segments.iter().map(|segment| (segment.base, segment.words.clone())).collect::<Vec<_>>();
+    assert_eq!(layout, [(1, vec![0x2a, 0x2a, 0x2a, 0]), (7, vec![0xff])]);
+
+    assert_eq!(read_logisim_raw("v2.0 raw\n1*0 12\n3*zz\n").err().unwrap(), "line 3: invalid entry `3*zz`");
+    assert_eq!(read_logisim_raw("v2.0 raw\n-1*5\n").err().unwrap(), "line 2: negative count in `-1*5`");
+    let message = read_logisim_raw("v2.0 raw\n2147483647*1\n").err().unwrap();
+    assert_eq!(message, "line 2: `2147483647*1` runs past the end of the address space");
+    let message = read_logisim_raw("v2.0 raw\n2147483647*0 2*0\n").err().unwrap();
+    assert_eq!(message, "line 2: `2147483647*0` runs past the end of the address space");
+    assert!(read_logisim_raw("00000000").is_err());
+}
+
+#[test]
+fn test_symbolic_disassembly() {
+    use fixtures::{INPUT_SYMBOLIC, OUTPUT_SYMBOLIC, SYMBOLS_SYMBOLIC};
+
+    let directory = tempdir().unwrap();
+    let image = directory.path().join("image.raw");
+    let symbols = directory.path().join("image.sym");
+    fs::write(&image, assemble(INPUT_SYMBOLIC)).unwrap();
+    fs::write(&symbols, SYMBOLS_SYMBOLIC).unwrap();
+
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .args(["disasm", "--symbols"])
+        .args([&symbols, &image])
+        .assert()
+        .success();
+    let source = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(source, OUTPUT_SYMBOLIC);
+    // the source assembles back to the same image
+    assert_eq!(assemble(&source), fs::read_to_string(&image).unwrap());
+
+    // words the encoder would write differently, like `ret` without `ra` in its A field, stay as they are
+    let mut command = assert_cmd::Command::cargo_bin(BIN_NAME).unwrap();
+    let output = command.args(["disasm", "--symbolic", "-"]).write_stdin("f800283a 0000283a 003fffc6\n").assert().success();
+    let source = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(source, "# assemble with --text-base 0x0\n\t.text\n\tret\n\t.word\t0x0000283a\n.L2:\n\tbr\t.L2\n");
+    assert_eq!(assemble_with(&source, &["-O", "readmemh"]), "// Generated by nios2asm\n@00000000\nf800283a\n0000283a\n003fffc6\n");
+}
+
+#[test]
+fn test_map_file() {
+    use fixtures::{INPUT_MAP, OUTPUT_MAP, OUTPUT_MAP_NM};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_MAP).unwrap();
+    let map = |format: &str| {
+        Command::cargo_bin(BIN_NAME)
+            .unwrap()
+            .current_dir(directory.path())
+            .args(["-O", "elf-obj", "-o", "main.o", "-D", "DEBUG", "--map", "main.map", "--map-format", format, "main.s"])
+            .assert()
+            .success();
+        fs::read_to_string(directory.path().join("main.map")).unwrap()
+    };
+    assert_eq!(map("text"), OUTPUT_MAP);
+    assert_eq!(map("nm"), OUTPUT_MAP_NM);
+    let json = map("json");
+    assert!(json.contains(r#"{"name": ".rodata", "base": 257, "size": 2, "end": 259}"#));
+    assert!(json.contains(
+        r#"{"name": "data1", "address": 256, "section": ".data", "kind": "data", "binding": "local", "file": "main.s", "line": 12}"#
+    ));
+    assert!(json.contains(
+        r#"{"name": "DEBUG", "address": 1, "section": "*ABS*", "kind": "constant", "binding": "local", "file": null, "line": null}"#
+    ));
+
+    // the linker writes the map of the merged image, whose ELF output counts the same addresses in bytes
+    fs::write(directory.path().join("helper.s"), "\t.text\n\t.global\thelper\nhelper:\n\tret\n").unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["-O", "elf-obj", "-o", "helper.o", "helper.s"])
+        .assert()
+        .success();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["link", "-O", "elf", "-o", "program.elf", "--map=program.nm", "--map-format=nm", "main.o", "helper.o"])
+        .assert()
+        .success();
+    let symbols = fs::read_to_string(directory.path().join("program.nm")).unwrap();
+    assert_eq!(symbols, "00000000 T main\n00000003 t loop\n00000004 T helper\n00000100 d data1\n00000101 r table\n");
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["disasm", "program.elf"])
+        .assert()
+        .success();
+    let listing = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(listing.contains("\tori\tr2, zero, 0x400\n"), "{}", listing);
+    assert!(listing.contains("00000008:\t00000100\tcall\t0x10\n0000000c:\t003fff06\tbr\t0xc\n"), "{}", listing);
+    // the symbolic disassembly names word addresses only
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["disasm", "--symbols", "program.nm", "program.elf"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_symbol_export() {
+    use fixtures::{INPUT_EXPORT, OUTPUT_EXPORT_C, OUTPUT_EXPORT_SV, OUTPUT_EXPORT_VHDL};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_EXPORT).unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["-o", "main.raw", "--export", "symbols.h", "--export", "symbols_pkg.sv", "--export=symbols_pkg.vhd", "main.s"])
+        .assert()
+        .success();
+    // local labels and constants stay out unless marked by `.export`
+    assert_eq!(fs::read_to_string(directory.path().join("symbols.h")).unwrap(), OUTPUT_EXPORT_C);
+    assert_eq!(fs::read_to_string(directory.path().join("symbols_pkg.sv")).unwrap(), OUTPUT_EXPORT_SV);
+    assert_eq!(fs::read_to_string(directory.path().join("symbols_pkg.vhd")).unwrap(), OUTPUT_EXPORT_VHDL);
+
+    let output = assemble_failure_with(&format!("{}\t.export\tmissing\n", INPUT_EXPORT), &["--export", "symbols.h"]);
+    assert!(output.contains("exported symbol `missing` is not defined"));
+    // names must stay apart once made identifiers, without regard to case in VHDL
+    let output = assemble_failure_with("\t.text\n\t.equ\tdot.name, 1\n\t.equ\tdot_name, 2\n\t.export\tdot.name, dot_name\n", &["--export", "symbols.h"]);
+    assert!(output.contains("symbols `dot.name` and `dot_name` are both exported as `SYM_dot_name`"), "{}", output);
+    let source = "\t.text\n\t.equ\tLIMIT, 1\n\t.equ\tlimit, 2\n\t.export\tLIMIT, limit\n";
+    assemble_with(source, &["--export", &directory.path().join("limits.h").display().to_string()]);
+    let output = assemble_failure_with(source, &["--export", "limits.vhd"]);
+    assert!(output.contains("symbols `LIMIT` and `limit` are both exported as `SYM_limit`"), "{}", output);
+    let mut command = Command::cargo_bin(BIN_NAME).unwrap();
+    command.args(["--export", "symbols.txt", "-o", "main.raw", "main.s"]).assert().code(2);
+}
+
+#[test]
+fn test_emit_json() {
+    use fixtures::{INPUT_EMIT, OUTPUT_EMIT};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_EMIT).unwrap();
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["--emit", "json", "-o", "main.json", "main.s"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(directory.path().join("main.json")).unwrap(), OUTPUT_EMIT);
+
+    // errors are reported in the document too
+    fs::write(directory.path().join("error.s"), "\t.text\n\tcall\tnowhere\n").unwrap();
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["--emit=json", "-o", "-", "error.s"])
+        .assert()
+        .failure();
+    let document = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(document.contains(r#"{"severity": "error", "message": "undefined symbol `nowhere`", "file": "error.s", "line": 2}"#));
+    // an instruction that does not encode still leaves the others in the document
+    fs::write(directory.path().join("error.s"), "\t.text\n\tfoo\tr1\n\tnop\n").unwrap();
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["--emit", "json", "-o", "-", "error.s"])
+        .assert()
+        .code(1);
+    let document = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(document.contains(r#"{"severity": "error", "message": "unknown instruction `foo`", "file": "error.s", "line": 2}"#));
+    assert!(document.contains(r#"{"address": 1, "source": "nop", "mnemonic": "add""#), "{}", document);
+    fs::write(directory.path().join("error.s"), "\t.text\n\t.error \"stop\"\n").unwrap();
+    let output = Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["--emit", "json", "-o", "-", "error.s"])
+        .assert()
+        .failure();
+    let document = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert!(document.starts_with("{\n  \"statements\": [],\n"));
+    assert!(document.contains(r#"{"severity": "error", "message": "stop", "file": "error.s", "line": 2}"#));
+}
+
+#[test]
+fn test_simulator() {
+    use fixtures::{INPUT_SIMULATOR, OUTPUT_SIMULATOR};
+    use nios2asm::constants::INSTRUCTION_TABLE;
+    use nios2asm::simulator::{Simulator, Stop};
+
+    let simulate = |image: &[u8], args: &[&str], code: i32| {
+        let mut image_file = NamedTempFile::new().unwrap();
+        image_file.write_all(image).unwrap();
+        let output = Command::cargo_bin(BIN_NAME).unwrap().arg("sim").args(args).arg(image_file.path()).assert().code(code);
+        let output = output.get_output();
+        (String::from_utf8(output.stdout.clone()).unwrap(), String::from_utf8(output.stderr.clone()).unwrap())
+    };
+
+    let image = assemble(INPUT_SIMULATOR);
+    assert_eq!(simulate(image.as_bytes(), &[], 0).0, OUTPUT_SIMULATOR);
+    let (trace, error) = simulate(image.as_bytes(), &["--trace", "--max-steps", "2"], 1);
+    assert!(trace.starts_with("00000000: 008001c4  addi\tr2, zero, 7\n00000001: 00ffff44  addi\tr3, zero, -3\nzero 00000000"));
+    assert_eq!(error, "error: no `break` after 2 instructions\n");
+
+    // an ELF executable starts at its entry point, and its addresses count bytes
+    let elf = assemble_bytes_with(INPUT_SIMULATOR, &["-O", "elf", "--text-base", "0x40"]);
+    assert!(simulate(&elf, &[], 0).0.starts_with("break at 0x00000148 after 20 instructions\n"));
+    let source = "\t.text\n_start:\n\taddi\tr3, r0, msg\n\tldbu\tr2, 1(r3)\n\tldh\tr4, 2(r3)\n\tstb\tr2, 0(r3)\n\tldw\tr5, 0(r3)\n\tbreak\n\t.data\nmsg:\t.word\t0x6c6c6548\n";
+    let (registers, _) = simulate(&assemble_bytes_with(source, &["-O", "elf"]), &[], 0);
+    assert!(registers.contains("r2 00000065    r3 00000400\n  r4 00006c6c    r5 6c6c6565"), "{}", registers);
+    // sections are loaded whole words at a time
+    let mut elf = assemble_bytes_with(source, &["-O", "elf"]);
+    let data = u32::from_le_bytes(elf[32..36].try_into().unwrap()) as usize + 2 * 40;
+    elf[data + 12] = 2;
+    let (_, error) = simulate(&elf, &[], 1);
+    assert!(error.contains("section `.data` address 0x402 is not word-aligned"), "{}", error);
+    let (_, error) = simulate(b"v2.0 raw\nffffffff\n", &[], 1);
+    assert_eq!(error, "error: invalid instruction 0xffffffff at 0x00000000\n");
+
+    let mut simulator = Simulator::from_bytes(image.as_bytes(), 0).unwrap();
+    assert_eq!(simulator.run(100), Stop::BREAK);
+    assert_eq!((simulator.pc, simulator.registers[12], simulator.registers[31]), (0x12, 49, 0xb));
+
+    // every instruction runs with all its fields zero, `break` stopping before it
+    for instruction in INSTRUCTION_TABLE.values() {
+        let mut simulator = Simulator::new(0);
+        simulator.memory.write_word(0, (instruction.opx << 11 | instruction.opcode) as u32);
+        let expected = if instruction.name == "break" { Some(Stop::BREAK) } else { None };
+        assert_eq!(simulator.step(), expected, "{}", instruction.name);
+    }
+}
+
+#[test]
+fn test_simulator_exceptions() {
+    use fixtures::INPUT_SIMULATOR_EXCEPTIONS;
+    use nios2asm::simulator::{Simulator, BSTATUS, CAUSE_SUPERVISOR_ONLY, ESTATUS, EXCEPTION, STATUS, STATUS_PIE, STATUS_U};
+
+    let mut image_file = NamedTempFile::new().unwrap();
+    image_file.write_all(assemble(INPUT_SIMULATOR_EXCEPTIONS).as_bytes()).unwrap();
+    let simulate = |args: &[&str]| {
+        let output = Command::cargo_bin(BIN_NAME).unwrap().arg("sim").args(args).arg(image_file.path()).assert().success();
+        String::from_utf8(output.get_output().stdout.clone()).unwrap()
+    };
+
+    // `trap`, the unimplemented `mul` and IRQ 3 each run the handler
+    let output = simulate(&["--irq", "3@24", "--unimplemented", "mul,div"]);
+    assert!(output.starts_with("break at 0x00000016 after 32 instructions\n"));
+    assert!(output.contains(" r20 00000008   r21 00000003   r22 00000001"));
+    assert!(output.contains("status 00000001  estatus 00000001  bstatus 00000000  ienable 00000008  ipending 00000000  exception 00000008\n"));
+    // without them, `mul` runs and the loop waits for the interrupt `ipending` shows
+    let output = simulate(&["--irq", "3@100"]);
+    assert!(output.starts_with("break at 0x00000016 after 108 instructions\n"));
+    assert!(output.contains(" r20 00000008   r21 00000002"));
+    // starting at `main` skips the `br`
+    let output = simulate(&["--reset-address", "0xf", "--irq", "3@23", "--unimplemented", "mul"]);
+    assert!(output.starts_with("break at 0x00000016 after 31 instructions\n"));
+    assert!(output.contains(" r20 00000008   r21 00000003"));
+
+    // `break` saves `status` and the return address for `bret`, user mode refuses `rdctl`
+    let mut simulator = Simulator::new(0x20);
+    simulator.memory.write_word(0x20 * 4, 0x003da03a);
+    simulator.memory.write_word(0x21 * 4, 0x0009303a);
+    simulator.memory.write_word(0x30 * 4, 0xf000483a);
+    simulator.break_address = Some(0x30);
+    simulator.exception_address = 0x40;
+    simulator.control[STATUS] = STATUS_PIE | STATUS_U;
+    assert_eq!(simulator.step(), None);
+    assert_eq!((simulator.pc, simulator.registers[30], simulator.control[BSTATUS], simulator.control[STATUS]), (0x30, 0x21, 3, 0));
+    assert_eq!(simulator.step(), None);
+    assert_eq!((simulator.pc, simulator.control[STATUS]), (0x21, STATUS_PIE | STATUS_U));
+    assert_eq!(simulator.step(), None);
+    assert_eq!((simulator.pc, simulator.registers[29], simulator.registers[4]), (0x40, 0x22, 0));
+    assert_eq!((simulator.control[EXCEPTION], simulator.control[ESTATUS]), (CAUSE_SUPERVISOR_ONLY << 2, 3));
+}
+
+#[test]
+fn test_explain() {
+    use fixtures::OUTPUT_EXPLAIN;
+
+    let explain = |args: &[&str]| {
+        let output = Command::cargo_bin(BIN_NAME).unwrap().arg("explain").args(args).assert().success();
+        String::from_utf8(output.get_output().stdout.clone()).unwrap()
+    };
+    assert_eq!(explain(&["addi", "r4,", "r0,", "10"]), OUTPUT_EXPLAIN);
+    assert_eq!(explain(&["01000284"]), OUTPUT_EXPLAIN);
+
+    let listing = explain(&["--address", "0x10", "bgeu r2, r3, 0xf"]);
+    assert!(listing.contains("IMM16  21..6  1111111111111110                65534  0xfffe     target 0xf\n"));
+    let listing = explain(&["0x000530fa"]);
+    assert!(listing.starts_with("rdctl\tr2, ienable\nformat: R\nbinary: 00000 00000 00010 100110 00011 111010\n"));
+    assert!(listing.contains("OPX    16..11 100110                             38  0x26       rdctl\n"));
+
+    let output = Command::cargo_bin(BIN_NAME).unwrap().args(["explain", "br", "loop"]).assert().code(1);
+    assert_eq!(String::from_utf8(output.get_output().stderr.clone()).unwrap(), "error: undefined symbol `loop`\n");
+}
+
+#[test]
+fn test_listing_file() {
+    use fixtures::{INPUT_LISTING, INPUT_LISTING_INCLUDE, OUTPUT_LISTING};
+
+    let directory = tempdir().unwrap();
+    fs::write(directory.path().join("main.s"), INPUT_LISTING).unwrap();
+    fs::write(directory.path().join("defs.inc"), INPUT_LISTING_INCLUDE).unwrap();
+
+    Command::cargo_bin(BIN_NAME)
+        .unwrap()
+        .current_dir(directory.path())
+        .args(["-a", "main.lst", "-o", "main.raw", "main.s"])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(directory.path().join("main.lst")).unwrap(), OUTPUT_LISTING);
+    // the listing does not change the output
+    let output = fs::read_to_string(directory.path().join("main.raw")).unwrap();
+    assert_eq!(output, assemble(&INPUT_LISTING.replace("\t.include \"defs.inc\"", INPUT_LISTING_INCLUDE)));
+}